@@ -0,0 +1,275 @@
+use crate::factor::{factor, Factorization};
+use crate::gcd::lcm;
+use crate::mparith::{BigInt, Pow};
+use std::borrow::Cow;
+
+/// Arguments accepted by the arithmetic functions: a nonzero BigInt, which is factored with
+/// `factor`, or a factorization computed beforehand. The sign is ignored.
+pub trait Factored {
+    fn factorization(&self) -> Cow<'_, Factorization>;
+}
+
+impl Factored for BigInt {
+    fn factorization(&self) -> Cow<'_, Factorization> {
+        Cow::Owned(factor(self))
+    }
+}
+
+impl Factored for Factorization {
+    fn factorization(&self) -> Cow<'_, Factorization> {
+        Cow::Borrowed(self)
+    }
+}
+
+fn prime_power(p: &BigInt, k: u32) -> BigInt {
+    p.pow(&BigInt::from_u64(k as u64))
+}
+
+/// Euler's totient phi(n), the number of integers in [1, n] coprime to n.
+pub fn totient<T: Factored + ?Sized>(n: &T) -> BigInt {
+    let one = BigInt::from_u64(1);
+    let mut res = one.clone();
+    for (p, k) in &n.factorization().factors {
+        res = res * prime_power(p, k - 1) * (p - &one);
+    }
+    res
+}
+
+/// Carmichael's function lambda(n), the exponent of the group of units modulo n: the
+/// smallest m >= 1 with a^m = 1 (mod n) for every a coprime to n.
+pub fn carmichael<T: Factored + ?Sized>(n: &T) -> BigInt {
+    let one = BigInt::from_u64(1);
+    let two = BigInt::from_u64(2);
+    let mut res = one.clone();
+    for (p, k) in &n.factorization().factors {
+        // lambda(2^k) = 2^(k - 2) for k >= 3, lambda(p^k) = phi(p^k) otherwise
+        let l = if *p == two && *k >= 3 {
+            prime_power(p, k - 2)
+        } else {
+            prime_power(p, k - 1) * (p - &one)
+        };
+        res = lcm(&res, &l);
+    }
+    res
+}
+
+/// The divisor function sigma_k(n), the sum of the k-th powers of the divisors of n; sigma_0
+/// counts the divisors and sigma_1 adds them up.
+pub fn sigma<T: Factored + ?Sized>(n: &T, k: u32) -> BigInt {
+    let one = BigInt::from_u64(1);
+    let mut res = one.clone();
+    for (p, e) in &n.factorization().factors {
+        if k == 0 {
+            res = res * BigInt::from_u64(*e as u64 + 1);
+        } else {
+            // 1 + p^k + ... + p^(ke) = (p^(k(e + 1)) - 1) / (p^k - 1)
+            let pk = prime_power(p, k);
+            res = res * ((prime_power(&pk, e + 1) - &one) / (pk - &one));
+        }
+    }
+    res
+}
+
+/// The number of divisors of n, sigma_0(n).
+pub fn divisor_count<T: Factored + ?Sized>(n: &T) -> BigInt {
+    sigma(n, 0)
+}
+
+/// The Möbius function mu(n): 0 if n has a square factor, otherwise (-1)^(number of prime
+/// factors).
+pub fn mobius<T: Factored + ?Sized>(n: &T) -> i32 {
+    let f = n.factorization();
+    if f.factors.iter().any(|&(_, k)| k > 1) {
+        0
+    } else if f.factors.len().is_multiple_of(2) {
+        1
+    } else {
+        -1
+    }
+}
+
+/// Liouville's function lambda(n) = (-1)^Omega(n), where Omega counts the prime factors
+/// with multiplicity.
+pub fn liouville<T: Factored + ?Sized>(n: &T) -> i32 {
+    let omega: u64 = n
+        .factorization()
+        .factors
+        .iter()
+        .map(|&(_, k)| k as u64)
+        .sum();
+    if omega.is_multiple_of(2) {
+        1
+    } else {
+        -1
+    }
+}
+
+/// The radical of n, the product of its distinct prime factors.
+pub fn radical<T: Factored + ?Sized>(n: &T) -> BigInt {
+    n.factorization()
+        .factors
+        .iter()
+        .fold(BigInt::from_u64(1), |acc, (p, _)| acc * p)
+}
+
+/// Returns an iterator over the positive divisors of n.
+///
+/// The divisors are not produced in increasing order: the exponent of the smallest prime
+/// varies fastest, so the divisors of 12 come out as 1, 2, 4, 3, 6, 12.
+pub fn divisors<T: Factored + ?Sized>(n: &T) -> Divisors {
+    let powers = n
+        .factorization()
+        .factors
+        .iter()
+        .map(|(p, k)| {
+            let mut row = vec![BigInt::from_u64(1)];
+            for i in 0..*k as usize {
+                let next = &row[i] * p;
+                row.push(next);
+            }
+            row
+        })
+        .collect::<Vec<_>>();
+    Divisors {
+        exponents: vec![0; powers.len()],
+        powers,
+        done: false,
+    }
+}
+
+/// Iterator over the divisors of an integer, created by `divisors`.
+#[derive(Debug, Clone)]
+pub struct Divisors {
+    // powers[i][e] = p_i^e for e up to the multiplicity of p_i
+    powers: Vec<Vec<BigInt>>,
+    exponents: Vec<usize>,
+    done: bool,
+}
+
+impl Iterator for Divisors {
+    type Item = BigInt;
+
+    fn next(&mut self) -> Option<BigInt> {
+        if self.done {
+            return None;
+        }
+        let divisor = self
+            .exponents
+            .iter()
+            .zip(self.powers.iter())
+            .fold(BigInt::from_u64(1), |acc, (&e, row)| acc * &row[e]);
+        // advance the exponents like an odometer
+        self.done = true;
+        for (e, row) in self.exponents.iter_mut().zip(self.powers.iter()) {
+            if *e + 1 < row.len() {
+                *e += 1;
+                self.done = false;
+                break;
+            }
+            *e = 0;
+        }
+        Some(divisor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::factor::{factor, Factorization};
+    use crate::mparith::{build_bigint, BigInt};
+
+    fn gcd(a: u64, b: u64) -> u64 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    #[test]
+    fn arithmetic_functions_test() {
+        for n in 1_u64..=200 {
+            let big = BigInt::from_u64(n);
+            let divisors: Vec<u64> = (1..=n).filter(|d| n % d == 0).collect();
+            let mut found: Vec<BigInt> = super::divisors(&big).collect();
+            found.sort();
+            let expected: Vec<BigInt> = divisors.iter().map(|&d| BigInt::from_u64(d)).collect();
+            assert_eq!(expected, found);
+
+            let phi = (1..=n).filter(|&a| gcd(a, n) == 1).count() as u64;
+            assert_eq!(BigInt::from_u64(phi), super::totient(&big));
+            let lambda = (1..)
+                .find(|&m| {
+                    (1..=n)
+                        .filter(|&a| gcd(a, n) == 1)
+                        .all(|a| (0..m).fold(1, |acc, _| acc * a % n) == 1 % n)
+                })
+                .unwrap();
+            assert_eq!(BigInt::from_u64(lambda), super::carmichael(&big), "{n}");
+            assert_eq!(
+                BigInt::from_u64(divisors.len() as u64),
+                super::divisor_count(&big)
+            );
+            assert_eq!(
+                BigInt::from_u64(divisors.iter().sum()),
+                super::sigma(&big, 1)
+            );
+            assert_eq!(
+                BigInt::from_u64(divisors.iter().map(|d| d * d * d).sum()),
+                super::sigma(&big, 3)
+            );
+
+            let f = factor(&big);
+            let squarefree = f.factors.iter().all(|&(_, k)| k == 1);
+            let omega: u32 = f.factors.iter().map(|&(_, k)| k).sum();
+            let mu = if squarefree {
+                1 - 2 * (f.factors.len() as i32 % 2)
+            } else {
+                0
+            };
+            assert_eq!(mu, super::mobius(&f));
+            assert_eq!(1 - 2 * (omega as i32 % 2), super::liouville(&f));
+            let rad: u64 = (1..=n)
+                .filter(|&p| n % p == 0 && (2..p).all(|d| p % d != 0) && p > 1)
+                .product();
+            assert_eq!(BigInt::from_u64(rad), super::radical(&f));
+        }
+    }
+
+    #[test]
+    fn arithmetic_functions_large_test() {
+        // 2^64 - 1 = 3 * 5 * 17 * 257 * 641 * 65537 * 6700417
+        let n = build_bigint("18446744073709551615");
+        assert_eq!(build_bigint("9208981628670443520"), super::totient(&n));
+        assert_eq!(build_bigint("17153064960"), super::carmichael(&n));
+        assert_eq!(build_bigint("31421980989189888768"), super::sigma(&n, 1));
+        assert_eq!(BigInt::from_u64(128), super::divisor_count(&n));
+        assert_eq!(-1, super::mobius(&n));
+        assert_eq!(n, super::radical(&n));
+
+        // 2^10 * 3^5 * (10^20 + 39)^2, given by its factorization
+        let f = Factorization {
+            sgn: 1,
+            factors: vec![
+                (BigInt::from_u64(2), 10),
+                (BigInt::from_u64(3), 5),
+                (build_bigint("100000000000000000039"), 2),
+            ],
+        };
+        assert_eq!(
+            build_bigint("829440000000000000638668800000000000122923008"),
+            super::totient(&f)
+        );
+        assert_eq!(
+            build_bigint("34560000000000000026611200000000000005121792"),
+            super::carmichael(&f)
+        );
+        assert_eq!(
+            build_bigint("9287584943000000014488632511080000008476778777476100002204445436560822000215004155024021090"),
+            super::sigma(&f, 2)
+        );
+        assert_eq!(BigInt::from_u64(198), super::divisor_count(&f));
+        assert_eq!(198, super::divisors(&f).count());
+        assert_eq!(0, super::mobius(&f));
+        assert_eq!(-1, super::liouville(&f));
+    }
+}
@@ -0,0 +1,474 @@
+use crate::mparith::{abs, BigInt};
+use crate::rounding::{div_round, parse_decimal, pow10, RoundingMode};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops;
+use std::str::FromStr;
+
+/// An exact decimal number unscaled * 10^-scale.
+///
+/// The scale is part of the value's representation, so 1.5 and 1.50 are different
+/// `BigDecimal`s that print differently, but they compare (and hash) as equal. Sums keep the
+/// larger scale of their operands and products add the scales, so addition, subtraction and
+/// multiplication are exact. Division and reducing the scale need a `RoundingMode`.
+#[derive(Debug, Clone)]
+pub struct BigDecimal {
+    unscaled: BigInt,
+    scale: i64,
+}
+
+impl BigDecimal {
+    pub fn new(unscaled: &BigInt, scale: i64) -> BigDecimal {
+        BigDecimal {
+            unscaled: unscaled.clone(),
+            scale,
+        }
+    }
+
+    /// The integer n with scale 0.
+    pub fn from_bigint(n: &BigInt) -> BigDecimal {
+        BigDecimal::new(n, 0)
+    }
+
+    pub fn unscaled(&self) -> &BigInt {
+        &self.unscaled
+    }
+
+    pub fn scale(&self) -> i64 {
+        self.scale
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.unscaled.sgn == 0
+    }
+
+    /// The same value with the given scale, rounded when digits are dropped.
+    pub fn rescale(&self, scale: i64, mode: RoundingMode) -> BigDecimal {
+        let unscaled = if scale >= self.scale {
+            &self.unscaled * pow10((scale - self.scale) as u64)
+        } else {
+            div_round(&self.unscaled, &pow10((self.scale - scale) as u64), mode)
+        };
+        BigDecimal { unscaled, scale }
+    }
+
+    /// The same value with the smallest scale that represents it exactly, with scale 0 for
+    /// zero.
+    pub fn strip_trailing_zeros(&self) -> BigDecimal {
+        if self.is_zero() {
+            return BigDecimal::from_bigint(&BigInt::from_u64(0));
+        }
+        let mut unscaled = self.unscaled.clone();
+        let mut scale = self.scale;
+        while unscaled.rem_small(10) == 0 {
+            unscaled = unscaled / BigInt::from_u64(10);
+            scale -= 1;
+        }
+        BigDecimal { unscaled, scale }
+    }
+
+    /// self / other rounded to the given scale, which panics if other is zero.
+    pub fn div_rounded(&self, other: &BigDecimal, scale: i64, mode: RoundingMode) -> BigDecimal {
+        if other.is_zero() {
+            panic!("Divide by zero error");
+        }
+        // unscaled = self / other * 10^scale = (u1 / u2) 10^(scale - s1 + s2)
+        let mut num = self.unscaled.clone();
+        let mut den = other.unscaled.clone();
+        if den.sgn == -1 {
+            num = -num;
+            den = -den;
+        }
+        let k = scale - self.scale + other.scale;
+        if k >= 0 {
+            num = num * pow10(k as u64);
+        } else {
+            den = den * pow10(k.unsigned_abs());
+        }
+        BigDecimal {
+            unscaled: div_round(&num, &den, mode),
+            scale,
+        }
+    }
+
+    // the exponent of the leading digit, as in d.ddd * 10^adjusted
+    fn adjusted(&self) -> i64 {
+        if self.is_zero() {
+            return -self.scale;
+        }
+        // floor(log10 |u|) is floor((bits - 1) log10 2) or one more, the small margin keeps
+        // rounding in the estimate from overshooting, and one comparison settles it
+        let u = abs(&self.unscaled);
+        let estimate = (self.unscaled.bits() - 1) as f64 * std::f64::consts::LOG10_2 - 1e-6;
+        let mut digits = estimate.max(0.0) as u64;
+        let mut next = pow10(digits + 1);
+        while next <= u {
+            digits += 1;
+            next = next * BigInt::from_u64(10);
+        }
+        digits as i64 - self.scale
+    }
+}
+
+fn add(x: &BigDecimal, y: &BigDecimal) -> BigDecimal {
+    let scale = x.scale.max(y.scale);
+    let unscaled = x.rescale(scale, RoundingMode::Down).unscaled
+        + y.rescale(scale, RoundingMode::Down).unscaled;
+    BigDecimal { unscaled, scale }
+}
+
+fn mul(x: &BigDecimal, y: &BigDecimal) -> BigDecimal {
+    BigDecimal {
+        unscaled: &x.unscaled * &y.unscaled,
+        scale: x.scale + y.scale,
+    }
+}
+
+impl ops::Add<BigDecimal> for BigDecimal {
+    type Output = BigDecimal;
+
+    fn add(self, b: BigDecimal) -> BigDecimal {
+        add(&self, &b)
+    }
+}
+
+impl ops::Add<&BigDecimal> for BigDecimal {
+    type Output = BigDecimal;
+
+    fn add(self, b: &BigDecimal) -> BigDecimal {
+        add(&self, b)
+    }
+}
+
+impl ops::Add<BigDecimal> for &BigDecimal {
+    type Output = BigDecimal;
+
+    fn add(self, b: BigDecimal) -> BigDecimal {
+        add(self, &b)
+    }
+}
+
+impl ops::Add<&BigDecimal> for &BigDecimal {
+    type Output = BigDecimal;
+
+    fn add(self, b: &BigDecimal) -> BigDecimal {
+        add(self, b)
+    }
+}
+
+impl ops::Sub<BigDecimal> for BigDecimal {
+    type Output = BigDecimal;
+
+    fn sub(self, b: BigDecimal) -> BigDecimal {
+        add(&self, &-&b)
+    }
+}
+
+impl ops::Sub<&BigDecimal> for BigDecimal {
+    type Output = BigDecimal;
+
+    fn sub(self, b: &BigDecimal) -> BigDecimal {
+        add(&self, &-b)
+    }
+}
+
+impl ops::Sub<BigDecimal> for &BigDecimal {
+    type Output = BigDecimal;
+
+    fn sub(self, b: BigDecimal) -> BigDecimal {
+        add(self, &-&b)
+    }
+}
+
+impl ops::Sub<&BigDecimal> for &BigDecimal {
+    type Output = BigDecimal;
+
+    fn sub(self, b: &BigDecimal) -> BigDecimal {
+        add(self, &-b)
+    }
+}
+
+impl ops::Mul<BigDecimal> for BigDecimal {
+    type Output = BigDecimal;
+
+    fn mul(self, b: BigDecimal) -> BigDecimal {
+        mul(&self, &b)
+    }
+}
+
+impl ops::Mul<&BigDecimal> for BigDecimal {
+    type Output = BigDecimal;
+
+    fn mul(self, b: &BigDecimal) -> BigDecimal {
+        mul(&self, b)
+    }
+}
+
+impl ops::Mul<BigDecimal> for &BigDecimal {
+    type Output = BigDecimal;
+
+    fn mul(self, b: BigDecimal) -> BigDecimal {
+        mul(self, &b)
+    }
+}
+
+impl ops::Mul<&BigDecimal> for &BigDecimal {
+    type Output = BigDecimal;
+
+    fn mul(self, b: &BigDecimal) -> BigDecimal {
+        mul(self, b)
+    }
+}
+
+impl ops::Neg for BigDecimal {
+    type Output = BigDecimal;
+
+    fn neg(self) -> BigDecimal {
+        -&self
+    }
+}
+
+impl ops::Neg for &BigDecimal {
+    type Output = BigDecimal;
+
+    fn neg(self) -> BigDecimal {
+        BigDecimal {
+            unscaled: -&self.unscaled,
+            scale: self.scale,
+        }
+    }
+}
+
+impl PartialEq for BigDecimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for BigDecimal {}
+
+impl Hash for BigDecimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // consistent with Eq, which ignores trailing zeros
+        let stripped = self.strip_trailing_zeros();
+        stripped.unscaled.hash(state);
+        stripped.scale.hash(state);
+    }
+}
+
+impl Ord for BigDecimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let sign = self.unscaled.sgn;
+        if sign != other.unscaled.sgn || sign == 0 {
+            return sign.cmp(&other.unscaled.sgn);
+        }
+        // leading digits in different places order the magnitudes without aligning scales
+        // that may be far apart
+        let by_magnitude = self.adjusted().cmp(&other.adjusted());
+        if by_magnitude == Ordering::Equal {
+            let scale = self.scale.max(other.scale);
+            let a = self.rescale(scale, RoundingMode::Down);
+            let b = other.rescale(scale, RoundingMode::Down);
+            return a.unscaled.cmp(&b.unscaled);
+        }
+        if sign == 1 {
+            by_magnitude
+        } else {
+            by_magnitude.reverse()
+        }
+    }
+}
+
+impl PartialOrd for BigDecimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Shown like Java's `BigDecimal.toString`: in plain notation such as "-12.50" when the scale
+/// is not negative and the leading digit is at most 6 places after the point, and otherwise
+/// as "1.23E+8" or "5E-9" with all the unscaled digits.
+impl fmt::Display for BigDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = abs(&self.unscaled).to_string();
+        let sign = if self.unscaled.sgn == -1 { "-" } else { "" };
+        let adjusted = digits.len() as i64 - 1 - self.scale;
+        if self.scale >= 0 && adjusted >= -6 {
+            let scale = self.scale as usize;
+            if scale == 0 {
+                write!(f, "{sign}{digits}")
+            } else if digits.len() > scale {
+                let (int, frac) = digits.split_at(digits.len() - scale);
+                write!(f, "{sign}{int}.{frac}")
+            } else {
+                let zeros = "0".repeat(scale - digits.len());
+                write!(f, "{sign}0.{zeros}{digits}")
+            }
+        } else {
+            let (first, rest) = digits.split_at(1);
+            let point = if rest.is_empty() { "" } else { "." };
+            let plus = if adjusted >= 0 { "+" } else { "" };
+            write!(f, "{sign}{first}{point}{rest}E{plus}{adjusted}")
+        }
+    }
+}
+
+/// The error returned when a string is not a valid decimal number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDecimalError;
+
+impl fmt::Display for ParseDecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid decimal number")
+    }
+}
+
+/// Parses numbers such as "12.50", "-.5" or "1.23E+8", keeping every digit given, so "12.50"
+/// has scale 2 and "1.23E+8" has unscaled value 123 and scale -6.
+impl FromStr for BigDecimal {
+    type Err = ParseDecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (unscaled, exponent) = parse_decimal(s).ok_or(ParseDecimalError)?;
+        let scale = exponent.checked_neg().ok_or(ParseDecimalError)?;
+        Ok(BigDecimal { unscaled, scale })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BigDecimal;
+    use crate::mparith::BigInt;
+    use crate::rounding::RoundingMode::*;
+    use std::collections::HashSet;
+
+    fn d(s: &str) -> BigDecimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let x = &d("0.1") + &d("0.2");
+        assert_eq!(d("0.3"), x);
+        assert_eq!(1, x.scale());
+        let y = &d("12.50") - &d("0.125");
+        assert_eq!("12.375", y.to_string());
+        let z = d("1.5") * d("-2.25");
+        assert_eq!((&BigInt::from_i64(-3375), 3), (z.unscaled(), z.scale()));
+        assert_eq!("-3.375", z.to_string());
+        assert_eq!("3.375", (-z).to_string());
+        assert_eq!("1.20E+3", (d("1.2E+3") * d("1.0")).to_string());
+    }
+
+    #[test]
+    fn rounding_test() {
+        let (one, three) = (d("1"), d("3"));
+        assert_eq!("0.33333", one.div_rounded(&three, 5, HalfEven).to_string());
+        assert_eq!(
+            "-0.66667",
+            d("-2").div_rounded(&three, 5, HalfUp).to_string()
+        );
+        assert_eq!(
+            "-0.66666",
+            d("2").div_rounded(&d("-3"), 5, Down).to_string()
+        );
+        assert_eq!(
+            "2.5E+2",
+            d("1000").div_rounded(&d("4"), -1, HalfEven).to_string()
+        );
+        assert_eq!("40", d("1E+2").div_rounded(&d("2.5"), 0, Floor).to_string());
+        // 2.5, -2.5, 3.5 and 2.51 to an integer
+        let cases = [
+            (HalfEven, ["2", "-2", "4", "3"]),
+            (HalfUp, ["3", "-3", "4", "3"]),
+            (Down, ["2", "-2", "3", "2"]),
+            (Ceiling, ["3", "-2", "4", "3"]),
+            (Floor, ["2", "-3", "3", "2"]),
+        ];
+        for (mode, expected) in cases {
+            for (x, e) in ["2.5", "-2.5", "3.5", "2.51"].iter().zip(expected) {
+                assert_eq!(e, d(x).rescale(0, mode).to_string());
+            }
+        }
+        assert_eq!("1.2500", d("1.25").rescale(4, Down).to_string());
+        assert_eq!("1E+3", d("1499").rescale(-3, HalfUp).to_string());
+    }
+
+    #[test]
+    fn string_test() {
+        let cases = [
+            ("0", 0, 0, "0"),
+            ("-0", 0, 0, "0"),
+            ("0.00", 0, 2, "0.00"),
+            ("123", 123, 0, "123"),
+            ("-123", -123, 0, "-123"),
+            ("1.23E3", 123, -1, "1.23E+3"),
+            ("1.23e+3", 123, -1, "1.23E+3"),
+            ("12.3E+7", 123, -6, "1.23E+8"),
+            ("12.0", 120, 1, "12.0"),
+            ("0.00123", 123, 5, "0.00123"),
+            ("-1.23E-12", -123, 14, "-1.23E-12"),
+            ("1234.5E-4", 12345, 5, "0.12345"),
+            ("0E+7", 0, -7, "0E+7"),
+            ("-.5", -5, 1, "-0.5"),
+            ("5E-7", 5, 7, "5E-7"),
+            ("5E-6", 5, 6, "0.000005"),
+        ];
+        for (s, unscaled, scale, shown) in cases {
+            let x = d(s);
+            assert_eq!(
+                (&BigInt::from_i64(unscaled), scale),
+                (x.unscaled(), x.scale())
+            );
+            assert_eq!(shown, x.to_string());
+            let y = d(shown);
+            assert_eq!((x.unscaled(), x.scale()), (y.unscaled(), y.scale()));
+        }
+        for s in ["", "-", "1.2.3", "1e", "abc", "1e5.0"] {
+            assert!(s.parse::<BigDecimal>().is_err());
+        }
+    }
+
+    #[test]
+    fn adjusted_test() {
+        // around every power of ten the digit count changes, which the bit estimate alone
+        // cannot always tell
+        let mut p = BigInt::from_u64(1);
+        for k in 0..80 {
+            for u in [
+                &p - BigInt::from_u64(1),
+                p.clone(),
+                &p + BigInt::from_u64(1),
+            ] {
+                for x in [BigDecimal::new(&u, 3), BigDecimal::new(&-&u, -2)] {
+                    let expected = if x.is_zero() {
+                        -x.scale()
+                    } else {
+                        u.to_string().len() as i64 - 1 - x.scale()
+                    };
+                    assert_eq!(expected, x.adjusted(), "{k}");
+                }
+            }
+            p = p * BigInt::from_u64(10);
+        }
+    }
+
+    #[test]
+    fn comparison_test() {
+        assert_eq!(d("1.0"), d("1.000"));
+        assert_eq!(d("1E+2"), d("100.00"));
+        assert_eq!(d("0E+5"), d("0.000"));
+        let x = d("120.00").strip_trailing_zeros();
+        assert_eq!((&BigInt::from_i64(12), -1), (x.unscaled(), x.scale()));
+        assert!(d("0.1") < d("0.11"));
+        assert!(d("-1E+1000") < d("-0.5"));
+        assert!(d("9.99") < d("1E+1"));
+        assert!(d("-2.50") < d("-2.4999"));
+        let set: HashSet<_> = ["2.5", "2.50", "25E-1", "2.500"]
+            .iter()
+            .map(|s| d(s))
+            .collect();
+        assert_eq!(1, set.len());
+    }
+}
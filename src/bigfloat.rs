@@ -0,0 +1,984 @@
+use crate::mparith::{abs, divmod, isqrt, BigInt};
+use crate::rational::BigRational;
+use crate::rounding::{div_round, parse_decimal, pow10, RoundingMode};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops;
+
+// 2^n
+fn pow2(n: u64) -> BigInt {
+    BigInt::from_u64(1) << BigInt::from_u64(n)
+}
+
+// x 2^n, rounded toward negative infinity when n < 0
+fn shift(x: &BigInt, n: i64) -> BigInt {
+    if n >= 0 {
+        x << BigInt::from_u64(n as u64)
+    } else {
+        x >> BigInt::from_u64(n.unsigned_abs())
+    }
+}
+
+// the nonzero value x lies in [2^(top - 1), 2^top)
+fn top(x: &BigFloat) -> i64 {
+    x.exponent + x.mantissa.bits() as i64
+}
+
+/// A binary floating point number mantissa * 2^exponent with a precision of its own.
+///
+/// Values are always rounded to at most `precision` bits and stored with an odd mantissa, or
+/// a zero mantissa and exponent, so there are no infinities, NaNs or negative zero. Every
+/// operation rounds its exact result once: the operators use the larger precision of the two
+/// operands and round half to even, and the `_rounded` methods take a precision and a
+/// `RoundingMode`. Equality and ordering compare values and ignore the precision.
+#[derive(Debug, Clone)]
+pub struct BigFloat {
+    mantissa: BigInt,
+    exponent: i64,
+    precision: usize,
+}
+
+impl BigFloat {
+    /// m 2^e rounded to the given number of bits, which must be positive.
+    pub fn new(m: &BigInt, e: i64, precision: usize, mode: RoundingMode) -> BigFloat {
+        if precision == 0 {
+            panic!("The precision must be at least one bit");
+        }
+        if m.sgn == 0 {
+            return BigFloat::zero(precision);
+        }
+        let (mut m, mut e) = (m.clone(), e);
+        let bits = m.bits();
+        if bits > precision {
+            let drop = bits - precision;
+            m = div_round(&m, &pow2(drop as u64), mode);
+            e += drop as i64;
+        }
+        let zeros = m.trailing_zeros();
+        BigFloat {
+            mantissa: shift(&m, -(zeros as i64)),
+            exponent: e + zeros as i64,
+            precision,
+        }
+    }
+
+    pub fn zero(precision: usize) -> BigFloat {
+        BigFloat {
+            mantissa: BigInt::from_u64(0),
+            exponent: 0,
+            precision,
+        }
+    }
+
+    /// The exact value of a finite f64 with 53 bits of precision, or None for infinities and
+    /// NaN.
+    pub fn from_f64(x: f64) -> Option<BigFloat> {
+        let q = BigRational::from_f64(x)?;
+        Some(BigFloat::from_rational(&q, 53, RoundingMode::HalfEven))
+    }
+
+    pub fn from_bigint(n: &BigInt, precision: usize, mode: RoundingMode) -> BigFloat {
+        BigFloat::new(n, 0, precision, mode)
+    }
+
+    pub fn from_rational(q: &BigRational, precision: usize, mode: RoundingMode) -> BigFloat {
+        round_quotient(q.numer(), q.denom(), 0, precision, mode)
+    }
+
+    /// Parses a decimal number like "-1.25e-7" and rounds it, or returns None if the string
+    /// is not one.
+    pub fn from_decimal_str(s: &str, precision: usize, mode: RoundingMode) -> Option<BigFloat> {
+        let (n, k) = parse_decimal(s)?;
+        if k >= 0 {
+            Some(BigFloat::new(&(n * pow10(k as u64)), 0, precision, mode))
+        } else {
+            Some(round_quotient(
+                &n,
+                &pow10(k.unsigned_abs()),
+                0,
+                precision,
+                mode,
+            ))
+        }
+    }
+
+    pub fn mantissa(&self) -> &BigInt {
+        &self.mantissa
+    }
+
+    pub fn exponent(&self) -> i64 {
+        self.exponent
+    }
+
+    pub fn precision(&self) -> usize {
+        self.precision
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa.sgn == 0
+    }
+
+    /// The same value rounded to another precision.
+    pub fn with_precision(&self, precision: usize, mode: RoundingMode) -> BigFloat {
+        BigFloat::new(&self.mantissa, self.exponent, precision, mode)
+    }
+
+    /// The exact value as a fraction.
+    pub fn to_rational(&self) -> BigRational {
+        if self.exponent >= 0 {
+            BigRational::from_integer(&shift(&self.mantissa, self.exponent))
+        } else {
+            BigRational::new(&self.mantissa, &pow2(self.exponent.unsigned_abs()))
+        }
+    }
+
+    /// The nearest f64, with ties to even, gradual underflow to subnormals and overflow to
+    /// infinity.
+    pub fn to_f64(&self) -> f64 {
+        if self.is_zero() {
+            return 0.0;
+        }
+        let sign = if self.mantissa.sgn == -1 { 1 << 63 } else { 0 };
+        let bits: u64 = if top(self) <= -1022 {
+            // a multiple of 2^-1074, which may round up to 2^52 2^-1074, the smallest normal
+            let m = div_round(
+                &self.mantissa,
+                &pow2((-1074 - self.exponent).max(0) as u64),
+                RoundingMode::HalfEven,
+            );
+            m.to_u64().unwrap() << (self.exponent + 1074).max(0) as u32
+        } else {
+            let r = self.with_precision(53, RoundingMode::HalfEven);
+            let top = top(&r);
+            if top > 1024 {
+                0x7ff << 52
+            } else {
+                let m = r.mantissa.to_u64().unwrap() << (53 - r.mantissa.bits());
+                ((top + 1022) as u64) << 52 | (m & ((1 << 52) - 1))
+            }
+        };
+        f64::from_bits(sign | bits)
+    }
+
+    /// The value with the given number of significant digits in scientific notation, such as
+    /// "-1.2500e-7", rounded half to even.
+    pub fn to_decimal_string(&self, digits: usize) -> String {
+        if digits == 0 {
+            panic!("At least one digit is needed");
+        }
+        if self.is_zero() {
+            return format!("{:.*}e0", digits - 1, 0.0);
+        }
+        // the decimal exponent k of the first digit, from an estimate that is corrected below
+        let log10 = (self.mantissa.ln_abs() + self.exponent as f64 * std::f64::consts::LN_2)
+            / std::f64::consts::LN_10;
+        let mut k = log10.floor() as i64;
+        let (low, high) = (pow10(digits as u64 - 1), pow10(digits as u64));
+        let n = loop {
+            let n = self.scaled_by_ten(digits as i64 - 1 - k);
+            if abs(&n) >= high {
+                k += 1;
+            } else if abs(&n) < low {
+                k -= 1;
+            } else {
+                break n;
+            }
+        };
+        let s = n.to_string();
+        let (sign, digits) = s.split_at(if n.sgn == -1 { 1 } else { 0 });
+        if digits.len() == 1 {
+            format!("{sign}{digits}e{k}")
+        } else {
+            format!("{sign}{}.{}e{k}", &digits[..1], &digits[1..])
+        }
+    }
+
+    // self 10^j rounded half to even to an integer
+    fn scaled_by_ten(&self, j: i64) -> BigInt {
+        let mut num = self.mantissa.clone();
+        let mut den = BigInt::from_u64(1);
+        if j >= 0 {
+            num = num * pow10(j as u64);
+        } else {
+            den = pow10(j.unsigned_abs());
+        }
+        if self.exponent >= 0 {
+            num = shift(&num, self.exponent);
+        } else {
+            den = shift(&den, -self.exponent);
+        }
+        div_round(&num, &den, RoundingMode::HalfEven)
+    }
+
+    pub fn add_rounded(&self, other: &BigFloat, precision: usize, mode: RoundingMode) -> BigFloat {
+        if self.is_zero() || other.is_zero() {
+            let x = if self.is_zero() { other } else { self };
+            return x.with_precision(precision, mode);
+        }
+        let (big, small) = if top(self) >= top(other) {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        // Both the sum and any rounding boundary are multiples of 2^(t + 2), even when a
+        // subtraction loses the leading bit. An addend below 2^t cannot change which side of
+        // a boundary the sum lies on, so it is replaced by +-2^(t - 1) instead of aligning
+        // the mantissas across a huge exponent gap.
+        let t = big.exponent.min(top(big) - precision as i64 - 2) - 2;
+        let sticky;
+        let small = if top(small) < t {
+            let m = BigInt::from_i64(small.mantissa.sgn as i64);
+            sticky = BigFloat {
+                mantissa: m,
+                exponent: t - 1,
+                precision: 1,
+            };
+            &sticky
+        } else {
+            small
+        };
+        let e = big.exponent.min(small.exponent);
+        let m = shift(&big.mantissa, big.exponent - e) + shift(&small.mantissa, small.exponent - e);
+        BigFloat::new(&m, e, precision, mode)
+    }
+
+    pub fn sub_rounded(&self, other: &BigFloat, precision: usize, mode: RoundingMode) -> BigFloat {
+        self.add_rounded(&-other, precision, mode)
+    }
+
+    pub fn mul_rounded(&self, other: &BigFloat, precision: usize, mode: RoundingMode) -> BigFloat {
+        let m = &self.mantissa * &other.mantissa;
+        BigFloat::new(&m, self.exponent + other.exponent, precision, mode)
+    }
+
+    /// self / other, which panics if other is zero.
+    pub fn div_rounded(&self, other: &BigFloat, precision: usize, mode: RoundingMode) -> BigFloat {
+        if other.is_zero() {
+            panic!("Divide by zero error");
+        }
+        let (mut num, mut den) = (self.mantissa.clone(), other.mantissa.clone());
+        if den.sgn == -1 {
+            num = -num;
+            den = -den;
+        }
+        round_quotient(&num, &den, self.exponent - other.exponent, precision, mode)
+    }
+
+    pub fn sqrt_rounded(&self, precision: usize, mode: RoundingMode) -> BigFloat {
+        if self.mantissa.sgn == -1 {
+            panic!("Do not take the square root of a negative number");
+        }
+        if self.is_zero() {
+            return BigFloat::zero(precision);
+        }
+        // m 2^s with an even exponent e - s and at least 2 precision + 6 bits, so that its
+        // integer square root r has at least precision + 3 bits
+        let bits = self.mantissa.bits() as i64;
+        let mut s = (2 * precision as i64 + 6 - bits).max(0);
+        if (self.exponent - s) % 2 != 0 {
+            s += 1;
+        }
+        let n = shift(&self.mantissa, s);
+        let r = isqrt(&n);
+        // r + 1/2 stands in for the irrational root between r and r + 1, as in round_quotient
+        let inexact = BigInt::from_u64((&r * &r != n) as u64);
+        let m = &r + &r + inexact;
+        BigFloat::new(&m, (self.exponent - s) / 2 - 1, precision, mode)
+    }
+
+    /// The square root with the precision of self, rounded half to even.
+    pub fn sqrt(&self) -> BigFloat {
+        self.sqrt_rounded(self.precision, RoundingMode::HalfEven)
+    }
+
+    /// e^self rounded correctly, which panics when the result's exponent would not fit an
+    /// i64.
+    pub fn exp_rounded(&self, precision: usize, mode: RoundingMode) -> BigFloat {
+        if self.is_zero() {
+            return BigFloat::new(&BigInt::from_u64(1), 0, precision, mode);
+        }
+        let top = top(self);
+        if top > 62 {
+            panic!("The exponential is out of range");
+        }
+        // |e^x - 1| < 2|x| < 2^-(precision + 2) lies strictly between 1 and the nearest
+        // rounding boundary, and 1 +- 2^-(precision + 3) rounds the same way
+        let p = precision as i64 + 3;
+        if top < -p {
+            let m = pow2(p as u64) + BigInt::from_i64(self.mantissa.sgn as i64);
+            return BigFloat::new(&m, -p, precision, mode);
+        }
+        ziv(precision, mode, |w| exp_fixed(self, w))
+    }
+
+    /// e^self with the precision of self, rounded half to even.
+    pub fn exp(&self) -> BigFloat {
+        self.exp_rounded(self.precision, RoundingMode::HalfEven)
+    }
+
+    /// The natural logarithm rounded correctly, which panics unless self > 0.
+    pub fn ln_rounded(&self, precision: usize, mode: RoundingMode) -> BigFloat {
+        if self.mantissa.sgn != 1 {
+            panic!("Logarithms are only defined for positive numbers");
+        }
+        if self.mantissa == BigInt::from_u64(1) && self.exponent == 0 {
+            return BigFloat::zero(precision);
+        }
+        ziv(precision, mode, |w| ln_fixed(self, w))
+    }
+
+    /// The natural logarithm with the precision of self, rounded half to even.
+    pub fn ln(&self) -> BigFloat {
+        self.ln_rounded(self.precision, RoundingMode::HalfEven)
+    }
+
+    /// pi rounded correctly to the given precision.
+    pub fn pi(precision: usize, mode: RoundingMode) -> BigFloat {
+        ziv(precision, mode, pi_fixed)
+    }
+}
+
+// num / den 2^e for den > 0, rounded. The quotient q is taken with at least precision + 2
+// bits, and the remainder is folded into a sticky bit as 2q + 1: the exact quotient and
+// q + 1/2 then lie strictly between the same two multiples of 2 (the rounding boundaries,
+// at least 2 bits are dropped), so they round alike.
+fn round_quotient(
+    num: &BigInt,
+    den: &BigInt,
+    e: i64,
+    precision: usize,
+    mode: RoundingMode,
+) -> BigFloat {
+    if den.sgn == 0 {
+        panic!("Divide by zero error");
+    }
+    if num.sgn == 0 {
+        return BigFloat::zero(precision);
+    }
+    let mut a = num.clone();
+    a.sgn = 1;
+    let s = precision as i64 + 3 + den.bits() as i64 - a.bits() as i64;
+    let (q, r) = if s >= 0 {
+        divmod(&shift(&a, s), den)
+    } else {
+        divmod(&a, &shift(den, -s))
+    };
+    let mut m = &q + &q + BigInt::from_u64((r.sgn != 0) as u64);
+    m.sgn *= num.sgn;
+    BigFloat::new(&m, e - s - 1, precision, mode)
+}
+
+// Ziv's strategy: f(w) returns m, e and err with the exact value within err 2^e of m 2^e,
+// an approximation with about w correct bits. When both ends of that interval round to the
+// same number so does the exact value; otherwise w grows. This terminates for values that
+// are not dyadic, which holds for exp, ln and pi away from the special cases.
+fn ziv<F: Fn(usize) -> (BigInt, i64, BigInt)>(
+    precision: usize,
+    mode: RoundingMode,
+    f: F,
+) -> BigFloat {
+    let mut w = precision + 32;
+    loop {
+        let (m, e, err) = f(w);
+        let low = BigFloat::new(&(&m - &err), e, precision, mode);
+        let high = BigFloat::new(&(&m + &err), e, precision, mode);
+        if low == high {
+            return low;
+        }
+        w += w / 2;
+    }
+}
+
+// atan(1/n) 2^w, or atanh(1/n) 2^w, by the Taylor series with truncated terms, and a bound
+// on the error in units of 2^-w
+fn arctan_recip(n: u64, w: usize, hyperbolic: bool) -> (BigInt, u64) {
+    let n2 = BigInt::from_u64(n * n);
+    let mut power = pow2(w as u64) / BigInt::from_u64(n);
+    let mut sum = power.clone();
+    let mut k = 1;
+    loop {
+        power = power / &n2;
+        if power.sgn == 0 {
+            break;
+        }
+        let term = &power / BigInt::from_u64(2 * k + 1);
+        if hyperbolic || k % 2 == 0 {
+            sum = sum + term;
+        } else {
+            sum = sum - term;
+        }
+        k += 1;
+    }
+    (sum, 2 * k + 2)
+}
+
+// ln 2 = 2 atanh(1/3) in units of 2^-w, with an error bound
+fn ln2_fixed(w: usize) -> (BigInt, u64) {
+    let (a, err) = arctan_recip(3, w, true);
+    (&a + &a, 2 * err)
+}
+
+// pi = 16 atan(1/5) - 4 atan(1/239) (Machin)
+fn pi_fixed(w: usize) -> (BigInt, i64, BigInt) {
+    let (a, err_a) = arctan_recip(5, w, false);
+    let (b, err_b) = arctan_recip(239, w, false);
+    let pi = BigInt::from_u64(16) * a - BigInt::from_u64(4) * b;
+    (pi, -(w as i64), BigInt::from_u64(16 * err_a + 4 * err_b))
+}
+
+// floor(x 2^w)
+fn to_fixed(x: &BigFloat, w: usize) -> BigInt {
+    shift(&x.mantissa, x.exponent + w as i64)
+}
+
+// e^x = 2^k (e^(r / 2^j))^(2^j) with x = k ln 2 + r and |r| <= about ln 2 / 2. The Taylor
+// series runs on r / 2^j, and the j squarings double the relative error each time, so the
+// working precision carries j extra bits.
+fn exp_fixed(x: &BigFloat, w: usize) -> (BigInt, i64, BigInt) {
+    let k = (x.to_f64() / std::f64::consts::LN_2).round() as i64;
+    let k_bits = 64 - k.unsigned_abs().leading_zeros() as usize;
+    let j = (w as f64).sqrt() as usize / 2 + 1;
+    let wt = w + j + 2 * (64 - w.leading_zeros() as usize) + 8;
+    let one = pow2(wt as u64);
+
+    // r with an error of at most 2 + err_l units
+    let (l, err_l) = ln2_fixed(wt + k_bits);
+    let r = to_fixed(x, wt) - shift(&(BigInt::from_i64(k) * l), -(k_bits as i64));
+    let r = shift(&r, -(j as i64));
+
+    let mut sum = one.clone();
+    let mut term = one;
+    let mut n = 1;
+    loop {
+        term = shift(&(&term * &r), -(wt as i64)) / BigInt::from_u64(n);
+        if term.sgn == 0 {
+            break;
+        }
+        sum = sum + &term;
+        n += 1;
+    }
+    for _ in 0..j {
+        sum = shift(&(&sum * &sum), -(wt as i64));
+    }
+    // relative errors: 2^-wt (2 + err_l) from r and (2n + 2) / 0.3 from the truncated
+    // terms, then doubled and increased by about 4 2^-wt per squaring, with e^r < 3.2
+    let amplified = pow2(j as u64) * BigInt::from_u64(8 * n + 13 + 4 * j as u64);
+    let err = BigInt::from_u64(4) * (amplified + BigInt::from_u64(err_l + 2)) + BigInt::from_u64(1);
+    (sum, k - wt as i64, err)
+}
+
+// ln x = E ln 2 + 2 atanh((f - 1) / (f + 1)) for x = f 2^E with f in [3/4, 3/2). For E = 0 the
+// result is about x - 1, so the working precision gets the bits lost to cancellation.
+fn ln_fixed(x: &BigFloat, w: usize) -> (BigInt, i64, BigInt) {
+    let bits = x.mantissa.bits() as i64;
+    let mut e = x.exponent + bits - 1;
+    // f = m / 2^(bits - 1) is in [1, 2), halved when it is at least 3/2
+    let mut f_shift = -(bits - 1);
+    if &x.mantissa + &x.mantissa >= shift(&BigInt::from_u64(3), bits - 1) {
+        f_shift -= 1;
+        e += 1;
+    }
+    let mut wt = w + 8;
+    if e == 0 {
+        // x is not an integer here, and x - 1 = (m - 2^-exponent) 2^exponent exactly
+        let d = &x.mantissa - pow2(x.exponent.unsigned_abs());
+        let top = x.exponent + d.bits() as i64;
+        wt += top.min(0).unsigned_abs() as usize;
+    }
+    let one = pow2(wt as u64);
+    let f = shift(&x.mantissa, f_shift + wt as i64);
+    let s = shift(&(&f - &one), wt as i64) / (&f + &one);
+    // atanh is odd, and the series runs on |s| so that the truncated terms reach 0
+    let sign = BigInt::from_i64(s.sgn as i64);
+    let s = &s * &sign;
+
+    let s2 = shift(&(&s * &s), -(wt as i64));
+    let mut sum = s.clone();
+    let mut power = s;
+    let mut n = 1;
+    loop {
+        power = shift(&(&power * &s2), -(wt as i64));
+        let term = &power / BigInt::from_u64(2 * n + 1);
+        if term.sgn == 0 {
+            break;
+        }
+        sum = sum + term;
+        n += 1;
+    }
+    let e_bits = 64 - e.unsigned_abs().leading_zeros() as usize;
+    let (l, err_l) = ln2_fixed(wt + e_bits);
+    let result = (&sum + &sum) * sign + shift(&(BigInt::from_i64(e) * l), -(e_bits as i64));
+    (result, -(wt as i64), BigInt::from_u64(6 * n + 8 + err_l))
+}
+
+impl PartialEq for BigFloat {
+    fn eq(&self, other: &Self) -> bool {
+        self.mantissa == other.mantissa && self.exponent == other.exponent
+    }
+}
+
+impl Eq for BigFloat {}
+
+impl Ord for BigFloat {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let sign = self.mantissa.sgn;
+        if sign != other.mantissa.sgn || sign == 0 {
+            return sign.cmp(&other.mantissa.sgn);
+        }
+        // the magnitudes are ordered by their leading bits unless those are in the same place,
+        // when the exponents are close enough to align the mantissas
+        let by_magnitude = match top(self).cmp(&top(other)) {
+            Ordering::Equal => {
+                let e = self.exponent.min(other.exponent);
+                let a = shift(&self.mantissa, self.exponent - e);
+                let b = shift(&other.mantissa, other.exponent - e);
+                return a.cmp(&b);
+            }
+            order => order,
+        };
+        if sign == 1 {
+            by_magnitude
+        } else {
+            by_magnitude.reverse()
+        }
+    }
+}
+
+impl PartialOrd for BigFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl ops::Add<BigFloat> for BigFloat {
+    type Output = BigFloat;
+
+    fn add(self, b: BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.add_rounded(&b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Add<&BigFloat> for BigFloat {
+    type Output = BigFloat;
+
+    fn add(self, b: &BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.add_rounded(b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Add<BigFloat> for &BigFloat {
+    type Output = BigFloat;
+
+    fn add(self, b: BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.add_rounded(&b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Add<&BigFloat> for &BigFloat {
+    type Output = BigFloat;
+
+    fn add(self, b: &BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.add_rounded(b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Sub<BigFloat> for BigFloat {
+    type Output = BigFloat;
+
+    fn sub(self, b: BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.sub_rounded(&b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Sub<&BigFloat> for BigFloat {
+    type Output = BigFloat;
+
+    fn sub(self, b: &BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.sub_rounded(b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Sub<BigFloat> for &BigFloat {
+    type Output = BigFloat;
+
+    fn sub(self, b: BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.sub_rounded(&b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Sub<&BigFloat> for &BigFloat {
+    type Output = BigFloat;
+
+    fn sub(self, b: &BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.sub_rounded(b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Mul<BigFloat> for BigFloat {
+    type Output = BigFloat;
+
+    fn mul(self, b: BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.mul_rounded(&b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Mul<&BigFloat> for BigFloat {
+    type Output = BigFloat;
+
+    fn mul(self, b: &BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.mul_rounded(b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Mul<BigFloat> for &BigFloat {
+    type Output = BigFloat;
+
+    fn mul(self, b: BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.mul_rounded(&b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Mul<&BigFloat> for &BigFloat {
+    type Output = BigFloat;
+
+    fn mul(self, b: &BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.mul_rounded(b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Div<BigFloat> for BigFloat {
+    type Output = BigFloat;
+
+    fn div(self, b: BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.div_rounded(&b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Div<&BigFloat> for BigFloat {
+    type Output = BigFloat;
+
+    fn div(self, b: &BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.div_rounded(b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Div<BigFloat> for &BigFloat {
+    type Output = BigFloat;
+
+    fn div(self, b: BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.div_rounded(&b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Div<&BigFloat> for &BigFloat {
+    type Output = BigFloat;
+
+    fn div(self, b: &BigFloat) -> BigFloat {
+        let precision = self.precision.max(b.precision);
+        self.div_rounded(b, precision, RoundingMode::HalfEven)
+    }
+}
+
+impl ops::Neg for BigFloat {
+    type Output = BigFloat;
+
+    fn neg(self) -> BigFloat {
+        -&self
+    }
+}
+
+impl ops::Neg for &BigFloat {
+    type Output = BigFloat;
+
+    fn neg(self) -> BigFloat {
+        BigFloat {
+            mantissa: -&self.mantissa,
+            exponent: self.exponent,
+            precision: self.precision,
+        }
+    }
+}
+
+/// Shown in scientific notation with enough digits to read the same value back at the same
+/// precision, without trailing zeros, such as "1.5e-3".
+impl fmt::Display for BigFloat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // 10^(digits - 1) > 2^precision separates neighbouring values
+        let digits = (self.precision as f64 * std::f64::consts::LOG10_2).ceil() as usize + 1;
+        let s = self.to_decimal_string(digits);
+        let (mantissa, exponent) = s.split_once('e').unwrap();
+        let mantissa = if mantissa.contains('.') {
+            mantissa.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            mantissa
+        };
+        write!(f, "{mantissa}e{exponent}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BigFloat, RoundingMode};
+    use crate::mparith::{build_bigint, BigInt};
+    use crate::rational::BigRational;
+    use RoundingMode::*;
+
+    fn i(n: i64) -> BigInt {
+        BigInt::from_i64(n)
+    }
+
+    // m 2^e, exactly
+    fn float(m: &str, e: i64) -> BigFloat {
+        BigFloat::new(&build_bigint(m), e, 4000, HalfEven)
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        // from mpmath, whose basic operations are correctly rounded
+        let one = BigFloat::from_bigint(&i(1), 10, HalfEven);
+        let three = BigFloat::from_bigint(&i(3), 10, HalfEven);
+        let cases = [
+            (HalfEven, ("683", -11), ("-683", -11)),
+            (Down, ("341", -10), ("-341", -10)),
+            (Up, ("683", -11), ("-683", -11)),
+            (Ceiling, ("683", -11), ("-341", -10)),
+            (Floor, ("341", -10), ("-683", -11)),
+        ];
+        for (mode, (m, e), (neg_m, neg_e)) in cases {
+            assert_eq!(float(m, e), one.div_rounded(&three, 10, mode));
+            assert_eq!(float(neg_m, neg_e), (-&one).div_rounded(&three, 10, mode));
+        }
+        let two = BigFloat::from_bigint(&i(2), 64, HalfEven);
+        assert_eq!(float("3260954456333195553", -61), two.sqrt());
+        assert_eq!(
+            float("3260954456333195553", -61),
+            two.sqrt_rounded(64, Down)
+        );
+        assert_eq!(float("13043817825332782213", -63), two.sqrt_rounded(64, Up));
+        assert_eq!(float("3", 0), float("9", 0).sqrt_rounded(2, Up));
+
+        // 1 +- 2^-1000 only changes the result in the directed modes
+        let tiny = float("1", -1000);
+        let one = BigFloat::from_bigint(&i(1), 53, HalfEven);
+        assert_eq!(one, one.add_rounded(&tiny, 53, HalfEven));
+        assert_eq!(one, one.sub_rounded(&tiny, 53, HalfEven));
+        assert_eq!(one, one.add_rounded(&tiny, 53, Floor));
+        assert_eq!(one, one.sub_rounded(&tiny, 53, Ceiling));
+        assert_eq!(
+            float("4503599627370497", -52),
+            one.add_rounded(&tiny, 53, Ceiling)
+        );
+        assert_eq!(
+            float("9007199254740991", -53),
+            one.sub_rounded(&tiny, 53, Floor)
+        );
+        assert_eq!(float("-1", -1000), &(&one - &one) - &tiny);
+
+        // the operators agree with f64 at 53 bits
+        let (a, b) = (
+            BigFloat::from_f64(0.1).unwrap(),
+            BigFloat::from_f64(0.2).unwrap(),
+        );
+        assert_eq!(0.1 + 0.2, (&a + &b).to_f64());
+        assert_eq!(0.1 * 0.1, (&a * &a).to_f64());
+        assert_eq!(0.1 / 0.2, (&a / &b).to_f64());
+        assert_eq!(0.1 - 0.2, (a - b).to_f64());
+        let exact = float("1", -3).mul_rounded(&float("-5", 7), 200, HalfEven);
+        assert_eq!(float("-5", 4), exact);
+
+        assert!(float("3", -1) < float("1", 1) && float("-1", 1) < float("-3", -1));
+        assert!(float("-1", 100) < float("0", 0) && float("1", -100) > float("0", 0));
+        assert_eq!(
+            std::cmp::Ordering::Less,
+            float("1023", 0).cmp(&float("1", 10))
+        );
+    }
+
+    #[test]
+    fn conversion_test() {
+        let values = [
+            0.1,
+            -2.5,
+            1e300,
+            -1e-300,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            5e-324,
+            -1.5e-310,
+            0.0,
+        ];
+        for x in values {
+            let f = BigFloat::from_f64(x).unwrap();
+            assert_eq!(x, f.to_f64());
+            assert_eq!(BigRational::from_f64(x).unwrap(), f.to_rational());
+        }
+        assert!(BigFloat::from_f64(f64::NAN).is_none());
+        // rounding at the ends of the range
+        assert_eq!(f64::INFINITY, float("1", 1024).to_f64());
+        // 2^1024 - 2^970 is halfway between f64::MAX and 2^1024, and ties go to the even 2^1024
+        let below = |d: BigFloat| float("1", 1024).sub_rounded(&d, 200, HalfEven);
+        assert_eq!(-f64::MAX, (-below(float("1", 971))).to_f64());
+        assert_eq!(f64::MAX, below(float("1", 970) + float("1", 900)).to_f64());
+        assert_eq!(f64::INFINITY, below(float("1", 970)).to_f64());
+        assert_eq!(0.0, float("1", -1075).to_f64());
+        assert_eq!(5e-324, float("3", -1076).to_f64());
+        assert_eq!(
+            f64::MIN_POSITIVE,
+            float("4503599627370495", -1074)
+                .with_precision(51, Up)
+                .to_f64()
+        );
+        assert_eq!(f64::MIN_POSITIVE, float("9007199254740991", -1075).to_f64());
+
+        let parsed = BigFloat::from_decimal_str("0.1", 53, HalfEven).unwrap();
+        assert_eq!(BigFloat::from_f64(0.1).unwrap(), parsed);
+        let parsed = BigFloat::from_decimal_str("-1.7976931348623157e308", 53, HalfEven).unwrap();
+        assert_eq!(-f64::MAX, parsed.to_f64());
+        let parsed = BigFloat::from_decimal_str("12.5E-1", 10, Down).unwrap();
+        assert_eq!(float("5", -2), parsed);
+        for s in ["", "e5", "1.2.3", "--1", "1e", "0x10"] {
+            assert!(BigFloat::from_decimal_str(s, 53, HalfEven).is_none(), "{s}");
+        }
+
+        let third = float("1", 0).div_rounded(&float("3", 0), 53, HalfEven);
+        assert_eq!("3.33333e-1", third.to_decimal_string(6));
+        assert_eq!("-1.0000e5", float("-100000", 0).to_decimal_string(5));
+        assert_eq!("1e2", float("99", 0).to_decimal_string(1));
+        assert_eq!("0.00e0", float("0", 0).to_decimal_string(3));
+        assert_eq!(
+            "1.0000000000000001e-1",
+            BigFloat::from_f64(0.1).unwrap().to_string()
+        );
+        assert_eq!("-2.5e0", BigFloat::from_f64(-2.5).unwrap().to_string());
+        assert_eq!("3.3333333333333331e-1", third.to_string());
+        assert_eq!("5e-1", float("1", -1).to_string());
+        assert_eq!("0", BigFloat::zero(53).to_string());
+    }
+
+    #[test]
+    fn transcendental_test() {
+        // from mpmath at 3000 bits, rounded to 100
+        type Case<'a> = (RoundingMode, (&'a str, i64));
+        let check = |x: &BigFloat, f: fn(&BigFloat, RoundingMode) -> BigFloat, cases: &[Case]| {
+            for &(mode, (m, e)) in cases {
+                assert_eq!(float(m, e), f(x, mode), "{x} {mode:?}");
+            }
+        };
+        let exp = |x: &BigFloat, mode| x.exp_rounded(100, mode);
+        let ln = |x: &BigFloat, mode| x.ln_rounded(100, mode);
+        check(
+            &float("1", 0),
+            exp,
+            &[
+                (HalfEven, ("430728948929449700355022696705", -97)),
+                (Down, ("430728948929449700355022696705", -97)),
+                (Up, ("861457897858899400710045393411", -98)),
+            ],
+        );
+        check(
+            &float("-101", -1),
+            exp,
+            &[
+                (HalfEven, ("700306114212855337737618326659", -172)),
+                (Down, ("350153057106427668868809163329", -171)),
+            ],
+        );
+        check(
+            &float("1", -70),
+            exp,
+            &[
+                (HalfEven, ("1180591620717411303425", -70)),
+                (Up, ("633825300114114700748888473601", -99)),
+            ],
+        );
+        check(
+            &float("1000", 0),
+            exp,
+            &[
+                (HalfEven, ("513059496790153126143029214889", 1344)),
+                (Down, ("1026118993580306252286058429777", 1343)),
+            ],
+        );
+        check(
+            &float("2", 0),
+            ln,
+            &[
+                (HalfEven, ("6864597183463434168892683891", -93)),
+                (Ceiling, ("878668439483319573618263538049", -100)),
+            ],
+        );
+        check(
+            &float("10", 0),
+            ln,
+            &[(Floor, ("91214792975327357045064969303", -95))],
+        );
+        check(
+            &float("1208925819614629174706177", -80),
+            ln,
+            &[
+                (Floor, ("2417851639229258349412351", -161)),
+                (Ceiling, ("1267650600228229401496702681089", -180)),
+            ],
+        );
+        check(
+            &float("3", 10000),
+            ln,
+            &[
+                (HalfEven, ("134095413991704467380659759887", -84)),
+                (Floor, ("1072763311933635739045278079095", -87)),
+            ],
+        );
+        check(
+            &float("3", -2),
+            ln,
+            &[
+                (HalfEven, ("-22792521988650066934617725403", -96)),
+                (Floor, ("-729360703636802141907767212897", -101)),
+            ],
+        );
+        check(
+            &float("5", -3),
+            ln,
+            &[(Ceiling, ("-18618761960087589657217972953", -95))],
+        );
+
+        assert_eq!(float("884279719003555", -48), BigFloat::pi(53, HalfEven));
+        assert_eq!(float("7074237752028441", -51), BigFloat::pi(53, Ceiling));
+        assert_eq!(std::f64::consts::PI, BigFloat::pi(53, HalfEven).to_f64());
+        let pi = BigFloat::pi(1000, Floor);
+        assert_eq!(-996, pi.exponent());
+        assert!(pi.mantissa().to_string().ends_with("36958243323917766023"));
+
+        // ln undoes a more precise exp, and the exact cases are exact
+        let x = BigFloat::from_f64(0.7).unwrap();
+        assert_eq!(x, x.exp_rounded(120, HalfEven).ln_rounded(53, HalfEven));
+        assert_eq!(BigFloat::zero(53), float("1", 0).ln());
+        assert_eq!(float("1", 0), BigFloat::zero(53).exp());
+        assert_eq!(
+            float("4503599627370497", -52),
+            float("1", -2000).exp_rounded(53, Up)
+        );
+        assert_eq!(
+            float("9007199254740991", -53),
+            float("-1", -2000).exp_rounded(53, Down)
+        );
+    }
+}
@@ -0,0 +1,560 @@
+use crate::factor::factor;
+use crate::gcd::gcd;
+use crate::modular::{pow_mod, BarrettCtx, ModContext};
+use crate::mparith::{divmod, BigInt};
+use crate::primality::{is_probable_prime, Primality, PrimalityTest};
+use crate::residues::jacobi;
+use crate::roots::small_primes;
+use std::fmt;
+use std::str::FromStr;
+
+// n - 1 is trial divided by the primes below this bound when looking for a Pocklington proof
+const POCKLINGTON_TRIAL_LIMIT: usize = 1 << 16;
+
+// a composite cofactor of n - 1 left after trial division is factored completely when it has
+// at most this many bits, larger ones are too slow to split in general
+const POCKLINGTON_FACTOR_BITS: usize = 100;
+
+// number of bases tried for each prime factor of n - 1 before giving up
+const POCKLINGTON_MAX_WITNESS: u64 = 200;
+
+/// A proof that a number is prime.
+///
+/// A certificate only records the choices made while searching for the proof (witnesses,
+/// parameters and the certificates of the prime factors it relies on), so
+/// `verify_certificate` can check it with a fixed amount of work. Certificates are written
+/// out with `to_string` and read back with `parse`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Certificate {
+    /// n < 2^64, which Miller-Rabin with the first twelve primes as bases decides exactly
+    Small(BigInt),
+    /// the Mersenne number 2^p - 1, proven by the Lucas-Lehmer test
+    LucasLehmer(u32),
+    /// k * 2^e - 1 with k odd and k < 2^e, proven by the Lucas-Lehmer-Riesel test with
+    /// Rödseth's starting value V_k(p, 1)
+    LucasLehmerRiesel { k: BigInt, e: u32, p: u64 },
+    /// a Pocklington-Lehmer n - 1 proof: the listed primes divide n - 1 and the part of
+    /// n - 1 they make up is larger than the square root of n
+    Pocklington {
+        n: BigInt,
+        factors: Vec<PocklingtonFactor>,
+    },
+}
+
+/// A prime factor q of n - 1 in a Pocklington certificate together with a base a satisfying
+/// a^(n-1) = 1 and gcd(a^((n-1)/q) - 1, n) = 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PocklingtonFactor {
+    pub q: BigInt,
+    pub a: BigInt,
+    pub certificate: Certificate,
+}
+
+impl Certificate {
+    /// The number this certificate proves prime.
+    pub fn n(&self) -> BigInt {
+        match self {
+            Certificate::Small(n) => n.clone(),
+            Certificate::LucasLehmer(p) => mersenne(*p),
+            Certificate::LucasLehmerRiesel { k, e, .. } => riesel(k, *e),
+            Certificate::Pocklington { n, .. } => n.clone(),
+        }
+    }
+}
+
+fn mersenne(p: u32) -> BigInt {
+    (BigInt::from_u64(1) << BigInt::from_u64(p as u64)) - BigInt::from_u64(1)
+}
+
+fn riesel(k: &BigInt, e: u32) -> BigInt {
+    (k << BigInt::from_u64(e as u64)) - BigInt::from_u64(1)
+}
+
+/// Tries to prove that n is prime.
+///
+/// Numbers below 2^64 are proven directly, Mersenne numbers with the Lucas-Lehmer test,
+/// numbers of the form k * 2^e - 1 with k < 2^e with the Lucas-Lehmer-Riesel test, and
+/// everything else (including k * 2^e - 1 when no Lucas-Lehmer-Riesel parameter turns up)
+/// with a Pocklington-Lehmer proof. Returns None if n is composite or if not enough of
+/// n - 1 could be factored for a proof: n - 1 is only trial divided by the primes below
+/// 2^16, and what remains must be a probable prime or have at most 100 bits, so most large
+/// primes without special form get None.
+pub fn certify_prime(n: &BigInt) -> Option<Certificate> {
+    match is_probable_prime(n, PrimalityTest::BailliePsw) {
+        Primality::Composite => return None,
+        Primality::Prime => return Some(Certificate::Small(n.clone())),
+        Primality::ProbablyPrime => {}
+    }
+
+    let n_plus_one = n + BigInt::from_u64(1);
+    let e = n_plus_one.trailing_zeros();
+    let k = &n_plus_one >> BigInt::from_u64(e as u64);
+    if k == BigInt::from_u64(1) {
+        return if lucas_lehmer(e as u32) {
+            Some(Certificate::LucasLehmer(e as u32))
+        } else {
+            None
+        };
+    }
+    if k.bits() <= e {
+        if let Some(p) = riesel_parameter(n) {
+            let cert = Certificate::LucasLehmerRiesel { k, e: e as u32, p };
+            return if verify_certificate(&cert) {
+                Some(cert)
+            } else {
+                None
+            };
+        }
+    }
+
+    pocklington(n)
+}
+
+/// Checks a certificate produced by `certify_prime` (or read back from its string form),
+/// returning true when it proves that `cert.n()` is prime.
+pub fn verify_certificate(cert: &Certificate) -> bool {
+    match cert {
+        Certificate::Small(n) => {
+            n.bits() <= 64 && is_probable_prime(n, PrimalityTest::BailliePsw) == Primality::Prime
+        }
+        Certificate::LucasLehmer(p) => {
+            is_probable_prime(&BigInt::from_u64(*p as u64), PrimalityTest::BailliePsw)
+                == Primality::Prime
+                && lucas_lehmer(*p)
+        }
+        Certificate::LucasLehmerRiesel { k, e, p } => verify_riesel(k, *e, *p),
+        Certificate::Pocklington { n, factors } => verify_pocklington(n, factors),
+    }
+}
+
+/// Runs the Lucas-Lehmer test on the Mersenne number 2^p - 1, returning whether it is prime.
+pub fn lucas_lehmer(p: u32) -> bool {
+    if p == 2 {
+        return true;
+    }
+    if p < 2 || p.is_multiple_of(2) {
+        return false;
+    }
+    let m = mersenne(p);
+    let p_bigint = BigInt::from_u64(p as u64);
+    let mut s = BigInt::from_u64(4);
+    for _ in 0..(p - 2) {
+        s = &s * &s - BigInt::from_u64(2);
+        // x mod 2^p - 1 is the sum of the p-bit chunks of x
+        while s > m {
+            s = (&s & &m) + (&s >> &p_bigint);
+        }
+        if s == m {
+            s = BigInt::from_u64(0);
+        }
+    }
+    s.sgn == 0
+}
+
+// finds p with (p - 2 / n) = 1 and (p + 2 / n) = -1
+fn riesel_parameter(n: &BigInt) -> Option<u64> {
+    (3..1000).find(|&p| {
+        jacobi(&BigInt::from_u64(p - 2), n) == 1 && jacobi(&BigInt::from_u64(p + 2), n) == -1
+    })
+}
+
+fn verify_riesel(k: &BigInt, e: u32, p: u64) -> bool {
+    if k.sgn != 1 || !k.is_odd() || k.bits() > e as usize || e < 2 || p < 3 {
+        return false;
+    }
+    let n = riesel(k, e);
+    if jacobi(&BigInt::from_u64(p - 2), &n) != 1 || jacobi(&BigInt::from_u64(p + 2), &n) != -1 {
+        return false;
+    }
+
+    // u_0 = V_k(p, 1) from the ladder (V_m, V_(m+1)), then u_(i+1) = u_i^2 - 2
+    let ctx = BarrettCtx::new(&n);
+    let two = BigInt::from_u64(2);
+    let p_bigint = BigInt::from_u64(p);
+    let mut v0 = two.clone();
+    let mut v1 = &p_bigint % &n;
+    for i in (0..k.bits()).rev() {
+        let mixed = ctx.sub_mod(&ctx.mul_mod(&v0, &v1), &p_bigint);
+        if (k.mag[i / (isize::BITS as usize - 2)] >> (i % (isize::BITS as usize - 2))) & 1 == 1 {
+            v0 = mixed;
+            v1 = ctx.sub_mod(&ctx.sqr_mod(&v1), &two);
+        } else {
+            v1 = mixed;
+            v0 = ctx.sub_mod(&ctx.sqr_mod(&v0), &two);
+        }
+    }
+    let mut u = v0;
+    for _ in 0..(e - 2) {
+        u = ctx.sub_mod(&ctx.sqr_mod(&u), &two);
+    }
+    u.sgn == 0
+}
+
+// returns the primes dividing n - 1 that could be found by trial division plus the prime
+// factors of the remaining cofactor when it is a probable prime or small enough to factor
+fn factor_n_minus_one(n_minus_one: &BigInt) -> Vec<BigInt> {
+    let primes = small_primes(POCKLINGTON_TRIAL_LIMIT);
+    let mut r = n_minus_one.clone();
+    let mut factors = Vec::new();
+    let mut i = 0;
+    while i < primes.len() && r > BigInt::from_u64(1) {
+        let mut prod: u64 = 1;
+        let mut j = i;
+        while j < primes.len() && prod < (1 << 46) {
+            prod *= primes[j] as u64;
+            j += 1;
+        }
+        let rem = (&r % BigInt::from_u64(prod)).to_u64().unwrap();
+        for &p in &primes[i..j] {
+            if rem.is_multiple_of(p as u64) {
+                let p = BigInt::from_u64(p as u64);
+                loop {
+                    let (q, m) = divmod(&r, &p);
+                    if m.sgn != 0 {
+                        break;
+                    }
+                    r = q;
+                }
+                factors.push(p);
+            }
+        }
+        i = j;
+    }
+    if r > BigInt::from_u64(1) {
+        if is_probable_prime(&r, PrimalityTest::BailliePsw) != Primality::Composite {
+            factors.push(r);
+        } else if r.bits() <= POCKLINGTON_FACTOR_BITS {
+            factors.extend(factor(&r).factors.into_iter().map(|(p, _)| p));
+        }
+    }
+    factors
+}
+
+fn pocklington(n: &BigInt) -> Option<Certificate> {
+    let n_minus_one = n - BigInt::from_u64(1);
+    let mut factors = Vec::new();
+    let mut f = BigInt::from_u64(1);
+    for q in factor_n_minus_one(&n_minus_one) {
+        f = f * prime_power_part(&n_minus_one, &q);
+        let mut found = None;
+        for a in 2..POCKLINGTON_MAX_WITNESS {
+            let a = BigInt::from_u64(a);
+            if pow_mod(&a, &n_minus_one, n) != BigInt::from_u64(1) {
+                return None;
+            }
+            let g = gcd(
+                &(pow_mod(&a, &(&n_minus_one / &q), n) - BigInt::from_u64(1)),
+                n,
+            );
+            if g == BigInt::from_u64(1) {
+                found = Some(a);
+                break;
+            }
+            if g != *n {
+                return None;
+            }
+        }
+        factors.push(PocklingtonFactor {
+            certificate: certify_prime(&q)?,
+            q,
+            a: found?,
+        });
+        if &f * &f > *n {
+            return Some(Certificate::Pocklington {
+                n: n.clone(),
+                factors,
+            });
+        }
+    }
+    None
+}
+
+// the largest power of the prime q dividing m
+fn prime_power_part(m: &BigInt, q: &BigInt) -> BigInt {
+    let mut m = m.clone();
+    let mut res = BigInt::from_u64(1);
+    loop {
+        let (d, r) = divmod(&m, q);
+        if r.sgn != 0 {
+            return res;
+        }
+        m = d;
+        res = res * q;
+    }
+}
+
+fn verify_pocklington(n: &BigInt, factors: &[PocklingtonFactor]) -> bool {
+    if *n <= BigInt::from_u64(2) {
+        return false;
+    }
+    let n_minus_one = n - BigInt::from_u64(1);
+    let mut f = BigInt::from_u64(1);
+    for (i, factor) in factors.iter().enumerate() {
+        let q = &factor.q;
+        if factors[..i].iter().any(|other| other.q == *q)
+            || factor.certificate.n() != *q
+            || !verify_certificate(&factor.certificate)
+            || (&n_minus_one % q).sgn != 0
+        {
+            return false;
+        }
+        if pow_mod(&factor.a, &n_minus_one, n) != BigInt::from_u64(1)
+            || gcd(
+                &(pow_mod(&factor.a, &(&n_minus_one / q), n) - BigInt::from_u64(1)),
+                n,
+            ) != BigInt::from_u64(1)
+        {
+            return false;
+        }
+        f = f * prime_power_part(&n_minus_one, q);
+    }
+    &f * &f > *n
+}
+
+/// The error returned when a string is not a valid certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCertificateError;
+
+impl fmt::Display for ParseCertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid primality certificate")
+    }
+}
+
+// The string form is a whitespace separated list of tokens in prefix order:
+//   small n | ll p | llr k e p | pocklington n count (q a certificate)*
+impl fmt::Display for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Certificate::Small(n) => write!(f, "small {}", n),
+            Certificate::LucasLehmer(p) => write!(f, "ll {}", p),
+            Certificate::LucasLehmerRiesel { k, e, p } => write!(f, "llr {} {} {}", k, e, p),
+            Certificate::Pocklington { n, factors } => {
+                write!(f, "pocklington {} {}", n, factors.len())?;
+                for factor in factors {
+                    write!(f, " {} {} {}", factor.q, factor.a, factor.certificate)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for Certificate {
+    type Err = ParseCertificateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+        let cert = parse_certificate(&mut tokens)?;
+        if tokens.next().is_some() {
+            return Err(ParseCertificateError);
+        }
+        Ok(cert)
+    }
+}
+
+fn parse_bigint<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<BigInt, ParseCertificateError> {
+    match tokens.next() {
+        Some(t) if !t.is_empty() && t.chars().all(|c| c.is_ascii_digit()) => {
+            Ok(crate::mparith::build_bigint(t))
+        }
+        _ => Err(ParseCertificateError),
+    }
+}
+
+fn parse_number<'a, T: FromStr>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<T, ParseCertificateError> {
+    tokens
+        .next()
+        .and_then(|t| t.parse().ok())
+        .ok_or(ParseCertificateError)
+}
+
+fn parse_certificate<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<Certificate, ParseCertificateError> {
+    match tokens.next() {
+        Some("small") => Ok(Certificate::Small(parse_bigint(tokens)?)),
+        Some("ll") => Ok(Certificate::LucasLehmer(parse_number(tokens)?)),
+        Some("llr") => Ok(Certificate::LucasLehmerRiesel {
+            k: parse_bigint(tokens)?,
+            e: parse_number(tokens)?,
+            p: parse_number(tokens)?,
+        }),
+        Some("pocklington") => {
+            let n = parse_bigint(tokens)?;
+            let count: usize = parse_number(tokens)?;
+            let mut factors = Vec::new();
+            for _ in 0..count {
+                factors.push(PocklingtonFactor {
+                    q: parse_bigint(tokens)?,
+                    a: parse_bigint(tokens)?,
+                    certificate: parse_certificate(tokens)?,
+                });
+            }
+            Ok(Certificate::Pocklington { n, factors })
+        }
+        _ => Err(ParseCertificateError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Certificate;
+    use crate::mparith::{build_bigint, Pow};
+    use crate::primality::{is_probable_prime, Primality, PrimalityTest};
+
+    #[test]
+    fn lucas_lehmer_test() {
+        let exponents = [2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127, 521, 607];
+        for p in 2..700 {
+            assert_eq!(exponents.contains(&p), super::lucas_lehmer(p));
+        }
+    }
+
+    #[test]
+    fn mersenne_certificate_test() {
+        let m = build_bigint("2").pow(build_bigint("127")) - build_bigint("1");
+        let cert = super::certify_prime(&m).unwrap();
+        assert_eq!(Certificate::LucasLehmer(127), cert);
+        assert!(super::verify_certificate(&cert));
+        assert_eq!(None, super::certify_prime(&(&m + build_bigint("2"))));
+        assert!(!super::verify_certificate(&Certificate::LucasLehmer(11)));
+        assert!(!super::verify_certificate(&Certificate::LucasLehmer(4)));
+    }
+
+    #[test]
+    fn riesel_certificate_test() {
+        // 3 * 2^e - 1 is prime for e = 76, 94 and 103 and composite for the e in between
+        for e in 70..105 {
+            let n = build_bigint("3") * build_bigint("2").pow(build_bigint(&e.to_string()))
+                - build_bigint("1");
+            let expected = [76, 94, 103].contains(&e);
+            let cert = super::certify_prime(&n);
+            assert_eq!(expected, cert.is_some());
+            if let Some(cert) = cert {
+                assert!(matches!(cert, Certificate::LucasLehmerRiesel { .. }));
+                assert_eq!(n, cert.n());
+                assert!(super::verify_certificate(&cert));
+            }
+        }
+        let n = build_bigint("5") * build_bigint("2").pow(build_bigint("72")) - build_bigint("1");
+        let cert = super::certify_prime(&n).unwrap();
+        assert!(super::verify_certificate(&cert));
+        if let Certificate::LucasLehmerRiesel { k, e, p } = cert {
+            assert!(!super::verify_certificate(
+                &Certificate::LucasLehmerRiesel { k, e: e + 1, p }
+            ));
+        }
+    }
+
+    #[test]
+    fn pocklington_certificate_test() {
+        // k * 2^100 + 1 has an easily factored n - 1
+        let shift = build_bigint("2").pow(build_bigint("100"));
+        let mut found = 0;
+        for k in 1..200 {
+            let n = build_bigint(&k.to_string()) * &shift + build_bigint("1");
+            if is_probable_prime(&n, PrimalityTest::BailliePsw) == Primality::Composite {
+                assert_eq!(None, super::certify_prime(&n));
+                continue;
+            }
+            found += 1;
+            let cert = super::certify_prime(&n).unwrap();
+            assert!(matches!(cert, Certificate::Pocklington { .. }));
+            assert_eq!(n, cert.n());
+            assert!(super::verify_certificate(&cert));
+        }
+        assert!(found > 0);
+
+        // a prime whose n - 1 has a large prime factor, which needs its own certificate
+        let q = build_bigint("2").pow(build_bigint("89")) - build_bigint("1");
+        let mut k = build_bigint("2");
+        while is_probable_prime(&(&k * &q + build_bigint("1")), PrimalityTest::BailliePsw)
+            == Primality::Composite
+        {
+            k = k + build_bigint("2");
+        }
+        let cert = super::certify_prime(&(&k * &q + build_bigint("1"))).unwrap();
+        assert!(super::verify_certificate(&cert));
+        assert!(cert.to_string().contains("ll 89"));
+
+        // n - 1 = 2 q1 q2 with 46-bit primes, found by factoring the cofactor q1 q2
+        let n = build_bigint("2475880080337016028972593399");
+        let cert = super::certify_prime(&n).unwrap();
+        assert!(super::verify_certificate(&cert));
+        assert!(cert.to_string().contains("35184372101267"));
+    }
+
+    #[test]
+    fn tampered_certificate_test() {
+        let n = build_bigint("18") * build_bigint("2").pow(build_bigint("80")) + build_bigint("1");
+        let cert = super::certify_prime(&n).unwrap();
+        if let Certificate::Pocklington { n, factors } = cert {
+            // a base that fails the gcd condition
+            let mut bad = factors.clone();
+            bad[0].a = build_bigint("1");
+            assert!(!super::verify_certificate(&Certificate::Pocklington {
+                n: n.clone(),
+                factors: bad
+            }));
+            // not enough of n - 1 is covered
+            let partial = factors
+                .iter()
+                .filter(|f| f.q != build_bigint("2"))
+                .cloned()
+                .collect();
+            assert!(!super::verify_certificate(&Certificate::Pocklington {
+                n: n.clone(),
+                factors: partial
+            }));
+            // the same proof does not work for a different n
+            assert!(!super::verify_certificate(&Certificate::Pocklington {
+                n: n + build_bigint("2"),
+                factors
+            }));
+        } else {
+            panic!("expected a Pocklington certificate");
+        }
+        assert!(!super::verify_certificate(&Certificate::Small(
+            build_bigint("91")
+        )));
+    }
+
+    #[test]
+    fn certificate_string_test() {
+        for cert in [
+            super::certify_prime(&build_bigint("1000003")).unwrap(),
+            Certificate::LucasLehmer(127),
+            Certificate::LucasLehmerRiesel {
+                k: build_bigint("3"),
+                e: 76,
+                p: 5,
+            },
+            super::certify_prime(&build_bigint("18446744073709551629")).unwrap(),
+        ] {
+            let s = cert.to_string();
+            let parsed: Certificate = s.parse().unwrap();
+            assert_eq!(cert, parsed);
+        }
+
+        assert_eq!(
+            Certificate::Small(build_bigint("13")),
+            "small 13".parse().unwrap()
+        );
+        for bad in [
+            "",
+            "small",
+            "small -13",
+            "ll x",
+            "small 13 14",
+            "pocklington 7 1 3 2",
+            "prime 7",
+        ] {
+            assert!(bad.parse::<Certificate>().is_err());
+        }
+    }
+}
@@ -0,0 +1,483 @@
+use crate::gcd::{extended_gcd, gcd};
+use crate::mparith::{isqrt, BigInt};
+use std::collections::HashMap;
+
+// the baby steps of the regulator computation cover at least this many ideals past the giant
+// step, which keeps the reduction after each giant step from jumping over them
+const MIN_BABY_STEPS: u64 = 32;
+
+// modulus of the check that the unit found has norm +-1
+const CHECK_PRIME: u64 = (1 << 61) - 1;
+
+/// The fundamental unit eps = x + y sqrt(D) of Z[sqrt(D)] as the power product
+///
+/// eps = b_0^(2^k) b_1^(2^(k-1)) ... b_k, b_i = (x_i + y_i sqrt(D)) / z_i
+///
+/// with k about log2 of the regulator and factors of a few hundred bits, whereas x and y
+/// themselves have about R / ln(10) digits, millions when D is around 10^12.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompactUnit {
+    pub radicand: BigInt,
+    /// The factors (x_i, y_i, z_i), from b_0 to b_k.
+    pub factors: Vec<(BigInt, BigInt, BigInt)>,
+    norm: i32,
+    regulator: f64,
+}
+
+// the reduced principal ideal [1, (p + sqrt(D)) / q] of Z[sqrt(D)] and its distance, the
+// logarithm of the theta > 0 with ideal = theta Z[sqrt(D)]
+#[derive(Debug, Clone)]
+struct Ideal {
+    p: BigInt,
+    q: BigInt,
+    dist: f64,
+}
+
+// (x + y sqrt(D)) / z, a factor the element |q| theta of an ideal gets multiplied by when
+// the ideal moves; |q| theta is always in Z[sqrt(D)]
+type Factor = (BigInt, BigInt, BigInt);
+
+// walks through the principal ideals of Z[sqrt(D)], which is Shanks' infrastructure
+struct Infrastructure {
+    d: BigInt,
+    s: BigInt,
+    // sqrt(D) - isqrt(D)
+    frac: f64,
+}
+
+impl Infrastructure {
+    fn new(d: &BigInt) -> Infrastructure {
+        let s = isqrt(d);
+        let r = d - &s * &s;
+        let frac = r.ln_abs().exp() / (s.ln_abs().exp() + (d.ln_abs() / 2.0).exp());
+        Infrastructure {
+            d: d.clone(),
+            s,
+            frac,
+        }
+    }
+
+    // ln |p + sqrt(D)|
+    fn ln_plus_root(&self, p: &BigInt) -> f64 {
+        if p.sgn >= 0 {
+            let a = p + &self.s;
+            a.ln_abs() + (self.frac / a.ln_abs().exp()).ln_1p()
+        } else {
+            // |p + sqrt(D)| (sqrt(D) - p) = |D - p^2|
+            (&self.d - p * p).ln_abs() - self.ln_plus_root(&-p)
+        }
+    }
+
+    fn unit_ideal(&self) -> Ideal {
+        Ideal {
+            p: self.s.clone(),
+            q: BigInt::from_u64(1),
+            dist: 0.0,
+        }
+    }
+
+    fn is_reduced(&self, a: &Ideal) -> bool {
+        a.p.sgn == 1 && a.p <= self.s && &self.s - &a.p < a.q && a.q <= &self.s + &a.p
+    }
+
+    // one step of the continued fraction of (p + sqrt(D)) / q
+    fn forward(&self, a: &Ideal) -> (Ideal, Factor) {
+        let one = BigInt::from_u64(1);
+        let quotient = if a.q.sgn == 1 {
+            (&a.p + &self.s) / &a.q
+        } else {
+            -((&a.p + &self.s) / -&a.q) - &one
+        };
+        let p = &quotient * &a.q - &a.p;
+        let q = (&self.d - &p * &p) / &a.q;
+        let dist = a.dist + self.ln_plus_root(&p) - q.ln_abs();
+        let sign = BigInt::from_i64(q.sgn as i64);
+        let mut old_q = a.q.clone();
+        old_q.sgn = 1;
+        let factor = (&p * &sign, sign, old_q);
+        (Ideal { p, q, dist }, factor)
+    }
+
+    // the inverse of forward on reduced ideals
+    fn backward(&self, a: &Ideal) -> (Ideal, Factor) {
+        let q = (&self.d - &a.p * &a.p) / &a.q;
+        let p = &self.s - (&self.s + &a.p) % &q;
+        let dist = a.dist - (self.ln_plus_root(&a.p) - a.q.ln_abs());
+        let factor = (-&a.p, BigInt::from_u64(1), a.q.clone());
+        (Ideal { p, q, dist }, factor)
+    }
+
+    // continued fraction steps until the ideal is reduced
+    fn reduce(&self, a: Ideal) -> (Ideal, Factor) {
+        let mut a = a;
+        let mut factor = one_factor();
+        while !self.is_reduced(&a) {
+            let (next, f) = self.forward(&a);
+            factor = self.mul(&factor, &f);
+            a = next;
+        }
+        (a, factor)
+    }
+
+    // the reduced ideal of the product, following Cohen's composition of the forms
+    // (q, 2p, (p^2 - D) / q), Algorithm 5.4.7 of "A Course in Computational Algebraic Number
+    // Theory"
+    fn multiply(&self, a: &Ideal, b: &Ideal) -> (Ideal, Factor) {
+        let (a, b) = if a.q <= b.q { (a, b) } else { (b, a) };
+        let zero = BigInt::from_u64(0);
+        let s = &a.p + &b.p;
+        let n = &b.p - &a.p;
+        let (y1, g) = if (&b.q % &a.q).sgn == 0 {
+            (zero.clone(), a.q.clone())
+        } else {
+            let (g, u, _) = extended_gcd(&b.q, &a.q);
+            (u, g)
+        };
+        let (x2, y2, g) = if (&s % &g).sgn == 0 {
+            (zero, BigInt::from_i64(-1), g)
+        } else {
+            let (g1, u, v) = extended_gcd(&s, &g);
+            (u, -v, g1)
+        };
+        let v1 = &a.q / &g;
+        let v2 = &b.q / &g;
+        let c2 = (&b.p * &b.p - &self.d) / &b.q;
+        let r = (y1 * y2 * n - x2 * c2) % &v1;
+        let q = &v1 * &v2;
+        let p = (&b.p + v2 * r) % &q;
+        // |q| theta = (|q_a| theta_a)(|q_b| theta_b) / g
+        let dist = a.dist + b.dist + g.ln_abs();
+        let (product, factor) = self.reduce(Ideal { p, q, dist });
+        let factor = self.mul(&factor, &(BigInt::from_u64(1), BigInt::from_u64(0), g));
+        (product, factor)
+    }
+
+    fn mul(&self, a: &Factor, b: &Factor) -> Factor {
+        (
+            &a.0 * &b.0 + &self.d * &a.1 * &b.1,
+            &a.0 * &b.1 + &a.1 * &b.0,
+            &a.2 * &b.2,
+        )
+    }
+
+    // moves a reduced ideal to the neighbour whose distance is closest to target
+    fn approach(&self, a: Ideal, factor: Factor, target: f64) -> (Ideal, Factor) {
+        let (mut a, mut factor) = (a, factor);
+        loop {
+            let (next, f) = if a.dist < target {
+                self.forward(&a)
+            } else {
+                self.backward(&a)
+            };
+            if (next.dist - target).abs() >= (a.dist - target).abs() {
+                return (a, factor);
+            }
+            factor = self.mul(&factor, &f);
+            a = next;
+        }
+    }
+
+    // the regulator by baby-step giant-step: the baby steps store the ideals at distance up to
+    // about 2 delta, and from repeated multiplication by the ideal at distance delta the first
+    // ideal that is also a baby step lies at distance R + (its baby step distance)
+    fn regulator(&self) -> f64 {
+        let baby_steps = isqrt(&self.s)
+            .to_u64()
+            .unwrap_or(u64::MAX)
+            .max(MIN_BABY_STEPS);
+        let one = BigInt::from_u64(1);
+        let mut table = HashMap::new();
+        let mut a = self.unit_ideal();
+        table.insert((a.p.clone(), a.q.clone()), a.dist);
+        let mut giant = None;
+        for i in 1..=2 * baby_steps {
+            a = self.forward(&a).0;
+            if a.q == one {
+                return a.dist;
+            }
+            if i == baby_steps {
+                giant = Some(a.clone());
+            }
+            table.insert((a.p.clone(), a.q.clone()), a.dist);
+        }
+        let giant = giant.unwrap();
+        let mut c = giant.clone();
+        loop {
+            c = self.multiply(&c, &giant).0;
+            if let Some(&dist) = table.get(&(c.p.clone(), c.q.clone())) {
+                // the same ideal at the same distance is not a period
+                if c.dist - dist > 0.5 {
+                    return c.dist - dist;
+                }
+            }
+        }
+    }
+}
+
+fn one_factor() -> Factor {
+    (
+        BigInt::from_u64(1),
+        BigInt::from_u64(0),
+        BigInt::from_u64(1),
+    )
+}
+
+fn reduce_factor(f: Factor) -> Factor {
+    let g = gcd(&gcd(&f.0, &f.1), &f.2);
+    (&f.0 / &g, &f.1 / &g, &f.2 / &g)
+}
+
+/// Returns the fundamental unit of Z[sqrt(D)] in compact representation, or None if D is not
+/// a positive nonsquare. Its norm is -1 exactly when the continued fraction of sqrt(D) has
+/// an odd period, and then its square solves x^2 - D y^2 = 1.
+///
+/// The regulator R = ln(eps) is found first with baby-step giant-step in the cycle of
+/// reduced principal ideals, which is the continued fraction of sqrt(D) with distances
+/// attached, taking about D^(1/4) steps. Then an ideal near distance R / 2^k is squared k
+/// times, each square being reduced and moved along the cycle to distance R / 2^(k-i), and
+/// the factors b_i are the relative generators picked up on the way. The last ideal is
+/// Z[sqrt(D)] itself at distance R, whose generator is eps.
+pub fn compact_fundamental_unit(d: &BigInt) -> Option<CompactUnit> {
+    let s = isqrt(d);
+    if d.sgn != 1 || &s * &s == *d {
+        return None;
+    }
+    let infra = Infrastructure::new(d);
+    let one = BigInt::from_u64(1);
+    let regulator = infra.regulator();
+
+    // start the doubling below 2 ln(D) + 2, where a few baby steps suffice
+    let start = 2.0 * (d.ln_abs() + 1.0);
+    let mut k = 0;
+    while regulator / 2f64.powi(k) > start {
+        k += 1;
+    }
+    let mut a = infra.unit_ideal();
+    let mut factor = one_factor();
+    if k == 0 {
+        // walk the whole period
+        loop {
+            let (next, f) = infra.forward(&a);
+            factor = infra.mul(&factor, &f);
+            a = next;
+            if a.q == one {
+                break;
+            }
+        }
+    } else {
+        (a, factor) = infra.approach(a, factor, regulator / 2f64.powi(k));
+    }
+    let mut factors = vec![reduce_factor(factor)];
+    for i in (0..k).rev() {
+        let target = regulator / 2f64.powi(i);
+        let (square, f) = infra.multiply(&a, &a);
+        (a, factor) = infra.approach(square, f, target);
+        if i == 0 && a.q != one {
+            // Z[sqrt(D)] is the only reduced principal ideal with q = 1 and sits at distance R
+            (a, factor) = find_unit_ideal(&infra, a, factor, target);
+        }
+        factors.push(reduce_factor(factor));
+    }
+    if a.q != one {
+        panic!("Compact representation check failed");
+    }
+
+    let mut unit = CompactUnit {
+        radicand: d.clone(),
+        factors,
+        norm: 1,
+        regulator: a.dist,
+    };
+    let p = BigInt::from_u64(CHECK_PRIME);
+    let (x, y) = unit.eval_mod(&p);
+    let norm = (&x * &x - d * &y * &y) % &p;
+    unit.norm = if norm == one {
+        1
+    } else if norm == &p - &one {
+        -1
+    } else {
+        panic!("Compact representation check failed");
+    };
+    Some(unit)
+}
+
+// searches both directions for the ideal with q = 1 nearest to the target distance
+fn find_unit_ideal(
+    infra: &Infrastructure,
+    a: Ideal,
+    factor: Factor,
+    target: f64,
+) -> (Ideal, Factor) {
+    let one = BigInt::from_u64(1);
+    let mut best: Option<(Ideal, Factor)> = None;
+    for forward in [true, false] {
+        let (mut b, mut f) = (a.clone(), factor.clone());
+        for _ in 0..64 {
+            let (next, g) = if forward {
+                infra.forward(&b)
+            } else {
+                infra.backward(&b)
+            };
+            (b, f) = (next, infra.mul(&f, &g));
+            if b.q == one {
+                if best
+                    .as_ref()
+                    .is_none_or(|(c, _)| (b.dist - target).abs() < (c.dist - target).abs())
+                {
+                    best = Some((b, f));
+                }
+                break;
+            }
+        }
+    }
+    best.expect("Compact representation check failed")
+}
+
+/// Returns the fundamental solution of x^2 - D y^2 = 1 in compact representation, the square
+/// of the fundamental unit when that has norm -1, or None if D is not a positive nonsquare.
+pub fn compact_pell_fundamental(d: &BigInt) -> Option<CompactUnit> {
+    let mut unit = compact_fundamental_unit(d)?;
+    if unit.norm == -1 {
+        // appending b = 1 doubles every exponent
+        unit.factors.push(one_factor());
+        unit.norm = 1;
+        unit.regulator *= 2.0;
+    }
+    Some(unit)
+}
+
+impl CompactUnit {
+    /// The norm x^2 - D y^2 of the unit, 1 or -1.
+    pub fn norm(&self) -> i32 {
+        self.norm
+    }
+
+    /// The natural logarithm of the unit, which is the regulator of Z[sqrt(D)] for the
+    /// fundamental unit, as a floating point approximation.
+    pub fn regulator(&self) -> f64 {
+        self.regulator
+    }
+
+    /// Returns (x mod m, y mod m) for the unit x + y sqrt(D).
+    ///
+    /// The partial products E_i = b_0^(2^i) ... b_i are integral and E_i = E_(i-1)^2 b_i, so
+    /// E_(i-1) modulo m z_i ... z_k determines E_i modulo m z_(i+1) ... z_k with an exact
+    /// division by z_i. This works for every m, also when it shares factors with the z_i.
+    pub fn eval_mod(&self, m: &BigInt) -> (BigInt, BigInt) {
+        if m.sgn != 1 {
+            panic!("Modular arithmetic must be done with positive integers");
+        }
+        // moduli[i] = m z_(i+1) ... z_k
+        let mut moduli = vec![m.clone()];
+        for (_, _, z) in self.factors[1..].iter().rev() {
+            let next = moduli.last().unwrap() * z;
+            moduli.push(next);
+        }
+        moduli.reverse();
+        let (x, y, z) = &self.factors[0];
+        let mut e = ((x / z) % &moduli[0], (y / z) % &moduli[0]);
+        for ((x, y, z), modulus) in self.factors[1..].iter().zip(&moduli[1..]) {
+            let big = modulus * z;
+            let square = (
+                (&e.0 * &e.0 + &self.radicand * &e.1 * &e.1) % &big,
+                (BigInt::from_u64(2) * &e.0 * &e.1) % &big,
+            );
+            let product = (
+                (&square.0 * x + &self.radicand * &square.1 * y) % &big,
+                (&square.0 * y + &square.1 * x) % &big,
+            );
+            e = (product.0 / z, product.1 / z);
+        }
+        e
+    }
+
+    /// Multiplies out the representation, returning (x, y). The result has about R / ln(10)
+    /// digits, so this is only feasible when the regulator is moderate.
+    pub fn expand(&self) -> (BigInt, BigInt) {
+        let (x, y, z) = &self.factors[0];
+        let mut e = (x / z, y / z);
+        for (x, y, z) in &self.factors[1..] {
+            let square = (
+                &e.0 * &e.0 + &self.radicand * &e.1 * &e.1,
+                BigInt::from_u64(2) * &e.0 * &e.1,
+            );
+            e = (
+                (&square.0 * x + &self.radicand * &square.1 * y) / z,
+                (&square.0 * y + &square.1 * x) / z,
+            );
+        }
+        if &e.0 * &e.0 - &self.radicand * &e.1 * &e.1 != BigInt::from_i64(self.norm as i64) {
+            panic!("Compact representation check failed");
+        }
+        e
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mparith::{build_bigint, BigInt};
+    use crate::pell::{pell_fundamental, pell_negative_fundamental};
+
+    fn b(n: u64) -> BigInt {
+        BigInt::from_u64(n)
+    }
+
+    #[test]
+    fn compact_fundamental_unit_test() {
+        for d in 2..300 {
+            let d = b(d);
+            let Some(unit) = super::compact_fundamental_unit(&d) else {
+                assert!(pell_fundamental(&d).is_none());
+                continue;
+            };
+            let negative = pell_negative_fundamental(&d);
+            assert_eq!(negative.is_some(), unit.norm() == -1);
+            let (x, y) = unit.expand();
+            assert_eq!(
+                negative.unwrap_or_else(|| pell_fundamental(&d).unwrap()),
+                (x.clone(), y)
+            );
+            // 2x = eps +- 1 / eps
+            let r = unit.regulator();
+            assert!((r - (x.ln_abs() + 2f64.ln())).abs() < 2.0 * (-2.0 * r).exp() + 1e-9);
+            let pell = super::compact_pell_fundamental(&d).unwrap();
+            assert_eq!(pell_fundamental(&d).unwrap(), pell.expand());
+        }
+        for d in [0, 1, 4, 1000000] {
+            assert!(super::compact_fundamental_unit(&b(d)).is_none());
+        }
+    }
+
+    #[test]
+    fn compact_large_test() {
+        for d in ["1000003", "99999989", "1000000007"] {
+            let d = build_bigint(d);
+            let unit = super::compact_pell_fundamental(&d).unwrap();
+            let (x, y) = pell_fundamental(&d).unwrap();
+            assert_eq!((x.clone(), y.clone()), unit.expand());
+            assert!((unit.regulator() - (x.ln_abs() + 2f64.ln())).abs() < 1e-9);
+            // moduli sharing factors with the denominators
+            let z = unit.factors.last().unwrap().2.clone();
+            for m in [
+                b(1),
+                b(2),
+                b(12),
+                &z * b(6),
+                build_bigint("1000000000000000000000000000057"),
+            ] {
+                assert_eq!((&x % &m, &y % &m), unit.eval_mod(&m));
+            }
+        }
+    }
+
+    #[test]
+    fn compact_huge_test() {
+        // a period of 532572 terms and a fundamental unit with about 274000 digits, checked
+        // against the continued fraction convergents modulo p
+        let d = build_bigint("1000000000039");
+        let unit = super::compact_fundamental_unit(&d).unwrap();
+        assert_eq!(1, unit.norm());
+        assert!((unit.regulator() - 631893.23081985).abs() < 1e-4);
+        assert_eq!((b(842532802), b(194897865)), unit.eval_mod(&b(1000000007)));
+    }
+}
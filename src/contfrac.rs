@@ -0,0 +1,448 @@
+use crate::mparith::{divmod, isqrt, BigInt};
+
+/// A complete quotient (m + sqrt(D)) / d in the continued fraction expansion of sqrt(D).
+///
+/// As an iterator it yields the triples (m_k, d_k, a_k) of the classical recurrence, starting
+/// from m_0 = 0, d_0 = 1 and a_0 = isqrt(D):
+///
+/// m_(k+1) = d_k a_k - m_k, d_(k+1) = (D - m_(k+1)^2) / d_k, a_(k+1) = (a_0 + m_(k+1)) / d_(k+1)
+///
+/// where the divisions are exact and floored respectively. The iterator is infinite unless D
+/// is a perfect square, in which case it stops after a_0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuadraticSurd {
+    pub radicand: BigInt,
+    pub m: BigInt,
+    pub d: BigInt,
+    a0: BigInt,
+}
+
+impl QuadraticSurd {
+    /// The surd sqrt(D) itself, i.e. m = 0 and d = 1.
+    pub fn new(radicand: &BigInt) -> QuadraticSurd {
+        if radicand.sgn == -1 {
+            panic!("Cannot expand the square root of a negative number");
+        }
+        QuadraticSurd {
+            radicand: radicand.clone(),
+            m: BigInt::from_u64(0),
+            d: BigInt::from_u64(1),
+            a0: isqrt(radicand),
+        }
+    }
+
+    /// The integer part of the complete quotient.
+    pub fn floor(&self) -> BigInt {
+        (&self.a0 + &self.m) / &self.d
+    }
+}
+
+impl Iterator for QuadraticSurd {
+    type Item = (BigInt, BigInt, BigInt);
+
+    fn next(&mut self) -> Option<(BigInt, BigInt, BigInt)> {
+        if self.d.sgn == 0 {
+            return None;
+        }
+        let a = self.floor();
+        let item = (self.m.clone(), self.d.clone(), a.clone());
+        let m = &self.d * &a - &self.m;
+        // d is zero after a_0 exactly when D is a perfect square
+        self.d = (&self.radicand - &m * &m) / &self.d;
+        self.m = m;
+        Some(item)
+    }
+}
+
+/// The continued fraction sqrt(D) = [a_0; a_1, ..., a_l, a_1, ..., a_l, ...] given by a_0 and
+/// the period (a_1, ..., a_l), which ends with 2 a_0. The period is empty when D is a
+/// perfect square.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqrtContinuedFraction {
+    pub radicand: BigInt,
+    pub a0: BigInt,
+    pub period: Vec<BigInt>,
+}
+
+/// Expands sqrt(D) for D >= 0, running the (m, d, a) recurrence until d returns to 1, which
+/// is where the period ends.
+pub fn sqrt_continued_fraction(radicand: &BigInt) -> SqrtContinuedFraction {
+    let one = BigInt::from_u64(1);
+    let mut surd = QuadraticSurd::new(radicand);
+    let (_, _, a0) = surd.next().unwrap();
+    let mut period = Vec::new();
+    for (_, d, a) in surd {
+        period.push(a);
+        if d == one {
+            break;
+        }
+    }
+    SqrtContinuedFraction {
+        radicand: radicand.clone(),
+        a0,
+        period,
+    }
+}
+
+impl SqrtContinuedFraction {
+    pub fn is_perfect_square(&self) -> bool {
+        self.period.is_empty()
+    }
+
+    /// The partial quotients a_0, a_1, a_2, ..., repeating the period forever.
+    pub fn terms(&self) -> impl Iterator<Item = BigInt> + '_ {
+        std::iter::once(self.a0.clone()).chain(self.period.iter().cycle().cloned())
+    }
+
+    /// The convergents p_k / q_k of sqrt(D).
+    pub fn convergents(&self) -> Convergents<impl Iterator<Item = BigInt> + '_> {
+        convergents(self.terms())
+    }
+}
+
+/// Iterator over the convergents (p_k, q_k) of a continued fraction, created by
+/// `convergents`.
+#[derive(Debug, Clone)]
+pub struct Convergents<I> {
+    terms: I,
+    // (p_(k-1), q_(k-1)) and (p_(k-2), q_(k-2))
+    prev: (BigInt, BigInt),
+    prev2: (BigInt, BigInt),
+}
+
+/// Returns the convergents of the continued fraction with the given partial quotients, from
+/// p_k = a_k p_(k-1) + p_(k-2) and q_k = a_k q_(k-1) + q_(k-2).
+pub fn convergents<I: IntoIterator<Item = BigInt>>(terms: I) -> Convergents<I::IntoIter> {
+    Convergents {
+        terms: terms.into_iter(),
+        prev: (BigInt::from_u64(1), BigInt::from_u64(0)),
+        prev2: (BigInt::from_u64(0), BigInt::from_u64(1)),
+    }
+}
+
+impl<I: Iterator<Item = BigInt>> Iterator for Convergents<I> {
+    type Item = (BigInt, BigInt);
+
+    fn next(&mut self) -> Option<(BigInt, BigInt)> {
+        let a = self.terms.next()?;
+        let p = &a * &self.prev.0 + &self.prev2.0;
+        let q = &a * &self.prev.1 + &self.prev2.1;
+        self.prev2 = std::mem::replace(&mut self.prev, (p.clone(), q.clone()));
+        Some((p, q))
+    }
+}
+
+/// Returns the partial quotients [a_0; a_1, ..., a_n] of p / q for q != 0, by the Euclidean
+/// algorithm with floored quotients. a_0 may be negative, the other terms are positive and the
+/// last one is at least 2 unless it is a_0.
+pub fn rational_continued_fraction(p: &BigInt, q: &BigInt) -> Vec<BigInt> {
+    if q.sgn == 0 {
+        panic!("Divide by zero error");
+    }
+    let (mut p, mut q) = if q.sgn == -1 {
+        (-p, -q)
+    } else {
+        (p.clone(), q.clone())
+    };
+    let mut terms = Vec::new();
+    while q.sgn != 0 {
+        let (a, r) = divmod(&p, &q);
+        terms.push(a);
+        p = std::mem::replace(&mut q, r);
+    }
+    terms
+}
+
+/// The fraction p / q in lowest terms with q > 0 whose partial quotients are the given terms,
+/// all positive after the first.
+pub fn continued_fraction_to_rational(terms: &[BigInt]) -> (BigInt, BigInt) {
+    match convergents(terms.iter().cloned()).last() {
+        Some(pq) => pq,
+        None => panic!("A continued fraction needs at least one term"),
+    }
+}
+
+/// Iterator over the semiconvergents of a continued fraction, created by `semiconvergents`.
+#[derive(Debug, Clone)]
+pub struct Semiconvergents<I> {
+    terms: I,
+    // the current partial quotient a_k and the last m yielded for it
+    a: BigInt,
+    m: BigInt,
+    prev: (BigInt, BigInt),
+    prev2: (BigInt, BigInt),
+}
+
+/// Returns the semiconvergents (m p_(k-1) + p_(k-2)) / (m q_(k-1) + q_(k-2)) of a continued
+/// fraction for k >= 1 and 1 <= m <= a_k in order, after the first convergent a_0 / 1. Each
+/// run ends with the convergent p_k / q_k at m = a_k. The best rational approximations are
+/// all among them.
+pub fn semiconvergents<I: IntoIterator<Item = BigInt>>(terms: I) -> Semiconvergents<I::IntoIter> {
+    let zero = BigInt::from_u64(0);
+    Semiconvergents {
+        terms: terms.into_iter(),
+        a: zero.clone(),
+        m: zero.clone(),
+        prev: (BigInt::from_u64(1), zero.clone()),
+        prev2: (zero, BigInt::from_u64(1)),
+    }
+}
+
+impl<I: Iterator<Item = BigInt>> Iterator for Semiconvergents<I> {
+    type Item = (BigInt, BigInt);
+
+    fn next(&mut self) -> Option<(BigInt, BigInt)> {
+        if self.m == self.a {
+            let a = self.terms.next()?;
+            if self.prev.1.sgn == 0 {
+                // a_0 / 1 has no semiconvergents before it
+                let c = (a, BigInt::from_u64(1));
+                self.prev2 = std::mem::replace(&mut self.prev, c.clone());
+                return Some(c);
+            }
+            self.a = a;
+            self.m = BigInt::from_u64(0);
+        }
+        self.m = &self.m + BigInt::from_u64(1);
+        let c = (
+            &self.m * &self.prev.0 + &self.prev2.0,
+            &self.m * &self.prev.1 + &self.prev2.1,
+        );
+        if self.m == self.a {
+            self.prev2 = std::mem::replace(&mut self.prev, c.clone());
+        }
+        Some(c)
+    }
+}
+
+/// The closest fraction to p / q with a denominator of at most max_denominator, which must be
+/// positive, like Python's `Fraction.limit_denominator`. It is either the last convergent
+/// within the bound or the largest semiconvergent after it, and ties go to the convergent.
+pub fn best_rational_approximation(
+    p: &BigInt,
+    q: &BigInt,
+    max_denominator: &BigInt,
+) -> (BigInt, BigInt) {
+    if max_denominator.sgn != 1 {
+        panic!("The maximum denominator must be positive");
+    }
+    let terms = rational_continued_fraction(p, q);
+    let (p, q) = continued_fraction_to_rational(&terms);
+    if q <= *max_denominator {
+        return (p, q);
+    }
+    // p_(k-1) / q_(k-1) is the last convergent within the bound
+    let mut prev = (BigInt::from_u64(1), BigInt::from_u64(0));
+    let mut prev2 = (BigInt::from_u64(0), BigInt::from_u64(1));
+    for c in convergents(terms) {
+        if c.1 > *max_denominator {
+            break;
+        }
+        prev2 = std::mem::replace(&mut prev, c);
+    }
+    let m = (max_denominator - &prev2.1) / &prev.1;
+    let semi = (&m * &prev.0 + &prev2.0, &m * &prev.1 + &prev2.1);
+    // |p / q - a / b| compared as |p b - a q| / b with the common factor 1 / q dropped
+    let mut d1 = &p * &prev.1 - &prev.0 * &q;
+    let mut d2 = &p * &semi.1 - &semi.0 * &q;
+    d1.sgn = d1.sgn.abs();
+    d2.sgn = d2.sgn.abs();
+    if d1 * &semi.1 <= d2 * &prev.1 {
+        prev
+    } else {
+        semi
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mparith::{build_bigint, BigInt};
+
+    fn b(n: u64) -> BigInt {
+        BigInt::from_u64(n)
+    }
+
+    #[test]
+    fn sqrt_continued_fraction_test() {
+        let cases: [(u64, u64, &[u64]); 5] = [
+            (2, 1, &[2]),
+            (7, 2, &[1, 1, 1, 4]),
+            (13, 3, &[1, 1, 1, 1, 6]),
+            (61, 7, &[1, 4, 3, 1, 2, 2, 1, 3, 4, 1, 14]),
+            (94, 9, &[1, 2, 3, 1, 1, 5, 1, 8, 1, 5, 1, 1, 3, 2, 1, 18]),
+        ];
+        for (d, a0, period) in cases {
+            let cf = super::sqrt_continued_fraction(&b(d));
+            assert_eq!(b(a0), cf.a0);
+            assert_eq!(period.iter().map(|&a| b(a)).collect::<Vec<_>>(), cf.period);
+            assert!(!cf.is_perfect_square());
+        }
+        for d in [0, 1, 16, 1000000] {
+            let cf = super::sqrt_continued_fraction(&b(d));
+            assert!(cf.is_perfect_square());
+            assert_eq!(1, cf.terms().count());
+        }
+        let surd = super::QuadraticSurd::new(&b(7));
+        let triples: Vec<(BigInt, BigInt, BigInt)> = surd.take(3).collect();
+        assert_eq!(
+            vec![(b(0), b(1), b(2)), (b(2), b(3), b(1)), (b(1), b(2), b(1))],
+            triples
+        );
+    }
+
+    #[test]
+    fn sqrt_period_structure_test() {
+        // the period is a palindrome followed by 2 a_0, and the convergent before the end of
+        // the period solves p^2 - D q^2 = (-1)^l
+        for d in (2..400).chain([1000003]) {
+            let d = b(d);
+            let cf = super::sqrt_continued_fraction(&d);
+            if cf.is_perfect_square() {
+                continue;
+            }
+            let l = cf.period.len();
+            assert_eq!(&cf.a0 + &cf.a0, cf.period[l - 1]);
+            for i in 0..(l - 1) {
+                assert_eq!(cf.period[i], cf.period[l - 2 - i]);
+            }
+            let (p, q) = cf.convergents().nth(l - 1).unwrap();
+            let norm = &p * &p - &d * &q * &q;
+            let expected = if l.is_multiple_of(2) { b(1) } else { -b(1) };
+            assert_eq!(expected, norm);
+        }
+        let cf = super::sqrt_continued_fraction(&build_bigint(
+            "10000000000000000000000000000000000000001",
+        ));
+        assert_eq!(vec![build_bigint("200000000000000000000")], cf.period);
+    }
+
+    #[test]
+    fn convergents_test() {
+        let cf = super::sqrt_continued_fraction(&b(2));
+        let c: Vec<(BigInt, BigInt)> = cf.convergents().take(5).collect();
+        assert_eq!(
+            vec![
+                (b(1), b(1)),
+                (b(3), b(2)),
+                (b(7), b(5)),
+                (b(17), b(12)),
+                (b(41), b(29))
+            ],
+            c
+        );
+        let finite: Vec<(BigInt, BigInt)> =
+            super::convergents(vec![b(3), b(7), b(15), b(1)]).collect();
+        assert_eq!(
+            vec![
+                (b(3), b(1)),
+                (b(22), b(7)),
+                (b(333), b(106)),
+                (b(355), b(113))
+            ],
+            finite
+        );
+    }
+
+    fn i(n: i64) -> BigInt {
+        BigInt::from_i64(n)
+    }
+
+    fn pairs(v: &[(i64, i64)]) -> Vec<(BigInt, BigInt)> {
+        v.iter().map(|&(p, q)| (i(p), i(q))).collect()
+    }
+
+    #[test]
+    fn rational_continued_fraction_test() {
+        let cases: [(i64, i64, &[i64]); 6] = [
+            (415, 93, &[4, 2, 6, 7]),
+            (-415, 93, &[-5, 1, 1, 6, 7]),
+            (415, -93, &[-5, 1, 1, 6, 7]),
+            (830, 186, &[4, 2, 6, 7]),
+            (7, 1, &[7]),
+            (0, 5, &[0]),
+        ];
+        for (p, q, terms) in cases {
+            let terms: Vec<BigInt> = terms.iter().map(|&t| i(t)).collect();
+            assert_eq!(terms, super::rational_continued_fraction(&i(p), &i(q)));
+            let (x, y) = super::continued_fraction_to_rational(&terms);
+            assert!(y.sgn == 1 && x * i(q) == y * i(p));
+        }
+        // the convergents of 3.141592653589793 start with those of pi
+        let pi = build_bigint("3141592653589793");
+        let terms = super::rational_continued_fraction(&pi, &build_bigint("1000000000000000"));
+        assert_eq!(vec![b(3), b(7), b(15), b(1), b(292)], terms[..5].to_vec());
+        let c: Vec<(BigInt, BigInt)> = super::convergents(terms).take(4).collect();
+        assert_eq!(pairs(&[(3, 1), (22, 7), (333, 106), (355, 113)]), c);
+    }
+
+    #[test]
+    fn semiconvergents_test() {
+        let terms = super::rational_continued_fraction(&i(-415), &i(93));
+        let s: Vec<(BigInt, BigInt)> = super::semiconvergents(terms).collect();
+        let expected = [
+            (-5, 1),
+            (-4, 1),
+            (-9, 2),
+            (-13, 3),
+            (-22, 5),
+            (-31, 7),
+            (-40, 9),
+            (-49, 11),
+            (-58, 13),
+            (-67, 15),
+            (-125, 28),
+            (-183, 41),
+            (-241, 54),
+            (-299, 67),
+            (-357, 80),
+            (-415, 93),
+        ];
+        assert_eq!(pairs(&expected), s);
+        // each run of sqrt(2) = [1; 2, 2, ...] is one semiconvergent and one convergent
+        let cf = super::sqrt_continued_fraction(&b(2));
+        let s: Vec<(BigInt, BigInt)> = super::semiconvergents(cf.terms()).take(5).collect();
+        assert_eq!(pairs(&[(1, 1), (2, 1), (3, 2), (4, 3), (7, 5)]), s);
+    }
+
+    #[test]
+    fn best_rational_approximation_test() {
+        // from Python's Fraction.limit_denominator
+        let pi = (
+            build_bigint("3141592653589793"),
+            build_bigint("1000000000000000"),
+        );
+        let cases = [
+            (1, (3, 1)),
+            (7, (22, 7)),
+            (100, (311, 99)),
+            (1000, (355, 113)),
+            (30000, (94053, 29938)),
+        ];
+        for (max, (p, q)) in cases {
+            assert_eq!(
+                (i(p), i(q)),
+                super::best_rational_approximation(&pi.0, &pi.1, &i(max))
+            );
+        }
+        let cases = [
+            (1, (-4, 1)),
+            (2, (-9, 2)),
+            (5, (-9, 2)),
+            (10, (-40, 9)),
+            (20, (-58, 13)),
+            (93, (-415, 93)),
+            (1000, (-415, 93)),
+        ];
+        for (max, (p, q)) in cases {
+            assert_eq!(
+                (i(p), i(q)),
+                super::best_rational_approximation(&i(-830), &i(186), &i(max))
+            );
+        }
+        // 3 and 4 are equally close to 7 / 2, and the convergent wins
+        assert_eq!(
+            (i(3), i(1)),
+            super::best_rational_approximation(&i(7), &i(2), &i(1))
+        );
+    }
+}
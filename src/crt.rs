@@ -0,0 +1,288 @@
+use crate::gcd::{gcd, mod_inverse};
+use crate::modular::inv_mod_u64;
+use crate::mparith::{divmod, BigInt};
+use crate::primes::prev_prime;
+
+/// Solves the system x = r_i (mod m_i) given as (r_i, m_i) pairs, returning (x, m) where m
+/// is the least common multiple of the moduli and x is the solution in [0, m), or None if
+/// the congruences are inconsistent.
+///
+/// The moduli do not need to be pairwise coprime: two congruences are compatible exactly
+/// when their residues agree modulo the gcd of their moduli. The empty system gives (0, 1).
+pub fn crt(congruences: &[(BigInt, BigInt)]) -> Option<(BigInt, BigInt)> {
+    let mut x = BigInt::from_u64(0);
+    let mut m = BigInt::from_u64(1);
+    for (r, n) in congruences {
+        if n.sgn != 1 {
+            panic!("Modular arithmetic must be done with positive integers");
+        }
+        // x + m t = r (mod n) has a solution t exactly when g = gcd(m, n) divides r - x
+        let g = gcd(&m, n);
+        let (diff, rem) = divmod(&(r - &x), &g);
+        if rem.sgn != 0 {
+            return None;
+        }
+        let n_over_g = n / &g;
+        let t = (diff * mod_inverse(&(&m / &g), &n_over_g).unwrap()) % &n_over_g;
+        let l = &m * n_over_g;
+        x = (x + &m * t) % &l;
+        m = l;
+    }
+    Some((x, m))
+}
+
+/// Reconstruction of integers from their residues modulo several word-sized primes.
+///
+/// A computation whose result is an integer of bounded size, such as a determinant or a
+/// resultant, can be carried out modulo each prime with machine arithmetic and the result
+/// recovered with Garner's algorithm. The primes are the largest ones below 2^62, so that
+/// products of two residues fit in a u128.
+#[derive(Debug, Clone)]
+pub struct MultiModular {
+    primes: Vec<u64>,
+    // inverses[i] = (p_0 p_1 ... p_(i-1))^-1 mod p_i
+    inverses: Vec<u64>,
+    modulus: BigInt,
+}
+
+impl MultiModular {
+    /// Chooses enough primes to reconstruct every integer x with |x| <= bound.
+    pub fn new(bound: &BigInt) -> MultiModular {
+        let mut bound = bound.clone();
+        bound.sgn = 1;
+        // the symmetric range of the product must contain [-bound, bound]
+        let needed = &bound + &bound;
+        let mut primes = Vec::new();
+        let mut modulus = BigInt::from_u64(1);
+        let mut p = BigInt::from_u64(1 << 62);
+        while modulus <= needed {
+            p = prev_prime(&p).unwrap();
+            modulus = modulus * &p;
+            primes.push(p.to_u64().unwrap());
+        }
+
+        let mut inverses = Vec::with_capacity(primes.len());
+        for (i, &p) in primes.iter().enumerate() {
+            let product = primes[..i]
+                .iter()
+                .fold(1_u128, |acc, &q| acc * (q % p) as u128 % p as u128);
+            inverses.push(inv_mod_u64(product as u64, p));
+        }
+        MultiModular {
+            primes,
+            inverses,
+            modulus,
+        }
+    }
+
+    /// The primes the computation has to be carried out modulo.
+    pub fn primes(&self) -> &[u64] {
+        &self.primes
+    }
+
+    /// The product of the primes.
+    pub fn modulus(&self) -> &BigInt {
+        &self.modulus
+    }
+
+    /// Reduces a modulo each prime, giving residues in [0, p).
+    pub fn residues(&self, a: &BigInt) -> Vec<u64> {
+        self.primes
+            .iter()
+            .map(|&p| {
+                let r = a.rem_small(p);
+                if a.sgn == -1 && r != 0 {
+                    p - r
+                } else {
+                    r
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the x in [0, M) with the given residues, M being the product of the primes.
+    pub fn reconstruct(&self, residues: &[u64]) -> BigInt {
+        if residues.len() != self.primes.len() {
+            panic!("Expected one residue per prime");
+        }
+        // Garner: x = v_0 + v_1 p_0 + v_2 p_0 p_1 + ... with 0 <= v_i < p_i
+        let mut digits: Vec<u64> = Vec::with_capacity(self.primes.len());
+        for (i, &p) in self.primes.iter().enumerate() {
+            let p128 = p as u128;
+            let mut partial: u128 = 0;
+            let mut radix: u128 = 1;
+            for (j, &v) in digits.iter().enumerate() {
+                partial = (partial + v as u128 % p128 * radix) % p128;
+                radix = radix * (self.primes[j] % p) as u128 % p128;
+            }
+            let diff = (residues[i] as u128 % p128 + p128 - partial) % p128;
+            digits.push((diff * self.inverses[i] as u128 % p128) as u64);
+        }
+        let mut x = BigInt::from_u64(0);
+        for (i, &v) in digits.iter().enumerate().rev() {
+            x = x * BigInt::from_u64(self.primes[i]) + BigInt::from_u64(v);
+        }
+        x
+    }
+
+    /// Returns the x in (-M/2, M/2] with the given residues, which is the integer itself
+    /// whenever its absolute value is at most the bound given to `new`.
+    pub fn reconstruct_signed(&self, residues: &[u64]) -> BigInt {
+        let x = self.reconstruct(residues);
+        if &x + &x > self.modulus {
+            x - &self.modulus
+        } else {
+            x
+        }
+    }
+
+    /// Runs f modulo each prime and reconstructs the signed integer from its results.
+    pub fn evaluate<F: FnMut(u64) -> u64>(&self, mut f: F) -> BigInt {
+        let residues: Vec<u64> = self.primes.iter().map(|&p| f(p)).collect();
+        self.reconstruct_signed(&residues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MultiModular;
+    use crate::mparith::{build_bigint, Abs, BigInt};
+    use std::fs::File;
+    use std::io::{self, BufRead};
+    use std::path::Path;
+    const A_DEC: usize = 1;
+    const B_DEC: usize = 3;
+
+    fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(filename)?;
+        Ok(io::BufReader::new(file).lines())
+    }
+
+    fn pairs(v: &[(i64, u64)]) -> Vec<(BigInt, BigInt)> {
+        v.iter()
+            .map(|&(r, m)| (BigInt::from_i64(r), BigInt::from_u64(m)))
+            .collect()
+    }
+
+    #[test]
+    fn crt_test() {
+        assert_eq!(
+            Some((BigInt::from_u64(23), BigInt::from_u64(105))),
+            super::crt(&pairs(&[(2, 3), (3, 5), (2, 7)]))
+        );
+        assert_eq!(
+            Some((BigInt::from_u64(11), BigInt::from_u64(12))),
+            super::crt(&pairs(&[(3, 4), (5, 6)]))
+        );
+        assert_eq!(
+            Some((BigInt::from_u64(10), BigInt::from_u64(60))),
+            super::crt(&pairs(&[(-50, 12), (10, 30), (0, 10)]))
+        );
+        assert_eq!(None, super::crt(&pairs(&[(1, 4), (2, 6)])));
+        assert_eq!(None, super::crt(&pairs(&[(3, 5), (1, 2), (4, 10)])));
+        assert_eq!(
+            Some((BigInt::from_u64(0), BigInt::from_u64(1))),
+            super::crt(&[])
+        );
+    }
+
+    #[test]
+    fn crt_bigint_test() {
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.take(100).map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                let b = build_bigint(v[B_DEC]);
+                let x = &a * &b;
+                // moduli sharing the factor 6
+                let m1 = (&a).abs() * BigInt::from_u64(6) + BigInt::from_u64(6);
+                let m2 = b.abs() * BigInt::from_u64(6) + BigInt::from_u64(12);
+                let (y, m) = super::crt(&[(&x % &m1, m1.clone()), (&x % &m2, m2.clone())]).unwrap();
+                assert_eq!(crate::gcd::lcm(&m1, &m2), m);
+                assert_eq!(&x % &m, y);
+                let bad = &x % &m2 + BigInt::from_u64(1);
+                assert_eq!(None, super::crt(&[(&x % &m1, m1), (bad, m2)]));
+            }
+        }
+    }
+
+    #[test]
+    fn multi_modular_test() {
+        let bound = BigInt::from_u64(1) << BigInt::from_u64(700);
+        let mm = MultiModular::new(&bound);
+        assert_eq!(12, mm.primes().len());
+        assert!(mm.modulus() > &(&bound + &bound));
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.take(200).map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                let b = build_bigint(v[B_DEC]);
+                let x = &a * &b;
+                let residues: Vec<u64> = mm
+                    .residues(&a)
+                    .iter()
+                    .zip(mm.residues(&b))
+                    .zip(mm.primes())
+                    .map(|((&ra, rb), &p)| (ra as u128 * rb as u128 % p as u128) as u64)
+                    .collect();
+                assert_eq!(x, mm.reconstruct_signed(&residues));
+                assert_eq!(&x % mm.modulus(), mm.reconstruct(&residues));
+            }
+        }
+    }
+
+    #[test]
+    fn multi_modular_determinant_test() {
+        // det by Gaussian elimination modulo p
+        let det_mod = |m: &[Vec<i64>], p: u64| -> u64 {
+            let n = m.len();
+            let p128 = p as u128;
+            let mut a: Vec<Vec<u128>> = m
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|&x| x.rem_euclid(p as i64) as u128)
+                        .collect()
+                })
+                .collect();
+            let mut det: u128 = 1;
+            for c in 0..n {
+                let Some(r) = (c..n).find(|&r| a[r][c] != 0) else {
+                    return 0;
+                };
+                if r != c {
+                    a.swap(r, c);
+                    det = (p128 - det) % p128;
+                }
+                det = det * a[c][c] % p128;
+                let inv = crate::modular::inv_mod_u64(a[c][c] as u64, p) as u128;
+                let pivot = a[c].clone();
+                for row in a.iter_mut().skip(c + 1) {
+                    let f = row[c] * inv % p128;
+                    for (x, y) in row.iter_mut().zip(pivot.iter()).skip(c) {
+                        *x = (*x + (p128 - f) * y) % p128;
+                    }
+                }
+            }
+            det as u64
+        };
+        let m = vec![
+            vec![999999999989, -45, 7, 0],
+            vec![3, -999999999959, 1, 2],
+            vec![-7, 11, 999999999937, -5],
+            vec![1, 2, 3, -999999999961],
+        ];
+        // Hadamard's bound is below 2^200
+        let mm = MultiModular::new(&(BigInt::from_u64(1) << BigInt::from_u64(200)));
+        let det = mm.evaluate(|p| det_mod(&m, p));
+        assert_eq!(
+            build_bigint("999999999846000000008117999999836065000000874628"),
+            det
+        );
+        let m = vec![vec![2, 0], vec![0, -3]];
+        assert_eq!(BigInt::from_i64(-6), mm.evaluate(|p| det_mod(&m, p)));
+    }
+}
@@ -0,0 +1,333 @@
+use crate::arith::carmichael;
+use crate::crt::crt;
+use crate::factor::{factor, Factorization};
+use crate::gcd::{gcd, mod_inverse};
+use crate::modular::pow_mod;
+use crate::mparith::{isqrt, BigInt, Pow};
+use std::collections::HashMap;
+
+// prime orders below this are solved with baby-step giant-step, larger ones with Pollard's rho
+const BSGS_LIMIT: u64 = 1 << 32;
+
+// starting points Pollard's rho tries before giving up
+const RHO_ATTEMPTS: u64 = 16;
+
+// a walk is abandoned after this many multiples of sqrt(q) steps without a collision, which
+// is far beyond the expected sqrt(pi q / 2) for a walk in a group of prime order q
+const RHO_STEP_FACTOR: u64 = 8;
+
+// the order of a unit a whose order divides the factored number e
+fn order_dividing(a: &BigInt, n: &BigInt, e: &BigInt, e_factors: &Factorization) -> BigInt {
+    let one = BigInt::from_u64(1);
+    let mut order = e.clone();
+    for (q, _) in &e_factors.factors {
+        while (&order % q).sgn == 0 && pow_mod(a, &(&order / q), n) == one {
+            order = order / q;
+        }
+    }
+    order
+}
+
+/// Returns the multiplicative order of a modulo n, the smallest k >= 1 with a^k = 1 (mod n),
+/// or None when a is not coprime to n.
+///
+/// The order divides the exponent of the unit group, so this factors n and then that
+/// exponent, dividing out each prime while the power stays 1.
+pub fn multiplicative_order(a: &BigInt, n: &BigInt) -> Option<BigInt> {
+    if n.sgn != 1 {
+        panic!("Modular arithmetic must be done with positive integers");
+    }
+    if gcd(a, n) != BigInt::from_u64(1) {
+        return None;
+    }
+    let e = carmichael(n);
+    let e_factors = factor(&e);
+    Some(order_dividing(&(a % n), n, &e, &e_factors))
+}
+
+/// Returns the smallest primitive root modulo n, or None if the unit group modulo n is not
+/// cyclic (n other than 1, 2, 4, p^k and 2p^k for an odd prime p).
+pub fn primitive_root(n: &BigInt) -> Option<BigInt> {
+    if n.sgn != 1 {
+        panic!("Modular arithmetic must be done with positive integers");
+    }
+    let one = BigInt::from_u64(1);
+    if *n <= BigInt::from_u64(2) {
+        return Some(n - &one);
+    }
+    let f = factor(n);
+    let odd_primes = f.factors.iter().filter(|(p, _)| p.is_odd()).count();
+    let twos = match f.factors.first() {
+        Some((p, k)) if !p.is_odd() => *k,
+        _ => 0,
+    };
+    if !(odd_primes == 0 && twos == 2 || odd_primes == 1 && twos <= 1) {
+        return None;
+    }
+
+    // g is a generator exactly when g^(e/q) != 1 for every prime q dividing the exponent e
+    let e = carmichael(&f);
+    let e_factors = factor(&e);
+    let mut g = BigInt::from_u64(2);
+    loop {
+        if gcd(&g, n) == one
+            && e_factors
+                .factors
+                .iter()
+                .all(|(q, _)| pow_mod(&g, &(&e / q), n) != one)
+        {
+            return Some(g);
+        }
+        g = g + &one;
+    }
+}
+
+/// Returns the smallest x >= 0 with g^x = h (mod n), or None if h is not a power of g.
+///
+/// g must be coprime to n. The order of g is factored and the logarithm is found modulo
+/// each prime power with Pohlig-Hellman, one base-q digit at a time, and the digits come from
+/// logarithms in subgroups of prime order q: `discrete_log_bsgs` for q below 2^32 and
+/// `discrete_log_rho` above. The results are combined with the Chinese remainder theorem.
+pub fn discrete_log(g: &BigInt, h: &BigInt, n: &BigInt) -> Option<BigInt> {
+    if n.sgn != 1 {
+        panic!("Modular arithmetic must be done with positive integers");
+    }
+    let one = BigInt::from_u64(1);
+    if gcd(g, n) != one {
+        panic!("The base of a discrete logarithm must be a unit");
+    }
+    let (g, h) = (g % n, h % n);
+    if *n == one {
+        return Some(BigInt::from_u64(0));
+    }
+    let e = carmichael(n);
+    let order = order_dividing(&g, n, &e, &factor(&e));
+    let order_factors = factor(&order);
+
+    let mut congruences = Vec::new();
+    for (q, k) in &order_factors.factors {
+        let qk = q.pow(&BigInt::from_u64(*k as u64));
+        let cofactor = &order / &qk;
+        let g_q = pow_mod(&g, &cofactor, n);
+        let h_q = pow_mod(&h, &cofactor, n);
+        // gamma has order q; x = d_0 + d_1 q + ... with gamma^d_i read off
+        // (h_q g_q^-x_i)^(q^(k - 1 - i))
+        let gamma = pow_mod(&g_q, &(&qk / q), n);
+        let g_q_inverse = mod_inverse(&g_q, n).unwrap();
+        let mut x = BigInt::from_u64(0);
+        let mut q_i = one.clone();
+        for i in 0..*k {
+            let shifted = (pow_mod(&g_q_inverse, &x, n) * &h_q) % n;
+            let target = pow_mod(&shifted, &(&qk / &q_i / q), n);
+            let d = if *q < BigInt::from_u64(BSGS_LIMIT) {
+                discrete_log_bsgs(&gamma, &target, n, q)?
+            } else {
+                discrete_log_rho(&gamma, &target, n, q)?
+            };
+            x = x + d * &q_i;
+            if i + 1 < *k {
+                q_i = q_i * q;
+            }
+        }
+        congruences.push((x, qk));
+    }
+    let (x, _) = crt(&congruences)?;
+    if pow_mod(&g, &x, n) == h {
+        Some(x)
+    } else {
+        None
+    }
+}
+
+/// Returns the smallest x in [0, order) with g^x = h (mod n) by baby-step giant-step, or
+/// None if there is none, where order is a bound on the order of g modulo n.
+///
+/// Uses a table of ceil(sqrt(order)) baby steps.
+pub fn discrete_log_bsgs(g: &BigInt, h: &BigInt, n: &BigInt, order: &BigInt) -> Option<BigInt> {
+    let one = BigInt::from_u64(1);
+    let mut m = isqrt(order);
+    if &m * &m < *order {
+        m = m + &one;
+    }
+    let m_u64 = m
+        .to_u64()
+        .expect("order too large for baby-step giant-step");
+
+    // baby steps g^j, keeping the smallest j for each value
+    let mut table = HashMap::new();
+    let mut power = one.clone();
+    for j in 0..m_u64 {
+        table.entry(power.clone()).or_insert(j);
+        power = (power * g) % n;
+    }
+    // giant steps h g^(-im)
+    let giant = mod_inverse(&power, n)?;
+    let mut gamma = h % n;
+    for i in 0..m_u64 {
+        if let Some(&j) = table.get(&gamma) {
+            let x = BigInt::from_u64(i) * &m + BigInt::from_u64(j);
+            return if x < *order { Some(x) } else { None };
+        }
+        gamma = (gamma * &giant) % n;
+    }
+    None
+}
+
+/// Returns x in [0, q) with g^x = h (mod n) by Pollard's rho, or None if no logarithm was
+/// found, where q is the order of g, which must be prime.
+///
+/// The walk multiplies by h, squares or multiplies by g depending on the residue of the
+/// current element modulo 3, tracking its exponents in g and h, and Floyd's cycle finding
+/// gives a collision g^a h^b = g^A h^B that yields x when b - B is invertible modulo q.
+/// A walk that finds no collision within a few multiples of sqrt(q) steps, as can happen when
+/// g does not have order q, is restarted from another random point.
+pub fn discrete_log_rho(g: &BigInt, h: &BigInt, n: &BigInt, q: &BigInt) -> Option<BigInt> {
+    let h = h % n;
+    let step = |(x, a, b): &(BigInt, BigInt, BigInt)| match x.rem_small(3) {
+        0 => ((x * &h) % n, a.clone(), (b + BigInt::from_u64(1)) % q),
+        1 => ((x * x) % n, (a + a) % q, (b + b) % q),
+        _ => ((x * g) % n, (a + BigInt::from_u64(1)) % q, b.clone()),
+    };
+    let max_steps = isqrt(q)
+        .to_u64()
+        .map_or(u64::MAX, |r| (r + 1).saturating_mul(RHO_STEP_FACTOR));
+    // starting exponents are drawn with a generator seeded by q, so the answer for given
+    // arguments is reproducible
+    let mut state = q.mag[0] as u64 ^ 0x9e37_79b9_7f4a_7c15;
+    let mut random_exponent = || {
+        let mut e = BigInt::from_u64(0);
+        for _ in 0..q.len {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            e = (e << BigInt::from_u64(64)) + BigInt::from_u64(state);
+        }
+        e % q
+    };
+    'attempts: for _ in 0..RHO_ATTEMPTS {
+        // start from g^a0 h^b0
+        let a0 = random_exponent();
+        let b0 = random_exponent();
+        let x0 = (pow_mod(g, &a0, n) * pow_mod(&h, &b0, n)) % n;
+        let mut tortoise = (x0, a0, b0);
+        let mut hare = step(&tortoise);
+        let mut steps = 0;
+        while tortoise.0 != hare.0 {
+            if steps == max_steps {
+                continue 'attempts;
+            }
+            tortoise = step(&tortoise);
+            hare = step(&step(&hare));
+            steps += 1;
+        }
+        // g^a h^b = g^A h^B gives x (B - b) = a - A
+        let db = (&tortoise.2 - &hare.2) % q;
+        if let Some(inverse) = mod_inverse(&db, q) {
+            let x = ((&hare.1 - &tortoise.1) * inverse) % q;
+            if pow_mod(g, &x, n) == h {
+                return Some(x);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mparith::{build_bigint, BigInt};
+
+    fn b(n: u64) -> BigInt {
+        BigInt::from_u64(n)
+    }
+
+    #[test]
+    fn multiplicative_order_test() {
+        let cases = [
+            (2, 7, 3),
+            (3, 7, 6),
+            (10, 49, 42),
+            (2, 15, 4),
+            (7, 64, 8),
+            (1, 9, 1),
+        ];
+        for (a, n, order) in cases {
+            assert_eq!(Some(b(order)), super::multiplicative_order(&b(a), &b(n)));
+        }
+        assert_eq!(None, super::multiplicative_order(&b(6), &b(15)));
+        // 4 = 5^2 and 5 is a primitive root modulo p
+        let p = build_bigint("1000000007");
+        assert_eq!(
+            Some(build_bigint("500000003")),
+            super::multiplicative_order(&b(4), &p)
+        );
+    }
+
+    #[test]
+    fn primitive_root_test() {
+        let cases = [
+            (1, 0),
+            (2, 1),
+            (4, 3),
+            (7, 3),
+            (9, 2),
+            (18, 5),
+            (23, 5),
+            (1000000007, 5),
+        ];
+        for (n, g) in cases {
+            assert_eq!(Some(b(g)), super::primitive_root(&b(n)));
+        }
+        for n in [8, 15, 12, 21] {
+            assert_eq!(None, super::primitive_root(&b(n)));
+        }
+    }
+
+    #[test]
+    fn discrete_log_test() {
+        let p = b(1000000007);
+        let g = b(5);
+        for x in [0, 1, 2, 12345, 999999999] {
+            let h = crate::modular::pow_mod(&g, &b(x), &p);
+            assert_eq!(Some(b(x)), super::discrete_log(&g, &h, &p));
+        }
+        // 4 has order 3 modulo 7 and 3 is not one of its powers
+        assert_eq!(None, super::discrete_log(&b(4), &b(3), &b(7)));
+        assert_eq!(Some(b(2)), super::discrete_log(&b(4), &b(2), &b(7)));
+        // composite modulus with a non-cyclic unit group
+        let n = b(2 * 2 * 2 * 3 * 3 * 5 * 7 * 11 * 13);
+        let h = crate::modular::pow_mod(&b(17), &b(53), &n);
+        let x = super::discrete_log(&b(17), &h, &n).unwrap();
+        assert_eq!(h, crate::modular::pow_mod(&b(17), &x, &n));
+    }
+
+    #[test]
+    fn discrete_log_pohlig_hellman_test() {
+        // p - 1 = 2^5 * 3^5 * 5 * 7 * ... * 53
+        let p = build_bigint("42235549386438297970081");
+        let g = super::primitive_root(&p).unwrap();
+        let x = build_bigint("31415926535897932384626");
+        let h = crate::modular::pow_mod(&g, &x, &p);
+        assert_eq!(Some(x), super::discrete_log(&g, &h, &p));
+    }
+
+    #[test]
+    fn discrete_log_bsgs_rho_test() {
+        // 2^k has prime order q modulo p = kq + 1
+        let q = b(2147483647);
+        let p = b(2147483647 * 46 + 1);
+        let g = crate::modular::pow_mod(&b(2), &b(46), &p);
+        let x = b(1234567890);
+        let h = crate::modular::pow_mod(&g, &x, &p);
+        assert_eq!(Some(x.clone()), super::discrete_log_rho(&g, &h, &p, &q));
+        let q = b(65537);
+        let p = b(65537 * 14 + 1);
+        let g = crate::modular::pow_mod(&b(2), &b(14), &p);
+        let h = crate::modular::pow_mod(&g, &b(40000), &p);
+        assert_eq!(Some(b(40000)), super::discrete_log_bsgs(&g, &h, &p, &q));
+        // 5 generates the whole group modulo 10^9 + 7, not a subgroup of order 101, so every
+        // walk gives up long before it would cycle
+        let p = b(1000000007);
+        let h = crate::modular::pow_mod(&b(5), &b(123456789), &p);
+        assert_eq!(None, super::discrete_log_rho(&b(5), &h, &p, &b(101)));
+    }
+}
@@ -0,0 +1,381 @@
+use crate::gcd::{gcd, mod_inverse, word_gcd};
+use crate::modular::{ModContext, MontgomeryCtx};
+use crate::mparith::BigInt;
+use crate::roots::small_primes;
+
+/// Parameters of the elliptic curve method.
+///
+/// Each curve runs stage 1 with bound `b1` and stage 2 with bound `b2`, and the curves use
+/// the Suyama parameters sigma, sigma + 1, ..., sigma + curves - 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcmParams {
+    pub b1: u64,
+    pub b2: u64,
+    pub curves: u32,
+    pub sigma: u64,
+}
+
+// (digits, B1, curves) from the table of recommended parameters in GMP-ECM's README
+const ECM_LEVELS: [(u32, u64, u32); 9] = [
+    (15, 2000, 25),
+    (20, 11000, 90),
+    (25, 50000, 300),
+    (30, 250000, 700),
+    (35, 1000000, 1800),
+    (40, 3000000, 5100),
+    (45, 11000000, 10600),
+    (50, 43000000, 19300),
+    (55, 110000000, 49000),
+];
+
+// stage 2 sieves the primes for this many giant steps at a time
+const STAGE2_BLOCK: u64 = 64;
+
+impl EcmParams {
+    /// Parameters with the given bounds and number of curves, starting from sigma = 6.
+    pub fn new(b1: u64, b2: u64, curves: u32) -> EcmParams {
+        EcmParams {
+            b1,
+            b2,
+            curves,
+            sigma: 6,
+        }
+    }
+
+    /// Parameters that are expected to find a prime factor with the given number of decimal
+    /// digits, with b2 = 100 * b1.
+    pub fn for_digits(digits: u32) -> EcmParams {
+        let &(_, b1, curves) = ECM_LEVELS
+            .iter()
+            .find(|&&(d, _, _)| d >= digits)
+            .unwrap_or(&ECM_LEVELS[ECM_LEVELS.len() - 1]);
+        EcmParams::new(b1, 100 * b1, curves)
+    }
+}
+
+// a point (X : Z) on a Montgomery curve, with both coordinates in the Montgomery domain
+#[derive(Debug, Clone)]
+struct Point {
+    x: BigInt,
+    z: BigInt,
+}
+
+struct Curve<'a> {
+    ctx: &'a MontgomeryCtx,
+    a24: BigInt, // (A + 2) / 4
+}
+
+impl Curve<'_> {
+    fn double(&self, p: &Point) -> Point {
+        let ctx = self.ctx;
+        let t1 = ctx.sqr_mod(&ctx.add_mod(&p.x, &p.z));
+        let t2 = ctx.sqr_mod(&ctx.sub_mod(&p.x, &p.z));
+        let t3 = ctx.sub_mod(&t1, &t2);
+        Point {
+            x: ctx.mul_mod(&t1, &t2),
+            z: ctx.mul_mod(&t3, &ctx.add_mod(&t2, &ctx.mul_mod(&self.a24, &t3))),
+        }
+    }
+
+    // p + q given the difference p - q
+    fn add(&self, p: &Point, q: &Point, diff: &Point) -> Point {
+        let ctx = self.ctx;
+        let u = ctx.mul_mod(&ctx.sub_mod(&p.x, &p.z), &ctx.add_mod(&q.x, &q.z));
+        let v = ctx.mul_mod(&ctx.add_mod(&p.x, &p.z), &ctx.sub_mod(&q.x, &q.z));
+        Point {
+            x: ctx.mul_mod(&diff.z, &ctx.sqr_mod(&ctx.add_mod(&u, &v))),
+            z: ctx.mul_mod(&diff.x, &ctx.sqr_mod(&ctx.sub_mod(&u, &v))),
+        }
+    }
+
+    // k * p for k >= 1 with the Montgomery ladder
+    fn mul(&self, p: &Point, k: u64) -> Point {
+        let mut r0 = p.clone();
+        let mut r1 = self.double(p);
+        for i in (0..(63 - k.leading_zeros())).rev() {
+            if (k >> i) & 1 == 1 {
+                r0 = self.add(&r1, &r0, p);
+                r1 = self.double(&r1);
+            } else {
+                r1 = self.add(&r1, &r0, p);
+                r0 = self.double(&r0);
+            }
+        }
+        r0
+    }
+}
+
+/// Looks for a nontrivial factor of the odd number n with Lenstra's elliptic curve method.
+///
+/// Every curve is a Montgomery curve By^2 = x^3 + Ax^2 + x from Suyama's parametrization,
+/// whose group order is divisible by 12. Stage 1 multiplies the starting point by every
+/// prime power up to b1, and stage 2 looks for a single remaining prime in (b1, b2] with
+/// the baby-step giant-step continuation. Returns None if none of the curves found a factor.
+pub fn ecm(n: &BigInt, params: &EcmParams) -> Option<BigInt> {
+    let ctx = MontgomeryCtx::new(n);
+    // the primes up to b1 for stage 1, and the sieving primes for the stage 2 segments,
+    // which reach b2 + D
+    let stage1 = small_primes(params.b1 as usize);
+    let top = params.b2 + stage2_d(params.b2);
+    let sieving = small_primes((top as f64).sqrt() as usize + 1);
+    for i in 0..params.curves {
+        let sigma = params.sigma + i as u64;
+        if let Some(d) = ecm_curve(&ctx, sigma, params.b1, params.b2, &stage1, &sieving) {
+            return Some(d);
+        }
+    }
+    None
+}
+
+// the giant step D of stage 2
+fn stage2_d(b2: u64) -> u64 {
+    if b2 < 100 * 2310 {
+        210
+    } else {
+        2310
+    }
+}
+
+// flags for [lo, lo + len), true for the primes, where the sieving primes reach the square
+// root of lo + len
+fn prime_flags(lo: u64, len: usize, sieving: &[u32]) -> Vec<bool> {
+    let end = lo + len as u64;
+    let mut flags = vec![true; len];
+    for v in lo..end.min(2) {
+        flags[(v - lo) as usize] = false;
+    }
+    for &p in sieving {
+        let p = p as u64;
+        if p * p >= end {
+            break;
+        }
+        let mut k = (p * p).max(lo.div_ceil(p) * p);
+        while k < end {
+            flags[(k - lo) as usize] = false;
+            k += p;
+        }
+    }
+    flags
+}
+
+// runs a single curve with the primes up to b1 and the sieving primes for stage 2
+fn ecm_curve(
+    ctx: &MontgomeryCtx,
+    sigma: u64,
+    b1: u64,
+    b2: u64,
+    stage1: &[u32],
+    sieving: &[u32],
+) -> Option<BigInt> {
+    let n = ctx.modulus();
+    let one = BigInt::from_u64(1);
+
+    // Suyama: u = sigma^2 - 5, v = 4 sigma, the point (u^3 : v^3) and
+    // (A + 2) / 4 = (v - u)^3 (3u + v) / (16 u^3 v)
+    let sigma = BigInt::from_u64(sigma);
+    let u = (&sigma * &sigma - BigInt::from_u64(5)) % n;
+    let v = (BigInt::from_u64(4) * &sigma) % n;
+    let u3 = (&u * &u * &u) % n;
+    let vmu = (&v - &u) % n;
+    let num = (&vmu * &vmu * &vmu * (BigInt::from_u64(3) * &u + &v)) % n;
+    let den = (BigInt::from_u64(16) * &u3 * &v) % n;
+    let den_inv = match mod_inverse(&den, n) {
+        Some(x) => x,
+        None => {
+            // a failed inversion is a factor found for free
+            let g = gcd(&den, n);
+            return if g == *n { None } else { Some(g) };
+        }
+    };
+    let curve = Curve {
+        ctx,
+        a24: ctx.to_domain(&(num * den_inv)),
+    };
+    let mut q = Point {
+        x: ctx.to_domain(&u3),
+        z: ctx.to_domain(&(&v * &v * &v)),
+    };
+
+    // stage 1
+    for &p in stage1 {
+        let p = p as u64;
+        let mut pk = p;
+        while pk * p <= b1 {
+            pk *= p;
+        }
+        q = curve.mul(&q, pk);
+    }
+    let g = gcd(&ctx.out_of_domain(&q.z), n);
+    if g == *n {
+        return None;
+    }
+    if g != one {
+        return Some(g);
+    }
+    if b2 <= b1 {
+        return None;
+    }
+
+    // stage 2: each prime s in (b1, b2] above D/2 is written as mD + j or mD - j with
+    // j < D/2 coprime to D, and mDQ = +-jQ exactly when X(mDQ) Z(jQ) - X(jQ) Z(mDQ) = 0 mod p
+    let d = stage2_d(b2);
+    let mut acc = ctx.one();
+
+    // the primes in (b1, D/2) have no such form, so sQ is computed directly for them
+    let direct_end = b2.min(d / 2);
+    if b1 < direct_end {
+        let is_prime = prime_flags(0, direct_end as usize + 1, sieving);
+        for s in (b1 + 1)..=direct_end {
+            if is_prime[s as usize] {
+                acc = ctx.mul_mod(&acc, &curve.mul(&q, s).z);
+            }
+        }
+    }
+
+    // baby steps jQ for odd j < D/2
+    let q2 = curve.double(&q);
+    let mut baby: Vec<Option<Point>> = vec![None; (d / 2) as usize];
+    let mut prev = q.clone();
+    let mut cur = curve.add(&q2, &q, &q);
+    baby[1] = Some(q.clone());
+    for j in (3..(d / 2)).step_by(2) {
+        if word_gcd(j, d) == 1 {
+            baby[j as usize] = Some(cur.clone());
+        }
+        let next = curve.add(&cur, &q2, &prev);
+        prev = cur;
+        cur = next;
+    }
+
+    // giant steps mDQ
+    let giant = curve.mul(&q, d);
+    let m_first = (b1 / d).max(1);
+    let m_last = (b2 + d / 2) / d;
+    let mut t = curve.mul(&giant, m_first);
+    let mut t_next = curve.mul(&giant, m_first + 1);
+    for block in (m_first..=m_last).step_by(STAGE2_BLOCK as usize) {
+        // every mD +- j of the block lies in [block D - D/2, block_end D + D/2)
+        let block_end = (block + STAGE2_BLOCK - 1).min(m_last);
+        let base = block * d - d / 2;
+        let is_prime = prime_flags(base, ((block_end - block + 1) * d) as usize, sieving);
+        for m in block..=block_end {
+            for (j, point) in baby.iter().enumerate() {
+                let Some(point) = point else { continue };
+                let j = j as u64;
+                let lo = m * d - j;
+                let hi = m * d + j;
+                if (lo > b1 && lo <= b2 && is_prime[(lo - base) as usize])
+                    || (hi > b1 && hi <= b2 && is_prime[(hi - base) as usize])
+                {
+                    let diff =
+                        ctx.sub_mod(&ctx.mul_mod(&t.x, &point.z), &ctx.mul_mod(&point.x, &t.z));
+                    acc = ctx.mul_mod(&acc, &diff);
+                }
+            }
+            let next = curve.add(&t_next, &giant, &t);
+            t = t_next;
+            t_next = next;
+        }
+    }
+    let g = gcd(&ctx.out_of_domain(&acc), n);
+    if g == one || g == *n {
+        None
+    } else {
+        Some(g)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EcmParams;
+    use crate::mparith::build_bigint;
+    use crate::roots::small_primes;
+
+    #[test]
+    fn ecm_stages_test() {
+        let p = build_bigint("1000000007");
+        let n = &p * build_bigint("1000000000000000000000000000057");
+        // the curve with sigma = 14 has a 300-smooth order modulo p
+        let params = EcmParams {
+            b1: 300,
+            b2: 300,
+            curves: 1,
+            sigma: 14,
+        };
+        assert_eq!(Some(p.clone()), super::ecm(&n, &params));
+        // the curve with sigma = 9 needs stage 2
+        let mut params = EcmParams {
+            b1: 300,
+            b2: 300,
+            curves: 1,
+            sigma: 9,
+        };
+        assert_eq!(None, super::ecm(&n, &params));
+        params.b2 = 20000;
+        assert_eq!(Some(p.clone()), super::ecm(&n, &params));
+        // D = 2310 and several sieved blocks
+        params.b2 = 1000000;
+        assert_eq!(Some(p.clone()), super::ecm(&n, &params));
+
+        assert_eq!(Some(p), super::ecm(&n, &EcmParams::new(300, 20000, 10)));
+        assert_eq!(None, super::ecm(&n, &EcmParams::new(300, 20000, 3)));
+
+        // the curve with sigma = 13 needs a prime below D/2 = 105 modulo 1000003
+        let p = build_bigint("1000003");
+        let n = &p * build_bigint("1000000000000000000000000000057");
+        let mut params = EcmParams {
+            b1: 50,
+            b2: 50,
+            curves: 1,
+            sigma: 13,
+        };
+        assert_eq!(None, super::ecm(&n, &params));
+        params.b2 = 100;
+        assert_eq!(Some(p.clone()), super::ecm(&n, &params));
+        params.b2 = 300000;
+        assert_eq!(Some(p), super::ecm(&n, &params));
+    }
+
+    #[test]
+    fn prime_flags_test() {
+        let sieving = small_primes(100);
+        let flags = super::prime_flags(9000, 1000, &sieving);
+        let expected: Vec<u32> = small_primes(9999)
+            .into_iter()
+            .filter(|&p| p >= 9000)
+            .collect();
+        let found: Vec<u32> = (0..1000)
+            .filter(|&i| flags[i])
+            .map(|i| 9000 + i as u32)
+            .collect();
+        assert_eq!(expected, found);
+        let flags = super::prime_flags(0, 30, &sieving);
+        assert_eq!(10, flags.iter().filter(|&&f| f).count());
+        let flags = super::prime_flags(1, 10, &sieving);
+        assert_eq!(
+            vec![false, true, true, false, true, false, true, false, false, false],
+            flags
+        );
+    }
+
+    #[test]
+    fn ecm_fifteen_digit_test() {
+        let p = build_bigint("100000000000031");
+        let n = &p * build_bigint("1000000000000000000000000000057");
+        let mut params = EcmParams::for_digits(15);
+        assert_eq!(
+            EcmParams {
+                b1: 2000,
+                b2: 200000,
+                curves: 25,
+                sigma: 6
+            },
+            params
+        );
+        params.sigma = 12;
+        params.curves = 1;
+        assert_eq!(Some(p), super::ecm(&n, &params));
+        assert_eq!(3000000, EcmParams::for_digits(38).b1);
+        assert_eq!(110000000, EcmParams::for_digits(80).b1);
+    }
+}
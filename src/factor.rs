@@ -0,0 +1,487 @@
+use crate::ecm::{ecm, EcmParams};
+use crate::gcd::gcd;
+use crate::modular::{ModContext, MontgomeryCtx};
+use crate::mparith::{divmod, BigInt};
+use crate::primality::{is_probable_prime, Primality, PrimalityTest};
+use crate::roots::{perfect_power, small_primes};
+use crate::siqs::siqs;
+use std::fmt;
+
+// factor() trial divides by every prime below this bound before anything else
+const TRIAL_DIVISION_LIMIT: usize = 10000;
+
+// bounds factor() uses for Pollard's p - 1
+const PM1_B1: u64 = 2000;
+const PM1_B2: u64 = 100000;
+
+// number of steps of Brent's rho between gcds
+const RHO_BATCH: u64 = 128;
+
+// steps of Brent's rho tried before switching to ECM
+const RHO_ITERATIONS: u64 = 1 << 16;
+
+// cofactors with at least this many decimal digits go to the quadratic sieve once ECM has
+// looked for factors of up to a third of their digits
+const SIQS_MIN_DIGITS: u32 = 40;
+
+/// The factorization of a nonzero integer: its sign and its prime factors with multiplicities
+/// in increasing order.
+///
+/// Factors above 2^64 are only known to be probable primes (see `is_probable_prime`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Factorization {
+    pub sgn: isize,
+    pub factors: Vec<(BigInt, u32)>,
+}
+
+impl Factorization {
+    /// The number that was factored.
+    pub fn value(&self) -> BigInt {
+        let mut res = BigInt::from_i64(self.sgn as i64);
+        for (p, k) in &self.factors {
+            for _ in 0..*k {
+                res = res * p;
+            }
+        }
+        res
+    }
+}
+
+impl fmt::Display for Factorization {
+    // writes the factorization as a product, e.g. "-2^3 * 5" or "1"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.sgn == -1 {
+            write!(f, "-")?;
+        }
+        if self.factors.is_empty() {
+            return write!(f, "1");
+        }
+        for (i, (p, k)) in self.factors.iter().enumerate() {
+            if i > 0 {
+                write!(f, " * ")?;
+            }
+            if *k == 1 {
+                write!(f, "{}", p)?;
+            } else {
+                write!(f, "{}^{}", p, k)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Factors n into primes.
+///
+/// Small factors are removed by trial division, perfect powers are split with
+/// `perfect_power`, and the remaining composite cofactors are split with Pollard's p - 1,
+/// a limited run of Brent's version of Pollard's rho and then ECM with bounds for
+/// increasingly large factors, recursing until every factor passes the primality test.
+/// Cofactors of 40 digits and more only get ECM runs aimed at factors of up to a third of
+/// their size before the self-initializing quadratic sieve takes over (see `siqs`).
+pub fn factor(n: &BigInt) -> Factorization {
+    if n.sgn == 0 {
+        panic!("Do not factor zero");
+    }
+    let mut m = n.clone();
+    m.sgn = 1;
+
+    let mut found = Vec::new();
+    let primes = small_primes(TRIAL_DIVISION_LIMIT);
+    let mut i = 0;
+    while i < primes.len() && m > BigInt::from_u64(1) {
+        // reduce m modulo a product of several primes at once to save on long divisions
+        let mut prod: u64 = 1;
+        let mut j = i;
+        while j < primes.len() && prod < (1 << 48) {
+            prod *= primes[j] as u64;
+            j += 1;
+        }
+        let rem = (&m % BigInt::from_u64(prod)).to_u64().unwrap();
+        for &p in &primes[i..j] {
+            if rem.is_multiple_of(p as u64) {
+                let p = BigInt::from_u64(p as u64);
+                let mut k = 0;
+                loop {
+                    let (q, r) = divmod(&m, &p);
+                    if r.sgn != 0 {
+                        break;
+                    }
+                    m = q;
+                    k += 1;
+                }
+                found.push((p, k));
+            }
+        }
+        i = j;
+    }
+
+    // every remaining cofactor is paired with the power it appears to
+    let mut stack = vec![(m, 1)];
+    while let Some((m, k)) = stack.pop() {
+        if m == BigInt::from_u64(1) {
+            continue;
+        }
+        if is_probable_prime(&m, PrimalityTest::BailliePsw) != Primality::Composite {
+            found.push((m, k));
+            continue;
+        }
+        if let Some((b, e)) = perfect_power(&m) {
+            stack.push((b, k * e));
+            continue;
+        }
+        let d = find_factor(&m);
+        let q = &m / &d;
+        stack.push((d, k));
+        stack.push((q, k));
+    }
+
+    found.sort();
+    let mut factors: Vec<(BigInt, u32)> = Vec::new();
+    for (p, k) in found {
+        match factors.last_mut() {
+            Some((last, e)) if *last == p => *e += k,
+            _ => factors.push((p, k)),
+        }
+    }
+    Factorization {
+        sgn: n.sgn,
+        factors,
+    }
+}
+
+// returns a nontrivial factor of an odd composite that is not a perfect power
+fn find_factor(n: &BigInt) -> BigInt {
+    if let Some(d) = pollard_pm1(n, PM1_B1, PM1_B2) {
+        return d;
+    }
+    if let Some(d) = pollard_rho(n, 1, RHO_ITERATIONS) {
+        return d;
+    }
+
+    // ECM aimed at increasingly large factors, each level with fresh curves, with the
+    // quadratic sieve in between for large n
+    let n_digits = (n.bits() as f64 * 2_f64.log10()).ceil() as u32;
+    let siqs_after = if n_digits >= SIQS_MIN_DIGITS {
+        (n_digits / 3).max(15)
+    } else {
+        u32::MAX
+    };
+    let mut sigma = 6;
+    for digits in (15..=55).step_by(5) {
+        if digits > siqs_after && digits <= siqs_after + 5 {
+            if let Some(d) = siqs(n) {
+                return d;
+            }
+        }
+        let mut params = EcmParams::for_digits(digits);
+        params.sigma = sigma;
+        if let Some(d) = ecm(n, &params) {
+            return d;
+        }
+        sigma += params.curves as u64;
+    }
+    let mut c = 2;
+    loop {
+        if let Some(d) = pollard_rho(n, c, u64::MAX) {
+            return d;
+        }
+        c += 1;
+    }
+}
+
+/// Looks for a nontrivial factor of the odd number n with Brent's version of Pollard's rho,
+/// iterating x -> x^2 + c for at most max_iterations steps.
+///
+/// Returns None if no factor was found, which includes the case where the cycles modulo
+/// every prime factor were detected at the same time; another c usually fixes that.
+pub fn pollard_rho(n: &BigInt, c: u64, max_iterations: u64) -> Option<BigInt> {
+    let ctx = MontgomeryCtx::new(n);
+    let one = BigInt::from_u64(1);
+    let c = ctx.to_domain(&BigInt::from_u64(c));
+    let f = |x: &BigInt| ctx.add_mod(&ctx.sqr_mod(x), &c);
+
+    let mut y = ctx.to_domain(&BigInt::from_u64(2));
+    let mut x = y.clone();
+    let mut ys = y.clone();
+    let mut q = ctx.one();
+    let mut g = one.clone();
+    let mut r: u64 = 1;
+    let mut iterations: u64 = 0;
+    while g == one {
+        x = y.clone();
+        for _ in 0..r {
+            y = f(&y);
+        }
+        let mut k = 0;
+        while k < r && g == one {
+            ys = y.clone();
+            for _ in 0..RHO_BATCH.min(r - k) {
+                y = f(&y);
+                q = ctx.mul_mod(&q, &ctx.sub_mod(&x, &y));
+            }
+            g = gcd(&q, n);
+            k += RHO_BATCH;
+        }
+        iterations = iterations.saturating_add(2 * r);
+        r *= 2;
+        if g == one && iterations >= max_iterations {
+            return None;
+        }
+    }
+
+    // the batch overshot, so redo it one step at a time
+    if g == *n {
+        loop {
+            ys = f(&ys);
+            g = gcd(&ctx.sub_mod(&x, &ys), n);
+            if g != one {
+                break;
+            }
+        }
+    }
+    if g == *n {
+        None
+    } else {
+        Some(g)
+    }
+}
+
+/// Looks for a nontrivial factor of the odd number n with Pollard's p - 1 method.
+///
+/// Stage 1 finds a prime factor p when p - 1 is b1-smooth. Stage 2 additionally finds p
+/// when p - 1 is b1-smooth apart from a single odd prime in (b1, b2], stepping through those
+/// primes with precomputed powers for the gaps between them.
+pub fn pollard_pm1(n: &BigInt, b1: u64, b2: u64) -> Option<BigInt> {
+    let ctx = MontgomeryCtx::new(n);
+    let one = BigInt::from_u64(1);
+    let primes = small_primes(b2.max(b1) as usize);
+
+    // stage 1: a = 2^E where E is the product of the largest powers of primes below b1
+    let stage_one: Vec<(u64, u64)> = primes
+        .iter()
+        .map(|&p| p as u64)
+        .take_while(|&p| p <= b1)
+        .map(|p| {
+            let mut pk = p;
+            while pk * p <= b1 {
+                pk *= p;
+            }
+            (p, pk)
+        })
+        .collect();
+    let base = ctx.to_domain(&BigInt::from_u64(2));
+    let mut a = base.clone();
+    for &(_, pk) in &stage_one {
+        a = ctx.pow_mod(&a, &BigInt::from_u64(pk));
+    }
+    let g = gcd(&(ctx.out_of_domain(&a) - &one), n);
+    if g == *n {
+        // every prime factor was found at once, so redo the stage one prime at a time and
+        // stop as soon as the gcd is nontrivial
+        let mut a = base;
+        for &(p, pk) in &stage_one {
+            let mut pj = 1;
+            while pj < pk {
+                a = ctx.pow_mod(&a, &BigInt::from_u64(p));
+                pj *= p;
+                let g = gcd(&(ctx.out_of_domain(&a) - &one), n);
+                if g == *n {
+                    return None;
+                }
+                if g != one {
+                    return Some(g);
+                }
+            }
+        }
+        return None;
+    }
+    if g != one {
+        return Some(g);
+    }
+
+    // stage 2: accumulate the product of a^q - 1 over the odd primes q in (b1, b2], so
+    // that every gap between them is even
+    let stage_two: Vec<u64> = primes
+        .iter()
+        .map(|&p| p as u64)
+        .filter(|&p| p > b1.max(2))
+        .collect();
+    if stage_two.is_empty() {
+        return None;
+    }
+    let a2 = ctx.sqr_mod(&a);
+    let mut gaps = vec![a2.clone()]; // gaps[i] = a^(2i + 2)
+    let mut x = ctx.pow_mod(&a, &BigInt::from_u64(stage_two[0]));
+    let mut acc = ctx.sub_mod(&x, &ctx.one());
+    for w in stage_two.windows(2) {
+        let idx = ((w[1] - w[0]) / 2 - 1) as usize;
+        while gaps.len() <= idx {
+            let next = ctx.mul_mod(gaps.last().unwrap(), &a2);
+            gaps.push(next);
+        }
+        x = ctx.mul_mod(&x, &gaps[idx]);
+        acc = ctx.mul_mod(&acc, &ctx.sub_mod(&x, &ctx.one()));
+    }
+    let g = gcd(&acc, n);
+    if g == one || g == *n {
+        None
+    } else {
+        Some(g)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mparith::{build_bigint, Abs, Pow};
+    use crate::primality::{is_probable_prime, Primality, PrimalityTest};
+    use std::fs::File;
+    use std::io::{self, BufRead};
+    use std::path::Path;
+    const A_DEC: usize = 1;
+
+    fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(filename)?;
+        Ok(io::BufReader::new(file).lines())
+    }
+
+    #[test]
+    #[should_panic]
+    fn factor_zero_test() {
+        let _ = super::factor(&build_bigint("0"));
+    }
+
+    #[test]
+    fn factor_small_test() {
+        for n in 1..3000_u64 {
+            let mut expected = Vec::new();
+            let mut m = n;
+            let mut p = 2;
+            while m > 1 {
+                let mut k = 0;
+                while m % p == 0 {
+                    m /= p;
+                    k += 1;
+                }
+                if k > 0 {
+                    expected.push((build_bigint(&p.to_string()), k));
+                }
+                p += 1;
+            }
+            let f = super::factor(&build_bigint(&n.to_string()));
+            assert_eq!(expected, f.factors);
+            assert_eq!(1, f.sgn);
+        }
+        let f = super::factor(&build_bigint("-360"));
+        assert_eq!("-2^3 * 3^2 * 5", f.to_string());
+        assert_eq!(build_bigint("-360"), f.value());
+        assert_eq!("1", super::factor(&build_bigint("1")).to_string());
+    }
+
+    #[test]
+    fn factor_large_test() {
+        let p1 = build_bigint("1000003");
+        let p2 = build_bigint("1000000007");
+        let p3 = build_bigint("2305843009213693951");
+        let p4 = build_bigint("170141183460469231731687303715884105727");
+        let n = build_bigint("48") * (&p2).pow(build_bigint("2")) * &p1 * &p3 * &p4;
+        let f = super::factor(&n);
+        assert_eq!(
+            vec![
+                (build_bigint("2"), 4),
+                (build_bigint("3"), 1),
+                (p1.clone(), 1),
+                (p2.clone(), 2),
+                (p3.clone(), 1),
+                (p4.clone(), 1)
+            ],
+            f.factors
+        );
+        assert_eq!(n, f.value());
+
+        // a perfect power of a composite
+        let f = super::factor(&(&p1 * &p2).pow(build_bigint("6")));
+        assert_eq!(vec![(p1, 6), (p2, 6)], f.factors);
+    }
+
+    #[test]
+    fn factor_inputs_test() {
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                if a.sgn == 0 || (&a).abs() > build_bigint("1000000000000000000") {
+                    continue;
+                }
+                let f = super::factor(&a);
+                assert_eq!(a, f.value());
+                for (p, _) in f.factors {
+                    assert_eq!(
+                        Primality::Prime,
+                        is_probable_prime(&p, PrimalityTest::BailliePsw)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn factor_ecm_test() {
+        // too large for the rho run, and p - 1 = 2 * 5 * 13 * 29 * 547 * 48492137 is not smooth
+        let p = build_bigint("100000000000031");
+        let q = build_bigint("1000000000000000000000000000057");
+        let f = super::factor(&(&p * &q));
+        assert_eq!(vec![(p, 1), (q, 1)], f.factors);
+    }
+
+    #[test]
+    fn factor_siqs_test() {
+        // two 20-digit primes, out of reach of the ECM run before the quadratic sieve
+        let p = build_bigint("30000000000000000041");
+        let q = build_bigint("71428571428571428601");
+        let f = super::factor(&(&p * &q));
+        assert_eq!(vec![(p, 1), (q, 1)], f.factors);
+    }
+
+    #[test]
+    fn pollard_rho_test() {
+        let n = build_bigint("1000003") * build_bigint("1000033");
+        let d = super::pollard_rho(&n, 1, u64::MAX).unwrap();
+        assert!(d == build_bigint("1000003") || d == build_bigint("1000033"));
+        assert_eq!(None, super::pollard_rho(&n, 1, 10));
+    }
+
+    #[test]
+    fn pollard_pm1_test() {
+        // the order of 2 modulo q is not smooth
+        let q = build_bigint("10000000000000000000000000000000000000121");
+        // p - 1 = 2^5 * 3^5 * 5 * 7 * ... * 53
+        let p = build_bigint("42235549386438297970081");
+        assert_eq!(Some(p.clone()), super::pollard_pm1(&(&p * &q), 2000, 2000));
+        // p - 1 = 2 * 50021 needs stage 2
+        let p = build_bigint("100043");
+        assert_eq!(None, super::pollard_pm1(&(&p * &q), 2000, 2000));
+        assert_eq!(
+            Some(p.clone()),
+            super::pollard_pm1(&(&p * &q), 2000, 100000)
+        );
+        // both factors are found by stage 1 at once and have to be separated
+        let n = build_bigint("2305843009213693951")
+            * build_bigint("170141183460469231731687303715884105727");
+        assert_eq!(
+            Some(build_bigint("2305843009213693951")),
+            super::pollard_pm1(&n, 2000, 2000)
+        );
+        // with an empty stage 1 only stage 2 runs: the order of 2 modulo 127 is 7
+        let p = build_bigint("127");
+        assert_eq!(Some(p.clone()), super::pollard_pm1(&(&p * &q), 1, 100));
+        assert_eq!(Some(p.clone()), super::pollard_pm1(&(&p * &q), 0, 100));
+        assert_eq!(None, super::pollard_pm1(&(&p * &q), 1, 2));
+        assert_eq!(
+            None,
+            super::pollard_pm1(&build_bigint("1000000016000000063"), 1, 100)
+        );
+    }
+}
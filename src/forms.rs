@@ -0,0 +1,617 @@
+use crate::gcd::{extended_gcd, gcd};
+use crate::mparith::{abs, isqrt, BigInt};
+use crate::quadint::QuadInt;
+use std::collections::HashSet;
+
+/// The binary quadratic form a x^2 + b xy + c y^2 with discriminant b^2 - 4ac.
+///
+/// Equivalence is proper equivalence, under substitutions (x, y) -> (alpha x + beta y,
+/// gamma x + delta y) with alpha delta - beta gamma = 1. Definite forms are reduced when
+/// |b| <= a <= c, with b >= 0 if |b| = a or a = c, and there is exactly one reduced form in
+/// each class. Indefinite forms with a nonsquare discriminant are reduced when
+/// |sqrt(disc) - 2|a|| < b < sqrt(disc), and the reduced forms of a class make up one cycle
+/// of `rho`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QuadraticForm {
+    pub a: BigInt,
+    pub b: BigInt,
+    pub c: BigInt,
+}
+
+// (alpha, beta, gamma, delta) of a substitution, composed by matrix multiplication
+type Matrix = (BigInt, BigInt, BigInt, BigInt);
+
+fn mat_mul(m: &Matrix, n: &Matrix) -> Matrix {
+    (
+        &m.0 * &n.0 + &m.1 * &n.2,
+        &m.0 * &n.1 + &m.1 * &n.3,
+        &m.2 * &n.0 + &m.3 * &n.2,
+        &m.2 * &n.1 + &m.3 * &n.3,
+    )
+}
+
+// panics unless disc is the discriminant of an indefinite form that can be reduced
+fn check_indefinite(disc: &BigInt) -> BigInt {
+    let s = isqrt(disc);
+    if &s * &s == *disc {
+        panic!("Indefinite forms must have a nonsquare discriminant");
+    }
+    s
+}
+
+impl QuadraticForm {
+    pub fn new(a: &BigInt, b: &BigInt, c: &BigInt) -> QuadraticForm {
+        QuadraticForm {
+            a: a.clone(),
+            b: b.clone(),
+            c: c.clone(),
+        }
+    }
+
+    /// The form (a, b, (b^2 - disc) / 4a), which needs 4a to divide b^2 - disc.
+    pub fn from_discriminant(a: &BigInt, b: &BigInt, disc: &BigInt) -> QuadraticForm {
+        let four_a = BigInt::from_u64(4) * a;
+        let num = b * b - disc;
+        if a.sgn == 0 || (&num % &abs(&four_a)).sgn != 0 {
+            panic!("No form with this discriminant has these coefficients");
+        }
+        QuadraticForm::new(a, b, &(num / four_a))
+    }
+
+    /// The principal form (1, b, (b^2 - disc) / 4) with b = disc (mod 2), the identity of
+    /// composition.
+    pub fn principal(disc: &BigInt) -> QuadraticForm {
+        let b = BigInt::from_u64(disc.is_odd() as u64);
+        QuadraticForm::from_discriminant(&BigInt::from_u64(1), &b, disc)
+    }
+
+    pub fn discriminant(&self) -> BigInt {
+        &self.b * &self.b - BigInt::from_u64(4) * &self.a * &self.c
+    }
+
+    pub fn is_primitive(&self) -> bool {
+        gcd(&gcd(&self.a, &self.b), &self.c) == BigInt::from_u64(1)
+    }
+
+    pub fn is_positive_definite(&self) -> bool {
+        self.discriminant().sgn == -1 && self.a.sgn == 1
+    }
+
+    pub fn is_indefinite(&self) -> bool {
+        self.discriminant().sgn == 1
+    }
+
+    pub fn evaluate(&self, x: &BigInt, y: &BigInt) -> BigInt {
+        &self.a * x * x + &self.b * x * y + &self.c * y * y
+    }
+
+    /// The form after the substitution (x, y) -> (alpha x + beta y, gamma x + delta y).
+    pub fn transform(
+        &self,
+        alpha: &BigInt,
+        beta: &BigInt,
+        gamma: &BigInt,
+        delta: &BigInt,
+    ) -> QuadraticForm {
+        let two = BigInt::from_u64(2);
+        QuadraticForm {
+            a: self.evaluate(alpha, gamma),
+            b: &two * &self.a * alpha * beta
+                + &self.b * (alpha * delta + beta * gamma)
+                + &two * &self.c * gamma * delta,
+            c: self.evaluate(beta, delta),
+        }
+    }
+
+    /// The inverse class under composition, (a, -b, c).
+    pub fn inverse(&self) -> QuadraticForm {
+        QuadraticForm::new(&self.a, &-&self.b, &self.c)
+    }
+
+    pub fn is_reduced(&self) -> bool {
+        let disc = self.discriminant();
+        if disc.sgn == -1 {
+            let mut b_abs = self.b.clone();
+            b_abs.sgn = b_abs.sgn.abs();
+            self.a.sgn == 1
+                && b_abs <= self.a
+                && self.a <= self.c
+                && (self.b.sgn >= 0 || (b_abs != self.a && self.a != self.c))
+        } else {
+            let s = check_indefinite(&disc);
+            // |sqrt(disc) - 2|a|| < b < sqrt(disc) in integers
+            let mut two_a = &self.a + &self.a;
+            two_a.sgn = two_a.sgn.abs();
+            self.b.sgn == 1 && self.b <= s && &s - &self.b < two_a && two_a <= &s + &self.b
+        }
+    }
+
+    // the translation (x, y) -> (x + ty, y) that brings b into (-|a|, |a|]
+    fn normalize(&self) -> QuadraticForm {
+        // b + 2at in (-|a|, |a|]
+        let mut a_abs = self.a.clone();
+        a_abs.sgn = 1;
+        let two_a = &a_abs + &a_abs;
+        let b = (&self.b + &a_abs - BigInt::from_u64(1)) % &two_a - &a_abs + BigInt::from_u64(1);
+        QuadraticForm::from_discriminant(&self.a, &b, &self.discriminant())
+    }
+
+    /// The reduction operator rho(a, b, c) = (c, r, (r^2 - disc) / 4c) of indefinite forms,
+    /// where r = -b (mod 2c) lies in (-|c|, |c|] if |c| > sqrt(disc) and in
+    /// (sqrt(disc) - 2|c|, sqrt(disc)) otherwise. It maps reduced forms to reduced forms and
+    /// reaches a reduced form from any form after finitely many steps.
+    pub fn rho(&self) -> QuadraticForm {
+        self.rho_with_matrix().0
+    }
+
+    // rho and the substitution (0, -1, 1, t) that carries self to it
+    fn rho_with_matrix(&self) -> (QuadraticForm, Matrix) {
+        let disc = self.discriminant();
+        let s = check_indefinite(&disc);
+        let one = BigInt::from_u64(1);
+        let mut c_abs = self.c.clone();
+        c_abs.sgn = 1;
+        let two_c = &c_abs + &c_abs;
+        let minus_b = -&self.b;
+        let r = if c_abs > s {
+            (&minus_b + &c_abs - &one) % &two_c - &c_abs + &one
+        } else {
+            &s - (&s - &minus_b) % &two_c
+        };
+        let t = (&r + &self.b) / (&self.c + &self.c);
+        let next = QuadraticForm::from_discriminant(&self.c, &r, &disc);
+        (next, (BigInt::from_u64(0), -&one, one, t))
+    }
+
+    /// The reduced form equivalent to self: Gauss reduction for definite forms, which are
+    /// reduced as forms with a > 0 and negated back when a < 0, and iterated `rho` for
+    /// indefinite ones, which gives one of the forms of the cycle.
+    pub fn reduce(&self) -> QuadraticForm {
+        let disc = self.discriminant();
+        if disc.sgn == 1 {
+            let mut f = self.clone();
+            while !f.is_reduced() {
+                f = f.rho();
+            }
+            return f;
+        }
+        if disc.sgn == 0 {
+            panic!("Forms of discriminant 0 cannot be reduced");
+        }
+        if self.a.sgn == -1 {
+            let neg = QuadraticForm::new(&-&self.a, &-&self.b, &-&self.c).reduce();
+            return QuadraticForm::new(&-&neg.a, &-&neg.b, &-&neg.c);
+        }
+        let mut f = self.normalize();
+        while f.a > f.c {
+            f = QuadraticForm::new(&f.c, &-&f.b, &f.a).normalize();
+        }
+        if f.a == f.c && f.b.sgn == -1 {
+            f.b = -&f.b;
+        }
+        f
+    }
+
+    /// The cycle of reduced forms that contains the reduction of this indefinite form,
+    /// starting with that reduction.
+    pub fn cycle(&self) -> Vec<QuadraticForm> {
+        let start = self.reduce();
+        let mut forms = vec![start.clone()];
+        let mut f = start.rho();
+        while f != start {
+            forms.push(f.clone());
+            f = f.rho();
+        }
+        forms
+    }
+
+    /// Whether self and other are properly equivalent.
+    pub fn is_equivalent(&self, other: &QuadraticForm) -> bool {
+        let disc = self.discriminant();
+        if disc != other.discriminant() {
+            return false;
+        }
+        if disc.sgn == -1 {
+            return self.reduce() == other.reduce();
+        }
+        let target = other.reduce();
+        self.cycle().contains(&target)
+    }
+
+    /// Composition of forms with the same discriminant (Gauss and Dirichlet, in Shanks'
+    /// formulation as in Cohen, Algorithm 5.4.7), without reduction. The first coefficient of
+    /// the result is a1 a2 / g^2 for g = gcd(a1, a2, (b1 + b2) / 2), so it is a quarter of
+    /// the size of the discriminant for reduced inputs.
+    pub fn compose(&self, other: &QuadraticForm) -> QuadraticForm {
+        let disc = self.discriminant();
+        if disc != other.discriminant() {
+            panic!("Only forms of the same discriminant can be composed");
+        }
+        let (v1, v2, k, _) = self.united(other);
+        let b = &other.b + BigInt::from_u64(2) * &v2 * k;
+        // v1 is |a1| / g, and the first coefficient a1 a2 / g^2 takes the sign of both
+        let a = v1 * v2 * BigInt::from_i64(self.a.sgn as i64);
+        QuadraticForm::from_discriminant(&a, &b, &disc)
+    }
+
+    // (v1, v2, k, g) with the composition (v1 v2, b2 + 2 v2 k, ...), 0 <= k < v1
+    fn united(&self, other: &QuadraticForm) -> (BigInt, BigInt, BigInt, BigInt) {
+        let zero = BigInt::from_u64(0);
+        let two = BigInt::from_u64(2);
+        let (f1, f2) = (self, other);
+        let s = (&f1.b + &f2.b) / &two;
+        let n = &f2.b - &s;
+        let (d, y1, _) = extended_gcd(&f2.a, &f1.a);
+        let (x2, y2, g) = if (&s % &d).sgn == 0 {
+            (zero, BigInt::from_i64(-1), d)
+        } else {
+            let (g, u, v) = extended_gcd(&s, &d);
+            (u, -v, g)
+        };
+        let mut g = g;
+        g.sgn = g.sgn.abs();
+        let mut v1 = &f1.a / &g;
+        v1.sgn = v1.sgn.abs();
+        let v2 = &f2.a / &g;
+        let k = (y1 * y2 * n - x2 * &f2.c) % &v1;
+        (v1, v2, k, g)
+    }
+
+    /// The reduced composition of two positive definite forms by Shanks' NUCOMP.
+    ///
+    /// The composition (v1 v2, b2 + 2 v2 k, ...) corresponds to the lattice with basis v1 v2
+    /// and v2 k + omega, omega = (b2 + sqrt(disc)) / 2. Instead of building that form, whose
+    /// coefficients are as large as the discriminant, the Euclidean algorithm on (v1, k) is
+    /// stopped once the remainder drops below sqrt(v1 / v2) |disc / 4|^(1/4), and the last two
+    /// lattice vectors v2 r_i + c_i omega give a nearly reduced form directly, which is then
+    /// reduced.
+    pub fn nucomp(&self, other: &QuadraticForm) -> QuadraticForm {
+        let disc = self.discriminant();
+        if disc != other.discriminant() {
+            panic!("Only forms of the same discriminant can be composed");
+        }
+        if !self.is_positive_definite() || !other.is_positive_definite() {
+            panic!("NUCOMP needs positive definite forms");
+        }
+        // the larger first coefficient goes first, which keeps v1 / v2 >= 1
+        let (f1, f2) = if self.a >= other.a {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let (v1, v2, k, _) = f1.united(f2);
+        let mut quarter = disc.clone();
+        quarter.sgn = 1;
+        let l = isqrt(&isqrt(&(quarter / BigInt::from_u64(4))));
+        let bound = &v1 * &l * &l;
+
+        // vectors x A + y (v2 k + omega) = v2 r + y omega with r = x v1 + y k, as (r, x, y)
+        let mut prev = (v1.clone(), BigInt::from_u64(1), BigInt::from_u64(0));
+        let mut cur = (k, BigInt::from_u64(0), BigInt::from_u64(1));
+        while cur.0.sgn != 0 && &cur.0 * &cur.0 * &v2 > bound {
+            let q = &prev.0 / &cur.0;
+            let next = (
+                &prev.0 - &q * &cur.0,
+                &prev.1 - &q * &cur.1,
+                &prev.2 - &q * &cur.2,
+            );
+            prev = std::mem::replace(&mut cur, next);
+        }
+        if cur.0.sgn == 0 {
+            // the Euclidean algorithm ended before the bound, which only happens for small v1
+            return f1.compose(f2).reduce();
+        }
+        // a properly oriented basis (cur, +-prev)
+        let det = &cur.1 * &prev.2 - &cur.2 * &prev.1;
+        let sign = BigInt::from_i64(det.sgn as i64);
+        let (r1, y1) = (&cur.0, &cur.2);
+        let (r2, y2) = (&prev.0 * &sign, &prev.2 * &sign);
+        // with s_i = v2 r_i: N(s + y omega) = s^2 + b2 s y + a2 c2 y^2, and
+        // Tr((s1 + y1 omega) conj(s2 + y2 omega)) = 2 s1 s2 + b2 (s1 y2 + s2 y1) + 2 a2 c2 y1 y2
+        let (s1, s2) = (&v2 * r1, &v2 * &r2);
+        let ac = &f2.a * &f2.c;
+        let norm = |s: &BigInt, y: &BigInt| s * s + &f2.b * s * y + &ac * y * y;
+        let two = BigInt::from_u64(2);
+        let trace = &two * &s1 * &s2 + &f2.b * (&s1 * &y2 + &s2 * y1) + &two * &ac * y1 * &y2;
+        let a = &v1 * &v2;
+        QuadraticForm::new(&(norm(&s1, y1) / &a), &(trace / &a), &(norm(&s2, &y2) / &a)).reduce()
+    }
+}
+
+/// Returns the primitive reduced forms of discriminant disc, which must be 0 or 1 (mod 4) and
+/// not a square: the positive definite ones for disc < 0, one per class, and all reduced
+/// indefinite ones for disc > 0, which fall into one cycle per class.
+pub fn reduced_forms(disc: &BigInt) -> Vec<QuadraticForm> {
+    let four = BigInt::from_u64(4);
+    if (disc % &four).rem_small(4) > 1 {
+        panic!("Discriminants must be 0 or 1 (mod 4)");
+    }
+    let one = BigInt::from_u64(1);
+    let mut forms = Vec::new();
+    if disc.sgn == -1 {
+        // |b| <= a <= c implies 3a^2 <= |disc|
+        let mut a = one.clone();
+        while BigInt::from_u64(3) * &a * &a <= -disc {
+            let mut b = -&a + &one;
+            while b <= a {
+                let num = &b * &b - disc;
+                if (&num % (&four * &a)).sgn == 0 {
+                    let f = QuadraticForm::from_discriminant(&a, &b, disc);
+                    if f.is_reduced() && f.is_primitive() {
+                        forms.push(f);
+                    }
+                }
+                b = b + &one;
+            }
+            a = a + &one;
+        }
+    } else {
+        // 0 < b < sqrt(disc) and sqrt(disc) - b < 2|a| < sqrt(disc) + b
+        let s = check_indefinite(disc);
+        let mut b = BigInt::from_u64(disc.is_odd() as u64);
+        if b.sgn == 0 {
+            b = BigInt::from_u64(2);
+        }
+        while b <= s {
+            let num = &b * &b - disc;
+            let mut a = (&s - &b) / BigInt::from_u64(2) + &one;
+            while &a + &a <= &s + &b {
+                if (&num % (&four * &a)).sgn == 0 {
+                    for sign in [1, -1] {
+                        let signed = &a * BigInt::from_i64(sign);
+                        let f = QuadraticForm::from_discriminant(&signed, &b, disc);
+                        if f.is_primitive() {
+                            forms.push(f);
+                        }
+                    }
+                }
+                a = a + &one;
+            }
+            b = b + BigInt::from_u64(2);
+        }
+    }
+    forms
+}
+
+// the cycles of the primitive reduced indefinite forms
+fn cycles(disc: &BigInt) -> Vec<Vec<QuadraticForm>> {
+    let mut seen = HashSet::new();
+    let mut cycles = Vec::new();
+    for f in reduced_forms(disc) {
+        if seen.contains(&f) {
+            continue;
+        }
+        let cycle = f.cycle();
+        seen.extend(cycle.iter().cloned());
+        cycles.push(cycle);
+    }
+    cycles
+}
+
+/// The number of proper equivalence classes of primitive forms of discriminant disc, positive
+/// definite ones for disc < 0; for disc > 0 this is the narrow class number, twice the class
+/// number of the order when its fundamental unit has norm 1.
+pub fn narrow_class_number(disc: &BigInt) -> u64 {
+    if disc.sgn == -1 {
+        reduced_forms(disc).len() as u64
+    } else {
+        cycles(disc).len() as u64
+    }
+}
+
+/// The class number of the quadratic order of discriminant disc, the number of classes of
+/// primitive forms up to proper equivalence and multiplication by -1.
+pub fn class_number(disc: &BigInt) -> u64 {
+    let h = narrow_class_number(disc);
+    if disc.sgn == -1 || fundamental_unit(disc).unwrap().norm().sgn == -1 {
+        h
+    } else {
+        h / 2
+    }
+}
+
+/// Returns the fundamental unit eps = (t + u sqrt(disc)) / 2 > 1 of the quadratic order of
+/// positive nonsquare discriminant disc, or None for other discriminants. For disc = 4D this
+/// is the unit of Z[sqrt(D)] behind the Pell equation x^2 - D y^2 = +-1.
+///
+/// The substitutions of `rho` multiplied around the cycle of the principal form give an
+/// automorph of it, ((t - bu) / 2, -cu, au, (t + bu) / 2) for a solution of t^2 - disc u^2
+/// = 4; stopping halfway when the cycle reaches the negated principal form gives a solution
+/// of t^2 - disc u^2 = -4 instead, a unit of norm -1.
+pub fn fundamental_unit(disc: &BigInt) -> Option<QuadInt> {
+    if disc.sgn != 1 || disc.rem_small(4) > 1 {
+        return None;
+    }
+    let s = isqrt(disc);
+    if &s * &s == *disc {
+        return None;
+    }
+    let f = QuadraticForm::principal(disc).reduce();
+    let negated = QuadraticForm::new(&-&f.a, &f.b, &-&f.c);
+    let one = BigInt::from_u64(1);
+    let zero = BigInt::from_u64(0);
+    let mut m = (one.clone(), zero.clone(), zero, one);
+    let mut g = f.clone();
+    loop {
+        let (next, step) = g.rho_with_matrix();
+        m = mat_mul(&m, &step);
+        g = next;
+        if g == f || g == negated {
+            break;
+        }
+    }
+    // a substitution carrying f to -f is ((t - bu) / 2, -cu, au, -(t + bu) / 2) instead
+    let mut t = if g == f { &m.0 + &m.3 } else { &m.0 - &m.3 };
+    let mut u = &m.2 / &f.a;
+    t.sgn = t.sgn.abs();
+    u.sgn = u.sgn.abs();
+    if disc.is_odd() {
+        Some(QuadInt::new_half(&t, &u, disc))
+    } else {
+        let two = BigInt::from_u64(2);
+        Some(QuadInt::new(&(t / &two), &u, &(disc / (&two * &two))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuadraticForm;
+    use crate::mparith::BigInt;
+    use crate::pell::{pell_fundamental, pell_negative_fundamental};
+    use crate::quadint::QuadInt;
+
+    fn i(n: i64) -> BigInt {
+        BigInt::from_i64(n)
+    }
+
+    fn form(a: i64, b: i64, c: i64) -> QuadraticForm {
+        QuadraticForm::new(&i(a), &i(b), &i(c))
+    }
+
+    // unimodular substitutions to scramble reduced forms with
+    const SUBSTITUTIONS: [(i64, i64, i64, i64); 4] =
+        [(1, 0, 0, 1), (2, 1, 1, 1), (5, -3, -3, 2), (0, -1, 1, 7)];
+
+    #[test]
+    fn reduction_test() {
+        let f = form(2, 1, 3);
+        assert!(f.is_reduced() && f.is_positive_definite());
+        assert_eq!(i(-23), f.discriminant());
+        for (alpha, beta, gamma, delta) in SUBSTITUTIONS {
+            let g = f.transform(&i(alpha), &i(beta), &i(gamma), &i(delta));
+            assert_eq!(f, g.reduce());
+            assert!(g.is_equivalent(&f));
+        }
+        // (2, -1, 3) is improperly but not properly equivalent to (2, 1, 3)
+        assert!(!form(2, -1, 3).is_equivalent(&f));
+        assert_eq!(form(1, 1, 6), form(6, 11, 6).reduce());
+        assert_eq!(
+            form(-2, -1, -3),
+            form(-2, -1, -3)
+                .transform(&i(2), &i(1), &i(1), &i(1))
+                .reduce()
+        );
+        assert_eq!(form(3, 2, 3), form(3, -2, 3).reduce());
+
+        // indefinite forms reduce into a cycle of rho
+        let f = form(3, 8, -5);
+        assert_eq!(i(124), f.discriminant());
+        let cycle = f.cycle();
+        assert!(cycle
+            .iter()
+            .all(|g| g.is_reduced() && g.discriminant() == i(124)));
+        for (alpha, beta, gamma, delta) in SUBSTITUTIONS {
+            let g = f.transform(&i(alpha), &i(beta), &i(gamma), &i(delta));
+            assert!(cycle.contains(&g.reduce()));
+            assert!(g.is_equivalent(&f));
+        }
+        // x^2 - 3y^2 never takes the value -1, x^2 - 2y^2 does
+        assert!(!QuadraticForm::principal(&i(12)).is_equivalent(&form(-1, 0, 3)));
+        assert!(QuadraticForm::principal(&i(8)).is_equivalent(&form(-1, 0, 2)));
+    }
+
+    #[test]
+    fn class_number_test() {
+        // from the analytic class number formula
+        let negative = [
+            (-3, 1),
+            (-4, 1),
+            (-23, 3),
+            (-47, 5),
+            (-71, 7),
+            (-84, 4),
+            (-104, 6),
+            (-163, 1),
+            (-3299, 27),
+            (-4027, 9),
+        ];
+        for (disc, h) in negative {
+            assert_eq!(h, super::class_number(&i(disc)));
+            let forms = super::reduced_forms(&i(disc));
+            assert!(forms.iter().all(|f| f.is_reduced() && f.is_primitive()));
+        }
+        // (disc, class number, narrow class number)
+        let positive = [
+            (5, 1, 1),
+            (8, 1, 1),
+            (12, 1, 2),
+            (13, 1, 1),
+            (40, 2, 2),
+            (60, 2, 4),
+            (136, 2, 4),
+            (145, 4, 4),
+            (229, 3, 3),
+            (316, 3, 6),
+            (328, 4, 4),
+            (1009, 7, 7),
+        ];
+        for (disc, h, narrow) in positive {
+            assert_eq!(h, super::class_number(&i(disc)));
+            assert_eq!(narrow, super::narrow_class_number(&i(disc)));
+        }
+    }
+
+    #[test]
+    fn composition_test() {
+        for disc in [-23, -104, -3299, -4027] {
+            let disc = i(disc);
+            let forms = super::reduced_forms(&disc);
+            let identity = QuadraticForm::principal(&disc).reduce();
+            for f in &forms {
+                assert_eq!(identity, f.nucomp(&f.inverse()));
+                assert_eq!(*f, f.nucomp(&identity));
+                for g in &forms {
+                    let h = f.nucomp(g);
+                    assert!(h.is_reduced() && h.discriminant() == disc);
+                    assert_eq!(f.compose(g).reduce(), h);
+                    assert_eq!(g.nucomp(f), h);
+                }
+            }
+        }
+        // (2, 1, 3) generates the class group of order 3
+        let f = form(2, 1, 3);
+        assert_eq!(form(2, -1, 3), f.nucomp(&f));
+        assert_eq!(form(1, 1, 6), f.nucomp(&f).nucomp(&f));
+
+        // composition of indefinite classes, checked through the cycles
+        let disc = i(316);
+        let cycles = super::cycles(&disc);
+        assert_eq!(6, cycles.len());
+        let principal = QuadraticForm::principal(&disc);
+        for c in &cycles {
+            let f = &c[0];
+            assert!(f.compose(&f.inverse()).is_equivalent(&principal));
+            let cube = f.compose(f).compose(f);
+            let sixth = cube.compose(&cube);
+            assert!(sixth.is_equivalent(&principal));
+        }
+    }
+
+    #[test]
+    fn fundamental_unit_test() {
+        for d in 2..200 {
+            let Some((x, y)) = pell_fundamental(&i(d)) else {
+                continue;
+            };
+            let unit = super::fundamental_unit(&i(4 * d)).unwrap();
+            match pell_negative_fundamental(&i(d)) {
+                Some((u, v)) => assert_eq!(QuadInt::new(&u, &v, &i(d)), unit),
+                None => assert_eq!(QuadInt::new(&x, &y, &i(d)), unit),
+            }
+        }
+        assert_eq!(
+            QuadInt::new_half(&i(1), &i(1), &i(5)),
+            super::fundamental_unit(&i(5)).unwrap()
+        );
+        assert_eq!(
+            QuadInt::new_half(&i(3), &i(1), &i(13)),
+            super::fundamental_unit(&i(13)).unwrap()
+        );
+        assert_eq!(
+            QuadInt::new_half(&i(5), &i(1), &i(21)),
+            super::fundamental_unit(&i(21)).unwrap()
+        );
+        assert!(super::fundamental_unit(&i(16)).is_none());
+        assert!(super::fundamental_unit(&i(-4)).is_none());
+    }
+}
@@ -0,0 +1,621 @@
+use crate::mparith::{abs, divmod, BigInt};
+
+// number of bits stored in each limb of a BigInt
+const LIMB_BITS: usize = isize::BITS as usize - 2;
+const LIMB_MASK: u64 = (1 << LIMB_BITS) - 1;
+// operands with at least this many limbs are reduced with the half-gcd instead of Lehmer steps;
+// half_gcd_speed_test in release mode shows the two breaking even between 1000 and 1500 limbs
+// and the half-gcd about 1.5x faster at 3000 and 2x at 6000
+const HGCD_CUTOFF: usize = 1500;
+// below this many limbs the half-gcd recursion switches to Lehmer steps; base cases of 100,
+// 400 and 800 limbs measured no faster
+const HGCD_BASECASE_CUTOFF: usize = 200;
+
+/// A cofactor matrix [[m11, m12], [m21, m22]] built from Euclidean quotients.
+///
+/// It has non-negative entries and determinant +1 or -1, and relates a pair to its reduced
+/// form by (a, b) = M (alpha, beta).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GcdMatrix {
+    pub m11: BigInt,
+    pub m12: BigInt,
+    pub m21: BigInt,
+    pub m22: BigInt,
+    det: isize,
+}
+
+impl GcdMatrix {
+    pub fn identity() -> GcdMatrix {
+        GcdMatrix {
+            m11: BigInt::from_u64(1),
+            m12: BigInt::from_u64(0),
+            m21: BigInt::from_u64(0),
+            m22: BigInt::from_u64(1),
+            det: 1,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.m12.sgn == 0 && self.m21.sgn == 0
+    }
+
+    pub fn det(&self) -> isize {
+        self.det
+    }
+
+    // self = self * [[q, 1], [1, 0]]
+    fn push_quotient(&mut self, q: &BigInt) {
+        let m11 = q * &self.m11 + &self.m12;
+        let m21 = q * &self.m21 + &self.m22;
+        self.m12 = std::mem::replace(&mut self.m11, m11);
+        self.m22 = std::mem::replace(&mut self.m21, m21);
+        self.det = -self.det;
+    }
+
+    // removes the last quotient pushed and returns it
+    fn pop_quotient(&mut self) -> BigInt {
+        // the last quotient is the smaller of the two column ratios, see Knuth's
+        // analysis of continuants: at least one row has m12 > previous m12
+        let mut q = &self.m11 / &self.m12;
+        if self.m22.sgn != 0 {
+            let q2 = &self.m21 / &self.m22;
+            if q2 < q {
+                q = q2;
+            }
+        }
+        let m12 = &self.m11 - &q * &self.m12;
+        let m22 = &self.m21 - &q * &self.m22;
+        self.m11 = std::mem::replace(&mut self.m12, m12);
+        self.m21 = std::mem::replace(&mut self.m22, m22);
+        self.det = -self.det;
+        q
+    }
+
+    fn mul(&self, other: &GcdMatrix) -> GcdMatrix {
+        GcdMatrix {
+            m11: &self.m11 * &other.m11 + &self.m12 * &other.m21,
+            m12: &self.m11 * &other.m12 + &self.m12 * &other.m22,
+            m21: &self.m21 * &other.m11 + &self.m22 * &other.m21,
+            m22: &self.m21 * &other.m12 + &self.m22 * &other.m22,
+            det: self.det * other.det,
+        }
+    }
+
+    // the inverse of a Lehmer matrix [[a, b], [c, d]], which maps (u, v) to the new pair
+    fn from_lehmer(a: i128, b: i128, c: i128, d: i128) -> GcdMatrix {
+        let det = (a * d - b * c) as i64;
+        GcdMatrix {
+            m11: BigInt::from_i64(det * d as i64),
+            m12: BigInt::from_i64(-det * b as i64),
+            m21: BigInt::from_i64(-det * c as i64),
+            m22: BigInt::from_i64(det * a as i64),
+            det: det as isize,
+        }
+    }
+
+    // returns M^-1 (x, y)
+    fn apply_inverse(&self, x: &BigInt, y: &BigInt) -> (BigInt, BigInt) {
+        let d = BigInt::from_i64(self.det as i64);
+        (
+            (&self.m22 * x - &self.m12 * y) * &d,
+            (&self.m11 * y - &self.m21 * x) * &d,
+        )
+    }
+}
+
+// bits [shift, shift + 62) of |a|
+fn bits_at(a: &BigInt, shift: usize) -> u64 {
+    let i = shift / LIMB_BITS;
+    let o = shift % LIMB_BITS;
+    if i >= a.len {
+        return 0;
+    }
+    let mut res = (a.mag[i] as u64) >> o;
+    if o != 0 && i + 1 < a.len {
+        res |= (a.mag[i + 1] as u64) << (LIMB_BITS - o);
+    }
+    res & LIMB_MASK
+}
+
+pub(crate) fn word_gcd(mut u: u64, mut v: u64) -> u64 {
+    if u == 0 {
+        return v;
+    }
+    if v == 0 {
+        return u;
+    }
+    let shift = (u | v).trailing_zeros();
+    u >>= u.trailing_zeros();
+    while v != 0 {
+        v >>= v.trailing_zeros();
+        if u > v {
+            std::mem::swap(&mut u, &mut v);
+        }
+        v -= u;
+    }
+    u << shift
+}
+
+// Runs Lehmer's inner loop (Knuth's Algorithm L) on the leading 62 bits of u >= v
+// and returns the cofactor matrix [[A, B], [C, D]]. A result with B == 0 means the
+// leading bits did not determine a single quotient and a full division step is needed.
+fn lehmer_matrix(u: &BigInt, v: &BigInt) -> (i128, i128, i128, i128) {
+    let shift = u.bits().saturating_sub(LIMB_BITS);
+    let mut uh = bits_at(u, shift) as i128;
+    let mut vh = bits_at(v, shift) as i128;
+    let (mut a, mut b, mut c, mut d): (i128, i128, i128, i128) = (1, 0, 0, 1);
+
+    while vh + c != 0 && vh + d != 0 {
+        let q = (uh + a) / (vh + c);
+        if q != (uh + b) / (vh + d) {
+            break;
+        }
+        (a, c) = (c, a - q * c);
+        (b, d) = (d, b - q * d);
+        (uh, vh) = (vh, uh - q * vh);
+    }
+    (a, b, c, d)
+}
+
+fn lin_comb(x: i128, u: &BigInt, y: i128, v: &BigInt) -> BigInt {
+    BigInt::from_i64(x as i64) * u + BigInt::from_i64(y as i64) * v
+}
+
+// Moves (alpha, beta) = M^-1 (a, b) back along the quotient sequence until it is a valid
+// remainder pair (alpha > beta >= 0) with alpha >= 2^m. A recursive call on truncated
+// operands may overshoot the true quotient sequence by a few steps, which this undoes.
+fn hgcd_backtrack(mat: &mut GcdMatrix, alpha: &mut BigInt, beta: &mut BigInt, m: usize) {
+    while !mat.is_identity() && (beta.sgn == -1 || *alpha <= *beta || alpha.bits() <= m) {
+        let q = mat.pop_quotient();
+        let prev = &q * &*alpha + &*beta;
+        *beta = std::mem::replace(alpha, prev);
+    }
+}
+
+// Lehmer steps on (alpha, beta) accumulated into mat until beta < 2^m; it may overshoot,
+// so callers backtrack afterwards
+fn hgcd_lehmer(mat: &mut GcdMatrix, alpha: &mut BigInt, beta: &mut BigInt, m: usize) {
+    while beta.bits() > m + LIMB_BITS && *alpha > *beta {
+        let (a, b, c, d) = lehmer_matrix(alpha, beta);
+        if b == 0 {
+            break;
+        }
+        (*alpha, *beta) = (lin_comb(a, alpha, b, beta), lin_comb(c, alpha, d, beta));
+        *mat = mat.mul(&GcdMatrix::from_lehmer(a, b, c, d));
+    }
+}
+
+// backtracks if needed and then takes Euclidean steps until beta < 2^m
+fn hgcd_fixup(mat: &mut GcdMatrix, alpha: &mut BigInt, beta: &mut BigInt, m: usize) {
+    hgcd_backtrack(mat, alpha, beta, m);
+    while beta.bits() > m {
+        let (q, r) = divmod(alpha, beta);
+        mat.push_quotient(&q);
+        *alpha = std::mem::replace(beta, r);
+    }
+}
+
+fn hgcd(a: &BigInt, b: &BigInt) -> (GcdMatrix, BigInt, BigInt) {
+    let m = a.bits().div_ceil(2);
+    let mut mat = GcdMatrix::identity();
+    if b.bits() <= m {
+        return (mat, a.clone(), b.clone());
+    }
+    if a.len < HGCD_BASECASE_CUTOFF {
+        let (mut alpha, mut beta) = (a.clone(), b.clone());
+        hgcd_lehmer(&mut mat, &mut alpha, &mut beta, m);
+        hgcd_fixup(&mut mat, &mut alpha, &mut beta, m);
+        return (mat, alpha, beta);
+    }
+
+    // reduce the top half, which determines the first half of the quotient sequence
+    let shift = BigInt::from_u64(m as u64);
+    mat = hgcd(&(a >> &shift), &(b >> &shift)).0;
+    let (mut alpha, mut beta) = mat.apply_inverse(a, b);
+    hgcd_backtrack(&mut mat, &mut alpha, &mut beta, m);
+    if beta.bits() <= m {
+        return (mat, alpha, beta);
+    }
+
+    let (q, r) = divmod(&alpha, &beta);
+    mat.push_quotient(&q);
+    alpha = std::mem::replace(&mut beta, r);
+    if beta.bits() <= m {
+        return (mat, alpha, beta);
+    }
+
+    // alpha now has about 3n/4 bits, so its top 2(l - m) bits give the remaining quotients
+    let l = alpha.bits();
+    if l < 2 * m {
+        let shift = BigInt::from_u64((2 * m - l) as u64);
+        let sub = hgcd(&(&alpha >> &shift), &(&beta >> &shift)).0;
+        (alpha, beta) = sub.apply_inverse(&alpha, &beta);
+        mat = mat.mul(&sub);
+    }
+    hgcd_fixup(&mut mat, &mut alpha, &mut beta, m);
+    (mat, alpha, beta)
+}
+
+/// Half-gcd of a and b (Thull-Yap variant of Schonhage's algorithm).
+///
+/// Returns (M, alpha, beta) where (|a|, |b|) = M (alpha, beta) and alpha, beta are the
+/// consecutive remainders of the Euclidean sequence with alpha >= 2^m > beta for
+/// m = ceil(bits(max(|a|, |b|)) / 2). For |a| < |b| the first quotient of M is 0.
+pub fn half_gcd(a: &BigInt, b: &BigInt) -> (GcdMatrix, BigInt, BigInt) {
+    let mut u = abs(a);
+    let mut v = abs(b);
+    let mut mat = GcdMatrix::identity();
+    if u < v {
+        mat.push_quotient(&BigInt::from_u64(0));
+        std::mem::swap(&mut u, &mut v);
+    }
+    if u == v {
+        return (mat, u, v);
+    }
+    let (sub, alpha, beta) = hgcd(&u, &v);
+    (mat.mul(&sub), alpha, beta)
+}
+
+/// Returns gcd(a, b) together with the cofactor matrix M of the whole Euclidean
+/// sequence, so (|a|, |b|) = M (g, 0).
+pub fn gcd_with_matrix(a: &BigInt, b: &BigInt) -> (BigInt, GcdMatrix) {
+    let mut u = abs(a);
+    let mut v = abs(b);
+    let mut mat = GcdMatrix::identity();
+    if u < v {
+        mat.push_quotient(&BigInt::from_u64(0));
+        std::mem::swap(&mut u, &mut v);
+    }
+
+    while v.sgn != 0 {
+        if v.len >= HGCD_CUTOFF && u != v {
+            let (sub, alpha, beta) = hgcd(&u, &v);
+            if !sub.is_identity() {
+                (u, v) = (alpha, beta);
+                mat = mat.mul(&sub);
+                continue;
+            }
+        }
+        let (a, b, c, d) = lehmer_matrix(&u, &v);
+        if b == 0 || u.len <= 1 {
+            let (q, r) = divmod(&u, &v);
+            mat.push_quotient(&q);
+            u = std::mem::replace(&mut v, r);
+        } else {
+            (u, v) = (lin_comb(a, &u, b, &v), lin_comb(c, &u, d, &v));
+            mat = mat.mul(&GcdMatrix::from_lehmer(a, b, c, d));
+        }
+    }
+    (u, mat)
+}
+
+/// Returns the greatest common divisor of a and b, which is always non-negative.
+///
+/// This uses Lehmer's algorithm: the quotients are guessed from the leading limbs so most
+/// steps only need a linear combination of the operands instead of a full division.
+/// Operands above `HGCD_CUTOFF` limbs are first reduced with the subquadratic half-gcd.
+pub fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    gcd_with_cutoff(a, b, HGCD_CUTOFF)
+}
+
+// gcd with the half-gcd used from the given number of limbs, usize::MAX for Lehmer alone
+fn gcd_with_cutoff(a: &BigInt, b: &BigInt, cutoff: usize) -> BigInt {
+    let mut u = abs(a);
+    let mut v = abs(b);
+    if u < v {
+        std::mem::swap(&mut u, &mut v);
+    }
+
+    while v.sgn != 0 {
+        if u.len <= 1 {
+            return BigInt::from_u64(word_gcd(u.mag[0] as u64, v.mag[0] as u64));
+        }
+        if v.len >= cutoff && u != v {
+            let (mat, alpha, beta) = hgcd(&u, &v);
+            if !mat.is_identity() {
+                (u, v) = (alpha, beta);
+                continue;
+            }
+        }
+
+        let (a, b, c, d) = lehmer_matrix(&u, &v);
+        if b == 0 {
+            let r = divmod(&u, &v).1;
+            u = v;
+            v = r;
+        } else {
+            (u, v) = (lin_comb(a, &u, b, &v), lin_comb(c, &u, d, &v));
+        }
+    }
+    u
+}
+
+/// Returns (g, x, y) with a * x + b * y = g where g = gcd(a, b) >= 0.
+///
+/// The cofactors are chosen as in the symmetric extended Euclidean algorithm:
+/// if b != 0 then |x| <= |b| / (2g) and y is the matching cofactor, if b == 0 then
+/// x is the sign of a and y = 0, and extended_gcd(0, 0) is (0, 0, 0).
+pub fn extended_gcd(a: &BigInt, b: &BigInt) -> (BigInt, BigInt, BigInt) {
+    let zero = BigInt::from_u64(0);
+    let a_abs = abs(a);
+    let b_abs = abs(b);
+    if b.sgn == 0 {
+        return (a_abs, BigInt::from_i64(a.sgn as i64), zero);
+    }
+
+    // u = su * |a| + tu * |b| and v = sv * |a| + tv * |b| throughout
+    let mut u = a_abs;
+    let mut v = b_abs;
+    let (mut su, mut tu) = (BigInt::from_u64(1), zero.clone());
+    let (mut sv, mut tv) = (zero.clone(), BigInt::from_u64(1));
+    if u < v {
+        std::mem::swap(&mut u, &mut v);
+        std::mem::swap(&mut su, &mut sv);
+        std::mem::swap(&mut tu, &mut tv);
+    }
+
+    while v.sgn != 0 {
+        if v.len >= HGCD_CUTOFF && u != v {
+            let (mat, alpha, beta) = hgcd(&u, &v);
+            if !mat.is_identity() {
+                (u, v) = (alpha, beta);
+                (su, sv) = mat.apply_inverse(&su, &sv);
+                (tu, tv) = mat.apply_inverse(&tu, &tv);
+                continue;
+            }
+        }
+        let (a, b, c, d) = lehmer_matrix(&u, &v);
+        if b == 0 {
+            let (q, r) = divmod(&u, &v);
+            let s = &su - &q * &sv;
+            let t = &tu - q * &tv;
+            (u, v) = (v, r);
+            (su, sv) = (sv, s);
+            (tu, tv) = (tv, t);
+        } else {
+            (u, v) = (lin_comb(a, &u, b, &v), lin_comb(c, &u, d, &v));
+            (su, sv) = (lin_comb(a, &su, b, &sv), lin_comb(c, &su, d, &sv));
+            (tu, tv) = (lin_comb(a, &tu, b, &tv), lin_comb(c, &tu, d, &tv));
+        }
+    }
+
+    // the cofactors of the final zero are +-(|b|, -|a|) / g, which move x into the
+    // symmetric range around zero without dividing
+    if sv.sgn == -1 {
+        (sv, tv) = (-sv, -tv);
+    }
+    if &su + &su > sv {
+        (su, tu) = (su - &sv, tu - &tv);
+    } else if -(&su + &su) >= sv {
+        (su, tu) = (su + &sv, tu + &tv);
+    }
+
+    (u, su * BigInt::from_i64(a.sgn as i64), tu * BigInt::from_i64(b.sgn as i64))
+}
+
+/// Returns the least common multiple of a and b, which is always non-negative.
+pub fn lcm(a: &BigInt, b: &BigInt) -> BigInt {
+    if a.sgn == 0 || b.sgn == 0 {
+        return BigInt::from_u64(0);
+    }
+    abs(a) / gcd(a, b) * abs(b)
+}
+
+/// Returns x in [0, m) with a * x = 1 (mod m), or None when gcd(a, m) != 1.
+pub fn mod_inverse(a: &BigInt, m: &BigInt) -> Option<BigInt> {
+    if m.sgn != 1 {
+        panic!("Modular arithmetic must be done with positive integers");
+    }
+    let (g, x, _) = extended_gcd(&(a % m), m);
+    if g != BigInt::from_u64(1) {
+        return None;
+    }
+    Some(x % m)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mparith::{build_bigint, Abs, BigInt};
+    use std::fs::File;
+    use std::io::{self, BufRead};
+    use std::path::Path;
+    const A_DEC: usize = 1;
+    const B_DEC: usize = 3;
+
+    fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(filename)?;
+        Ok(io::BufReader::new(file).lines())
+    }
+
+    fn euclid(a: &BigInt, b: &BigInt) -> BigInt {
+        let mut u = a.abs();
+        let mut v = b.abs();
+        while v.sgn != 0 {
+            let r = &u % &v;
+            u = v;
+            v = r;
+        }
+        u
+    }
+
+    #[test]
+    fn gcd_small_test() {
+        assert_eq!(build_bigint("0"), super::gcd(&build_bigint("0"), &build_bigint("0")));
+        assert_eq!(build_bigint("5"), super::gcd(&build_bigint("0"), &build_bigint("-5")));
+        assert_eq!(build_bigint("6"), super::gcd(&build_bigint("-12"), &build_bigint("18")));
+        assert_eq!(build_bigint("1"), super::gcd(&build_bigint("17"), &build_bigint("31")));
+    }
+
+    #[test]
+    fn gcd_test() {
+        let c = build_bigint("1208925819614629174706189");
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                let b = build_bigint(v[B_DEC]);
+                let g = euclid(&a, &b);
+                assert_eq!(g, super::gcd(&a, &b));
+                assert_eq!(&g * &c, super::gcd(&(&a * &c), &(&b * &c)));
+            }
+        }
+    }
+
+    #[test]
+    fn extended_gcd_test() {
+        let (g, x, y) = super::extended_gcd(&build_bigint("240"), &build_bigint("46"));
+        assert_eq!(
+            (build_bigint("2"), build_bigint("-9"), build_bigint("47")),
+            (g, x, y)
+        );
+        let (g, x, y) = super::extended_gcd(&build_bigint("-7"), &build_bigint("0"));
+        assert_eq!((build_bigint("7"), build_bigint("-1"), build_bigint("0")), (g, x, y));
+        let (g, x, y) = super::extended_gcd(&build_bigint("0"), &build_bigint("0"));
+        assert_eq!((build_bigint("0"), build_bigint("0"), build_bigint("0")), (g, x, y));
+
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                let b = build_bigint(v[B_DEC]);
+                let (g, x, y) = super::extended_gcd(&a, &b);
+                assert_eq!(super::gcd(&a, &b), g);
+                assert_eq!(g, &a * &x + &b * &y);
+                if b.sgn != 0 {
+                    assert!((&x + &x).abs() <= b.abs() / &g);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lcm_test() {
+        assert_eq!(build_bigint("0"), super::lcm(&build_bigint("0"), &build_bigint("3")));
+        assert_eq!(build_bigint("36"), super::lcm(&build_bigint("-12"), &build_bigint("18")));
+
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                let b = build_bigint(v[B_DEC]);
+                assert_eq!((&a * &b).abs(), super::lcm(&a, &b) * super::gcd(&a, &b));
+            }
+        }
+    }
+
+    // deterministic pseudo-random number with the given number of limbs
+    fn random_bigint(limbs: usize, seed: u64) -> BigInt {
+        let mut x = seed;
+        let mut res = build_bigint("0");
+        let base = build_bigint("4611686018427387904");
+        for _ in 0..limbs {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            res = res * &base + BigInt::from_u64(x >> 2);
+        }
+        res
+    }
+
+    #[test]
+    fn half_gcd_test() {
+        for (limbs, seed) in [(10, 1), (250, 2), (450, 3)] {
+            let c = random_bigint(limbs / 3, seed + 10);
+            let a = random_bigint(limbs, seed) * &c;
+            let b = random_bigint(limbs - 1, seed + 20) * &c;
+
+            let (mat, alpha, beta) = super::half_gcd(&a, &b);
+            assert_eq!(1, mat.det().abs());
+            assert_eq!(a, &mat.m11 * &alpha + &mat.m12 * &beta);
+            assert_eq!(b, &mat.m21 * &alpha + &mat.m22 * &beta);
+            let m = a.bits().div_ceil(2);
+            assert!(alpha.bits() > m && beta.bits() <= m && alpha > beta);
+
+            let g = super::gcd(&a, &b);
+            assert_eq!(g, &g / &c * &c);
+            let (g2, mat) = super::gcd_with_matrix(&a, &b);
+            assert_eq!(g, g2);
+            assert_eq!(a, &mat.m11 * &g);
+            assert_eq!(b, &mat.m21 * &g);
+            let (g3, x, y) = super::extended_gcd(&b, &a);
+            assert_eq!(g, g3);
+            assert_eq!(g, &b * &x + &a * &y);
+        }
+    }
+
+    #[test]
+    fn half_gcd_above_cutoff_test() {
+        // large enough that gcd, extended_gcd and gcd_with_matrix all start with half-gcd
+        // steps, checked against Lehmer's algorithm alone
+        let limbs = super::HGCD_CUTOFF + 10;
+        let c = random_bigint(limbs / 4, 31);
+        let a = random_bigint(limbs, 32) * &c;
+        let b = random_bigint(limbs - 3, 33) * &c;
+        let g = super::gcd_with_cutoff(&a, &b, usize::MAX);
+        assert!(g.bits() >= c.bits());
+
+        assert_eq!(g, super::gcd(&a, &b));
+        let (g2, mat) = super::gcd_with_matrix(&a, &b);
+        assert_eq!(g, g2);
+        assert_eq!(1, mat.det().abs());
+        assert_eq!(a, &mat.m11 * &g);
+        assert_eq!(b, &mat.m21 * &g);
+        let (g3, x, y) = super::extended_gcd(&a, &b);
+        assert_eq!(g, g3);
+        assert_eq!(g, &a * &x + &b * &y);
+        assert!((&x + &x).abs() * &g <= b);
+    }
+
+    // times gcd against Lehmer's algorithm alone on random operands of growing size, which
+    // is how HGCD_CUTOFF and HGCD_BASECASE_CUTOFF were chosen (run with --release)
+    #[test]
+    #[ignore]
+    fn half_gcd_speed_test() {
+        for limbs in [250, 500, 1000, 1500, 2000, 3000, 4000, 6000] {
+            let a = random_bigint(limbs, 41);
+            let b = random_bigint(limbs, 42);
+            let start = std::time::Instant::now();
+            let g = super::gcd_with_cutoff(&a, &b, usize::MAX);
+            let lehmer = start.elapsed();
+            let start = std::time::Instant::now();
+            let g2 = super::gcd_with_cutoff(&a, &b, 0);
+            let half = start.elapsed();
+            assert_eq!(g, g2);
+            println!("{limbs} limbs: Lehmer {lehmer:?}, half-gcd {half:?}");
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn mod_inverse_byneg_test() {
+        let _ = super::mod_inverse(&build_bigint("3"), &build_bigint("-7"));
+    }
+
+    #[test]
+    fn mod_inverse_test() {
+        assert_eq!(
+            Some(build_bigint("0")),
+            super::mod_inverse(&build_bigint("5"), &build_bigint("1"))
+        );
+        assert_eq!(
+            Some(build_bigint("4")),
+            super::mod_inverse(&build_bigint("-5"), &build_bigint("7"))
+        );
+        assert_eq!(None, super::mod_inverse(&build_bigint("6"), &build_bigint("9")));
+
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                let m = build_bigint(v[B_DEC]).abs() + build_bigint("1");
+                match super::mod_inverse(&a, &m) {
+                    Some(x) => assert_eq!(build_bigint("1") % &m, (&a * x) % &m),
+                    None => assert!(super::gcd(&a, &m) != build_bigint("1")),
+                }
+            }
+        }
+    }
+}
@@ -1 +1,23 @@
+pub mod arith;
+pub mod bigdecimal;
+pub mod bigfloat;
+pub mod certificate;
+pub mod compact;
+pub mod contfrac;
+pub mod crt;
+pub mod dlog;
+pub mod ecm;
+pub mod factor;
+pub mod forms;
+pub mod gcd;
+pub mod modular;
 pub mod mparith;
+pub mod pell;
+pub mod primality;
+pub mod primes;
+pub mod quadint;
+pub mod rational;
+pub mod residues;
+pub mod roots;
+pub mod rounding;
+pub mod siqs;
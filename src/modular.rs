@@ -0,0 +1,406 @@
+use crate::mparith::BigInt;
+use std::cmp::Ordering;
+
+// number of bits stored in each limb of a BigInt
+const LIMB_BITS: u32 = isize::BITS - 2;
+const LIMB_MASK: u64 = (1 << LIMB_BITS) - 1;
+
+/// Shared interface of the precomputed reduction contexts.
+///
+/// Values passed to `mul_mod`, `sqr_mod`, `add_mod`, `sub_mod` and `pow_mod` (the base)
+/// must already be in the context's domain, i.e. come from `to_domain` or another
+/// domain operation. `out_of_domain` converts a result back into an ordinary residue.
+pub trait ModContext {
+    fn modulus(&self) -> &BigInt;
+
+    fn to_domain(&self, a: &BigInt) -> BigInt;
+
+    fn out_of_domain(&self, a: &BigInt) -> BigInt;
+
+    fn mul_mod(&self, a: &BigInt, b: &BigInt) -> BigInt;
+
+    fn sqr_mod(&self, a: &BigInt) -> BigInt {
+        self.mul_mod(a, a)
+    }
+
+    // the representation of 1 in the domain
+    fn one(&self) -> BigInt {
+        self.to_domain(&BigInt::from_u64(1))
+    }
+
+    fn add_mod(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        let c = a + b;
+        if c >= *self.modulus() {
+            return c - self.modulus();
+        }
+        c
+    }
+
+    fn sub_mod(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        let c = a - b;
+        if c.sgn == -1 {
+            return c + self.modulus();
+        }
+        c
+    }
+
+    fn pow_mod(&self, a: &BigInt, e: &BigInt) -> BigInt {
+        if e.sgn == -1 {
+            panic!("Negative exponents need a modular inverse, invert the base first")
+        }
+        let mut res = self.one();
+        for i in (0..e.bits()).rev() {
+            res = self.sqr_mod(&res);
+            if (e.mag[i / LIMB_BITS as usize] >> (i % LIMB_BITS as usize)) & 1 == 1 {
+                res = self.mul_mod(&res, a);
+            }
+        }
+        res
+    }
+}
+
+/// Montgomery reduction modulo an odd number n.
+///
+/// Residues are kept as aR mod n where R = 2^(62k) and k is the number of limbs of n,
+/// so a product only needs a word-by-word REDC instead of a full division.
+#[derive(Debug, Clone)]
+pub struct MontgomeryCtx {
+    n: BigInt,
+    k: usize,
+    n_inv: u64, // -n^-1 mod 2^62
+    r2: BigInt, // R^2 mod n
+}
+
+impl MontgomeryCtx {
+    pub fn new(n: &BigInt) -> MontgomeryCtx {
+        if n.sgn != 1 || !n.is_odd() {
+            panic!("Montgomery reduction requires a positive odd modulus");
+        }
+
+        // Newton iteration for n^-1 mod 2^64, each step doubles the number of correct bits
+        let n0 = n.mag[0] as u64;
+        let mut x: u64 = n0;
+        for _ in 0..5 {
+            x = x.wrapping_mul(2_u64.wrapping_sub(n0.wrapping_mul(x)));
+        }
+
+        let r2 = (BigInt::from_u64(1) << BigInt::from_u64(2 * LIMB_BITS as u64 * n.len as u64)) % n;
+        MontgomeryCtx {
+            n: n.clone(),
+            k: n.len,
+            n_inv: x.wrapping_neg() & LIMB_MASK,
+            r2,
+        }
+    }
+
+    // computes t / R mod n for 0 <= t < nR
+    fn redc(&self, t: &BigInt) -> BigInt {
+        let k = self.k;
+        let mut tt: Vec<u64> = vec![0; 2 * k + 2];
+        for (x, &d) in tt.iter_mut().zip(t.mag[0..t.len].iter()) {
+            *x = d as u64;
+        }
+
+        for i in 0..k {
+            let m = tt[i].wrapping_mul(self.n_inv) & LIMB_MASK;
+            let mut carry: u128 = 0;
+            for j in 0..k {
+                let s = tt[i + j] as u128 + (m as u128) * (self.n.mag[j] as u128) + carry;
+                tt[i + j] = (s as u64) & LIMB_MASK;
+                carry = s >> LIMB_BITS;
+            }
+            let mut idx = i + k;
+            while carry != 0 {
+                let s = tt[idx] as u128 + carry;
+                tt[idx] = (s as u64) & LIMB_MASK;
+                carry = s >> LIMB_BITS;
+                idx += 1;
+            }
+        }
+
+        let mut res = BigInt {
+            mag: tt[k..(2 * k + 1)].iter().map(|&x| x as isize).collect(),
+            sgn: 1,
+            len: k + 1,
+        };
+        res.rm_leading_zeros();
+        if res >= self.n {
+            res = res - &self.n;
+        }
+        res
+    }
+}
+
+impl ModContext for MontgomeryCtx {
+    fn modulus(&self) -> &BigInt {
+        &self.n
+    }
+
+    fn to_domain(&self, a: &BigInt) -> BigInt {
+        self.redc(&((a % &self.n) * &self.r2))
+    }
+
+    fn out_of_domain(&self, a: &BigInt) -> BigInt {
+        self.redc(a)
+    }
+
+    fn mul_mod(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        self.redc(&(a * b))
+    }
+}
+
+/// Barrett reduction modulo any positive number m.
+///
+/// The domain is the ordinary residues in [0, m); `mu` = floor(B^(2k) / m) turns the
+/// division in each reduction into two multiplications.
+#[derive(Debug, Clone)]
+pub struct BarrettCtx {
+    m: BigInt,
+    k: usize,
+    mu: BigInt,
+}
+
+impl BarrettCtx {
+    pub fn new(m: &BigInt) -> BarrettCtx {
+        if m.sgn != 1 {
+            panic!("Modular arithmetic must be done with positive integers");
+        }
+        let mu = (BigInt::from_u64(1) << BigInt::from_u64(2 * LIMB_BITS as u64 * m.len as u64)) / m;
+        BarrettCtx {
+            m: m.clone(),
+            k: m.len,
+            mu,
+        }
+    }
+
+    // reduces 0 <= x < B^(2k) modulo m
+    pub fn reduce(&self, x: &BigInt) -> BigInt {
+        if x.len > 2 * self.k {
+            return x % &self.m;
+        }
+        let q = (x.shr_limbs(self.k - 1) * &self.mu).shr_limbs(self.k + 1);
+        let mut r = x - q * &self.m;
+        while r.cmp(&self.m) != Ordering::Less {
+            r = r - &self.m;
+        }
+        r
+    }
+}
+
+impl ModContext for BarrettCtx {
+    fn modulus(&self) -> &BigInt {
+        &self.m
+    }
+
+    fn to_domain(&self, a: &BigInt) -> BigInt {
+        if a.sgn == -1 {
+            return a % &self.m;
+        }
+        self.reduce(a)
+    }
+
+    fn out_of_domain(&self, a: &BigInt) -> BigInt {
+        a.clone()
+    }
+
+    fn mul_mod(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        self.reduce(&(a * b))
+    }
+}
+
+/// Computes a^e mod m, using Montgomery reduction for odd m and Barrett reduction otherwise.
+pub fn pow_mod(a: &BigInt, e: &BigInt, m: &BigInt) -> BigInt {
+    if m.sgn != 1 {
+        panic!("Modular arithmetic must be done with positive integers");
+    }
+    if m.is_odd() {
+        let ctx = MontgomeryCtx::new(m);
+        ctx.out_of_domain(&ctx.pow_mod(&ctx.to_domain(a), e))
+    } else {
+        let ctx = BarrettCtx::new(m);
+        ctx.pow_mod(&ctx.to_domain(a), e)
+    }
+}
+
+// word-size helpers for the sieves and multi-modular code, with products kept in u128
+
+/// b^e mod m for m < 2^64.
+pub(crate) fn pow_mod_u64(b: u64, mut e: u64, m: u64) -> u64 {
+    let mut b = (b % m) as u128;
+    let mut res: u128 = 1;
+    while e > 0 {
+        if e & 1 == 1 {
+            res = res * b % m as u128;
+        }
+        b = b * b % m as u128;
+        e >>= 1;
+    }
+    res as u64
+}
+
+/// a^-1 mod m for gcd(a, m) = 1.
+pub(crate) fn inv_mod_u64(a: u64, m: u64) -> u64 {
+    let (mut r0, mut r1) = (m as i128, (a % m) as i128);
+    let (mut t0, mut t1) = (0_i128, 1_i128);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (t0, t1) = (t1, t0 - q * t1);
+    }
+    t0.rem_euclid(m as i128) as u64
+}
+
+/// A square root of the quadratic residue a modulo an odd prime p, by Tonelli-Shanks.
+pub(crate) fn sqrt_mod_u64(a: u64, p: u64) -> u64 {
+    let a = a % p;
+    if a == 0 {
+        return 0;
+    }
+    let s = (p - 1).trailing_zeros();
+    let q = (p - 1) >> s;
+    let mut z = 2;
+    while pow_mod_u64(z, (p - 1) / 2, p) != p - 1 {
+        z += 1;
+    }
+    let mut m = s;
+    let mut c = pow_mod_u64(z, q, p) as u128;
+    let mut t = pow_mod_u64(a, q, p) as u128;
+    let mut r = pow_mod_u64(a, q.div_ceil(2), p) as u128;
+    let p = p as u128;
+    while t != 1 {
+        let mut i = 0;
+        let mut tt = t;
+        while tt != 1 {
+            tt = tt * tt % p;
+            i += 1;
+        }
+        let mut b = c;
+        for _ in 0..(m - i - 1) {
+            b = b * b % p;
+        }
+        m = i;
+        c = b * b % p;
+        t = t * c % p;
+        r = r * b % p;
+    }
+    r as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BarrettCtx, ModContext, MontgomeryCtx};
+    use crate::mparith::{build_bigint, Abs, Pow};
+    use std::fs::File;
+    use std::io::{self, BufRead};
+    use std::path::Path;
+    const A_DEC: usize = 1;
+    const B_DEC: usize = 3;
+    const SHT_AMT_DEC: usize = 18;
+
+    fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(filename)?;
+        Ok(io::BufReader::new(file).lines())
+    }
+
+    #[test]
+    #[should_panic]
+    fn montgomery_even_modulus_test() {
+        let _ = MontgomeryCtx::new(&build_bigint("10"));
+    }
+
+    #[test]
+    fn montgomery_mul_test() {
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                let mut m = build_bigint(v[B_DEC]).abs();
+                if !m.is_odd() {
+                    m = m + build_bigint("1");
+                }
+                let ctx = MontgomeryCtx::new(&m);
+                let am = ctx.to_domain(&a);
+                assert_eq!((&a * &a) % &m, ctx.out_of_domain(&ctx.sqr_mod(&am)));
+                assert_eq!(
+                    (&a * &m + build_bigint("7")) % &m,
+                    ctx.out_of_domain(&ctx.to_domain(&(&a * &m + build_bigint("7"))))
+                );
+                let b = &a + build_bigint("12345678901234567890");
+                assert_eq!(
+                    (&a * &b) % &m,
+                    ctx.out_of_domain(&ctx.mul_mod(&am, &ctx.to_domain(&b)))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn barrett_mul_test() {
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                let m = build_bigint(v[B_DEC]).abs() + build_bigint("1");
+                let ctx = BarrettCtx::new(&m);
+                let b = &a - build_bigint("98765432109876543210");
+                assert_eq!(
+                    (&a * &b) % &m,
+                    ctx.out_of_domain(&ctx.mul_mod(&ctx.to_domain(&a), &ctx.to_domain(&b)))
+                );
+                assert_eq!((&a * &a) % &m, ctx.sqr_mod(&ctx.to_domain(&a)));
+            }
+        }
+    }
+
+    #[test]
+    fn pow_mod_test() {
+        assert_eq!(
+            build_bigint("0"),
+            super::pow_mod(&build_bigint("12"), &build_bigint("5"), &build_bigint("1"))
+        );
+        assert_eq!(
+            build_bigint("445"),
+            super::pow_mod(&build_bigint("4"), &build_bigint("13"), &build_bigint("497"))
+        );
+        assert_eq!(
+            build_bigint("1"),
+            super::pow_mod(&build_bigint("-3"), &build_bigint("0"), &build_bigint("10"))
+        );
+
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.take(100).map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                let m = build_bigint(v[B_DEC]).abs() + build_bigint("2");
+                let e = build_bigint(v[SHT_AMT_DEC]);
+                let mut expected = build_bigint("1") % &m;
+                let mut i = build_bigint("0");
+                while i < e {
+                    expected = (expected * &a) % &m;
+                    i = i + build_bigint("1");
+                }
+                assert_eq!(expected, super::pow_mod(&a, &e, &m));
+            }
+            assert_eq!(
+                build_bigint("3").pow(build_bigint("200")) % build_bigint("1000000007"),
+                super::pow_mod(&build_bigint("3"), &build_bigint("200"), &build_bigint("1000000007"))
+            );
+        }
+    }
+
+    #[test]
+    fn sqrt_mod_u64_test() {
+        for p in [3_u64, 5, 13, 17, 97, 257, 65537, 1000003, 998244353] {
+            for a in 1..200 {
+                if super::pow_mod_u64(a, (p - 1) / 2, p) == 1 {
+                    let r = super::sqrt_mod_u64(a, p);
+                    assert_eq!(a % p, (r as u128 * r as u128 % p as u128) as u64);
+                }
+            }
+        }
+        assert_eq!(3, super::inv_mod_u64(5, 7));
+    }
+}
@@ -1,17 +1,19 @@
 use std::cmp::Ordering;
 use std::cmp::{max, min};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::ops;
 
 const B: isize = 1 << (isize::BITS - 2);
 const KARATSUBA_CUTOFF: usize = 80;
+const KARATSUBA_SQRT_CUTOFF: usize = 8;
 //const TOOM_COOK_CUTOFF: usize = usize::MAX;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BigInt {
-    mag: Vec<isize>, // least-significant digit stored in index 0
-    sgn: isize,
-    len: usize, // https://doc.rust-lang.org/stable/reference/types/numeric.html#machine-dependent-integer-types
+    pub(crate) mag: Vec<isize>, // least-significant digit stored in index 0
+    pub(crate) sgn: isize,
+    pub(crate) len: usize, // https://doc.rust-lang.org/stable/reference/types/numeric.html#machine-dependent-integer-types
 }
 
 pub fn build_bigint(val: &str) -> BigInt {
@@ -211,6 +213,13 @@ pub fn pow(a: &BigInt, b: &BigInt) -> BigInt {
 }
 
 pub fn isqrt(n: &BigInt) -> BigInt {
+    if n.sgn == 1 && n.len >= KARATSUBA_SQRT_CUTOFF {
+        return karatsuba_sqrt(n).0;
+    }
+    isqrt_newton(n)
+}
+
+fn isqrt_newton(n: &BigInt) -> BigInt {
     // This is based on the Python implementation of isqrt
     // The runtime of this algorithm is floor(log(log(a))),
     // and a proof of correctness is provided by Python here:
@@ -293,6 +302,51 @@ pub fn isqrt(n: &BigInt) -> BigInt {
     return a;
 }
 
+pub(crate) fn karatsuba_sqrt(n: &BigInt) -> (BigInt, BigInt) {
+    // This is Zimmermann's Karatsuba square root (Algorithm 1.12 in Brent and Zimmermann's
+    // "Modern Computer Arithmetic") with the base chosen as a power of two each level.
+    // Write n = a3 * b^3 + a2 * b^2 + a1 * b + a0 with b = 2^k and a3 >= b / 4, then
+    // the square root of the top half gives the top half of the square root and a single
+    // division by twice that root gives the bottom half.
+    if n.len < KARATSUBA_SQRT_CUTOFF {
+        let s = isqrt_newton(n);
+        let r = n - &s * &s;
+        return (s, r);
+    }
+
+    // shift by an even amount so the top bits satisfy a3 >= b / 4
+    let bits = n.bits();
+    let k = bits.div_ceil(4);
+    let c = (4 * k - bits) / 2;
+    let c_bigint = BigInt::from_u64(2 * c as u64);
+    let nn = n << &c_bigint;
+
+    let k_bigint = BigInt::from_u64(k as u64);
+    let mask = (BigInt::from_u64(1) << &k_bigint) - BigInt::from_u64(1);
+    let a1 = (&nn >> &k_bigint) & &mask;
+    let a0 = &nn & &mask;
+
+    let (s1, r1) = karatsuba_sqrt(&(&nn >> BigInt::from_u64(2 * k as u64)));
+    let (q, u) = divmod(&((r1 << &k_bigint) + a1), &(&s1 + &s1));
+    let mut s = (s1 << &k_bigint) + &q;
+    let mut r = (u << &k_bigint) + a0 - &q * &q;
+    if r.sgn == -1 {
+        r = r + &s + &s - BigInt::from_u64(1);
+        s = s - BigInt::from_u64(1);
+    }
+
+    if c != 0 {
+        // s = 2^c s' + t with t < 2^c, so n - s'^2 = (r + t (2^(c+1) s' + t)) / 4^c and the
+        // remainder follows from r without squaring s' again
+        let c_bigint = BigInt::from_u64(c as u64);
+        let t = &s & &((BigInt::from_u64(1) << &c_bigint) - BigInt::from_u64(1));
+        s = s >> &c_bigint;
+        r = (r + &t * ((&s << BigInt::from_u64(c as u64 + 1)) + &t))
+            >> BigInt::from_u64(2 * c as u64);
+    }
+    (s, r)
+}
+
 pub fn abs(a: &BigInt) -> BigInt {
     return BigInt {
         mag: a.mag.clone(),
@@ -383,7 +437,7 @@ impl BigInt {
         self.len = end + 1;
     }
 
-    fn rm_leading_zeros(&mut self) {
+    pub(crate) fn rm_leading_zeros(&mut self) {
         let mut end = self.len;
         while end > 0 && self.mag[end - 1] == 0 {
             end -= 1;
@@ -393,6 +447,108 @@ impl BigInt {
             self.sgn = 0;
         }
     }
+
+    // small helpers shared by the number theory modules
+    pub(crate) fn from_u64(v: u64) -> BigInt {
+        let mut res = BigInt {
+            mag: vec![(v & (B as u64 - 1)) as isize, (v >> (isize::BITS - 2)) as isize],
+            sgn: 1,
+            len: 2,
+        };
+        res.rm_leading_zeros();
+        res
+    }
+
+    pub(crate) fn from_i64(v: i64) -> BigInt {
+        let mut res = BigInt::from_u64(v.unsigned_abs());
+        if v < 0 {
+            res.sgn = -res.sgn;
+        }
+        res
+    }
+
+    // number of bits in the magnitude
+    pub(crate) fn bits(&self) -> usize {
+        if self.len == 0 {
+            return 0;
+        }
+        (isize::BITS as usize - 2) * (self.len - 1) + self.mag[self.len - 1].ilog2() as usize + 1
+    }
+
+    pub(crate) fn is_odd(&self) -> bool {
+        self.len != 0 && (self.mag[0] & 1) == 1
+    }
+
+    // the magnitude as a u64 if it fits
+    pub(crate) fn to_u64(&self) -> Option<u64> {
+        match self.len {
+            0 => Some(0),
+            1 => Some(self.mag[0] as u64),
+            2 if self.mag[1] < 4 => Some(self.mag[0] as u64 | ((self.mag[1] as u64) << (isize::BITS - 2))),
+            _ => None,
+        }
+    }
+
+    // (floor(|self| / d), |self| mod d) for 0 < d < 2^62
+    pub(crate) fn divmod_small(&self, d: u64) -> (BigInt, u64) {
+        let mut q = vec![0; self.len];
+        let mut r: u128 = 0;
+        for i in (0..self.len).rev() {
+            let cur = (r << (isize::BITS - 2)) | self.mag[i] as u128;
+            q[i] = (cur / d as u128) as isize;
+            r = cur % d as u128;
+        }
+        let mut res = BigInt {
+            mag: q,
+            sgn: 1,
+            len: self.len,
+        };
+        res.rm_leading_zeros();
+        (res, r as u64)
+    }
+
+    // |self| mod d for 0 < d < 2^62
+    pub(crate) fn rem_small(&self, d: u64) -> u64 {
+        let mut r: u128 = 0;
+        for i in (0..self.len).rev() {
+            r = ((r << (isize::BITS - 2)) | self.mag[i] as u128) % d as u128;
+        }
+        r as u64
+    }
+
+    // number of trailing zero bits of the magnitude, 0 for zero
+    pub(crate) fn trailing_zeros(&self) -> usize {
+        for i in 0..self.len {
+            if self.mag[i] != 0 {
+                return (isize::BITS as usize - 2) * i + self.mag[i].trailing_zeros() as usize;
+            }
+        }
+        0
+    }
+
+    // natural logarithm of the magnitude from its two leading limbs, -inf for zero
+    pub(crate) fn ln_abs(&self) -> f64 {
+        match self.len {
+            0 => f64::NEG_INFINITY,
+            1 => (self.mag[0] as f64).ln(),
+            n => {
+                let top = self.mag[n - 1] as f64 * B as f64 + self.mag[n - 2] as f64;
+                top.ln() + ((isize::BITS as usize - 2) * (n - 2)) as f64 * std::f64::consts::LN_2
+            }
+        }
+    }
+
+    // floor(|self| / B^k)
+    pub(crate) fn shr_limbs(&self, k: usize) -> BigInt {
+        if k >= self.len {
+            return BigInt::from_u64(0);
+        }
+        BigInt {
+            mag: self.mag[k..self.len].to_vec(),
+            sgn: 1,
+            len: self.len - k,
+        }
+    }
 }
 
 fn addsub(a: &BigInt, b: &BigInt, sgn: isize) -> BigInt {
@@ -942,7 +1098,7 @@ pub fn toom_cook(a: &BigInt, b: &BigInt) -> BigInt {
     return w;
 }*/
 
-fn divmod(a: &BigInt, b: &BigInt) -> (BigInt, BigInt) {
+pub(crate) fn divmod(a: &BigInt, b: &BigInt) -> (BigInt, BigInt) {
     if b.sgn == 0 {
         panic!("Divide by zero error")
     }
@@ -2032,6 +2188,16 @@ impl PartialOrd for BigInt {
 
 impl Eq for BigInt {}
 
+impl Hash for BigInt {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // consistent with Eq: every zero hashes alike and unused limbs are ignored
+        self.sgn.hash(state);
+        if self.sgn != 0 {
+            self.mag[..self.len].hash(state);
+        }
+    }
+}
+
 impl PartialEq for BigInt {
     fn eq(&self, other: &Self) -> bool {
         self.cmp(other) == Ordering::Equal
@@ -2749,6 +2915,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn bigint_karatsuba_sqrt_test() {
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = super::build_bigint_bin(v[A_BIN]).abs();
+                let (s, r) = super::karatsuba_sqrt(&(&a << super::build_bigint("2600")));
+                assert_eq!(
+                    v[A_ABS_ISQRT_BIN],
+                    (&s >> super::build_bigint("1300")).to_string_bin()
+                );
+                assert_eq!(
+                    v[A_ABS_ISQRT_DEC],
+                    (&s >> super::build_bigint("1300")).to_string()
+                );
+                assert_eq!(&a << super::build_bigint("2600"), &s * &s + &r);
+                assert!(r <= &s + &s);
+            }
+        }
+    }
+
     #[test]
     #[ignore]
     fn bigint_gradeschool_speed_test1() {
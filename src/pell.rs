@@ -0,0 +1,593 @@
+use crate::arith::divisors;
+use crate::contfrac::{sqrt_continued_fraction, SqrtContinuedFraction};
+use crate::factor::{factor, Factorization};
+use crate::mparith::{isqrt, BigInt};
+use crate::residues::sqrt_mod;
+use std::collections::HashSet;
+
+// the square roots of D modulo m are found by trying every residue up to this m, and with
+// sqrt_mod on the factorization of m above it
+const BRUTE_FORCE_LIMIT: u64 = 10000;
+
+// panics unless x^2 - D y^2 = n, every solution handed out goes through this
+fn check(d: &BigInt, x: &BigInt, y: &BigInt, n: &BigInt) {
+    if x * x - d * y * y != *n {
+        panic!("Pell solution check failed");
+    }
+}
+
+// the convergent of sqrt(D) solving x^2 - D y^2 = -1 (negative) or 1: the one at the end of
+// the first period when its length is odd (resp. even), of the second one for the positive
+// equation with an odd period, and None otherwise or when D is not a positive nonsquare
+fn period_convergent(d: &BigInt, negative: bool) -> Option<(BigInt, BigInt)> {
+    if d.sgn != 1 {
+        return None;
+    }
+    let cf = sqrt_continued_fraction(d);
+    if cf.is_perfect_square() {
+        return None;
+    }
+    let l = cf.period.len();
+    let k = match (!l.is_multiple_of(2), negative) {
+        (true, true) => l - 1,
+        (false, true) => return None,
+        (true, false) => 2 * l - 1,
+        (false, false) => l - 1,
+    };
+    let mut convergents = cf.convergents();
+    convergents.nth(k)
+}
+
+/// Returns the fundamental solution (x, y) of x^2 - D y^2 = 1, the one with the smallest
+/// x > 1 and y > 0, or None when D is not a positive nonsquare.
+///
+/// It is the convergent of sqrt(D) at the end of the first period of the continued fraction
+/// if the period length is even, and at the end of the second period otherwise.
+pub fn pell_fundamental(d: &BigInt) -> Option<(BigInt, BigInt)> {
+    let (x, y) = period_convergent(d, false)?;
+    check(d, &x, &y, &BigInt::from_i64(1));
+    Some((x, y))
+}
+
+/// Returns the fundamental solution (x, y) of the negative Pell equation x^2 - D y^2 = -1,
+/// the one with the smallest positive x and y, or None if the equation has no solutions.
+///
+/// Solutions exist exactly when the period of the continued fraction of sqrt(D) has odd
+/// length, and the fundamental one is the convergent at the end of the first period.
+pub fn pell_negative_fundamental(d: &BigInt) -> Option<(BigInt, BigInt)> {
+    let (x, y) = period_convergent(d, true)?;
+    check(d, &x, &y, &BigInt::from_i64(-1));
+    Some((x, y))
+}
+
+/// Iterator over the solutions (x_k, y_k) of a Pell equation in increasing order.
+///
+/// Each step multiplies x + y sqrt(D) by the unit u + v sqrt(D), that is
+/// x' = u x + D v y and y' = v x + u y.
+#[derive(Debug, Clone)]
+pub struct PellSolutions {
+    d: BigInt,
+    current: (BigInt, BigInt),
+    unit: (BigInt, BigInt),
+    norm: BigInt,
+}
+
+impl Iterator for PellSolutions {
+    type Item = (BigInt, BigInt);
+
+    fn next(&mut self) -> Option<(BigInt, BigInt)> {
+        let (x, y) = &self.current;
+        let (u, v) = &self.unit;
+        let next = (u * x + &self.d * v * y, v * x + u * y);
+        let solution = std::mem::replace(&mut self.current, next);
+        check(&self.d, &solution.0, &solution.1, &self.norm);
+        Some(solution)
+    }
+}
+
+/// Returns the positive solutions of x^2 - D y^2 = 1, which are the powers
+/// (x_1 + y_1 sqrt(D))^k of the fundamental solution, or None when D is not a positive
+/// nonsquare.
+pub fn pell_solutions(d: &BigInt) -> Option<PellSolutions> {
+    let fundamental = pell_fundamental(d)?;
+    Some(PellSolutions {
+        d: d.clone(),
+        current: fundamental.clone(),
+        unit: fundamental,
+        norm: BigInt::from_i64(1),
+    })
+}
+
+/// Returns the positive solutions of x^2 - D y^2 = -1, which are the odd powers of its
+/// fundamental solution, or None if there are none.
+pub fn pell_negative_solutions(d: &BigInt) -> Option<PellSolutions> {
+    let (x, y) = pell_negative_fundamental(d)?;
+    // the square of the fundamental solution of the negative equation is the fundamental
+    // solution of the positive one
+    let unit = (&x * &x + d * &y * &y, BigInt::from_u64(2) * &x * &y);
+    Some(PellSolutions {
+        d: d.clone(),
+        current: (x, y),
+        unit,
+        norm: BigInt::from_i64(-1),
+    })
+}
+
+/// The solutions of the generalized Pell equation x^2 - D y^2 = N, grouped into classes.
+///
+/// Two solutions are in the same class when they differ by a factor +-(u + v sqrt(D))^k,
+/// where (u, v) is the fundamental solution of x^2 - D y^2 = 1, so every solution is
+/// +-(x + y sqrt(D)) (u + v sqrt(D))^k for one of the class representatives (x, y).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneralizedPell {
+    pub d: BigInt,
+    pub n: BigInt,
+    /// One solution per class, the one with the smallest y >= 0, sorted by y.
+    pub classes: Vec<(BigInt, BigInt)>,
+    /// The fundamental solution of x^2 - D y^2 = 1.
+    pub unit: (BigInt, BigInt),
+}
+
+/// Finds every class of solutions of x^2 - D y^2 = N for a positive nonsquare D and a
+/// nonzero N, returning None otherwise. The list of classes is empty when there are no
+/// solutions.
+///
+/// For every f with f^2 dividing N the primitive solutions of x^2 - D y^2 = m with
+/// m = N / f^2 are found and scaled by f. When m^2 < D, Lagrange's theorem says every
+/// positive primitive solution is a convergent of sqrt(D), so the convergents over one
+/// period of the fundamental unit are scanned for those of norm m. Otherwise the
+/// Lagrange-Matthews-Mollin algorithm is used: each square root z of D modulo |m| in
+/// (-|m|/2, |m|/2] starts the continued fraction of (z + sqrt(D)) / |m|, and a denominator
+/// Q_i = +-1 within its first period gives a solution (G_(i-1), B_(i-1)) of
+/// x^2 - D y^2 = +-m; solutions of -m are turned into solutions of m with the negative Pell
+/// equation when it is solvable. The square roots are found by trying every residue for
+/// |m| up to 10000 and by `sqrt_mod` on the factorization of |m| above that, so only this
+/// path needs |m| factored.
+pub fn generalized_pell(d: &BigInt, n: &BigInt) -> Option<GeneralizedPell> {
+    let unit = pell_fundamental(d)?;
+    if n.sgn == 0 {
+        return None;
+    }
+    let negative_unit = pell_negative_fundamental(d);
+    let sqrt_d = isqrt(d);
+    let square_part = Factorization {
+        sgn: 1,
+        factors: factor(n)
+            .factors
+            .into_iter()
+            .filter(|&(_, k)| k >= 2)
+            .map(|(p, k)| (p, k / 2))
+            .collect(),
+    };
+
+    let cf = sqrt_continued_fraction(d);
+    let mut classes: Vec<(BigInt, BigInt)> = Vec::new();
+    for f in divisors(&square_part) {
+        let m = n / (&f * &f);
+        let primitive = if &m * &m < *d {
+            lagrange_solutions(&cf, &m)
+        } else {
+            lmm_solutions(d, &sqrt_d, &m, &negative_unit)
+        };
+        for (x, y) in primitive {
+            let solution = reduce_in_class(d, &unit, (&f * x, &f * y));
+            check(d, &solution.0, &solution.1, n);
+            if !classes.iter().any(|c| same_class(d, n, c, &solution)) {
+                classes.push(solution);
+            }
+        }
+    }
+    classes.sort_by(|a, b| (&a.1, &a.0).cmp(&(&b.1, &b.0)));
+    Some(GeneralizedPell {
+        d: d.clone(),
+        n: n.clone(),
+        classes,
+        unit,
+    })
+}
+
+// the convergents of sqrt(D) with p^2 - D q^2 = m, for m^2 < D, over one period of the
+// fundamental unit (two periods of the continued fraction when its length is odd), which
+// meets every class of primitive solutions
+fn lagrange_solutions(cf: &SqrtContinuedFraction, m: &BigInt) -> Vec<(BigInt, BigInt)> {
+    let l = cf.period.len();
+    let window = if l.is_multiple_of(2) { l } else { 2 * l };
+    let d = &cf.radicand;
+    let mut convergents = cf.convergents();
+    convergents
+        .by_ref()
+        .take(window)
+        .filter(|(p, q)| p * p - d * q * q == *m)
+        .collect()
+}
+
+// primitive solutions of x^2 - D y^2 = m from the square roots of D modulo |m|, at least one
+// in each class
+fn lmm_solutions(
+    d: &BigInt,
+    sqrt_d: &BigInt,
+    m: &BigInt,
+    negative_unit: &Option<(BigInt, BigInt)>,
+) -> Vec<(BigInt, BigInt)> {
+    let mut m_abs = m.clone();
+    m_abs.sgn = 1;
+    let mut res = Vec::new();
+    for z in symmetric_sqrt_mod(d, &m_abs) {
+        let Some((r, s)) = lmm_solution(d, sqrt_d, &z, &m_abs) else {
+            continue;
+        };
+        if &r * &r - d * &s * &s == *m {
+            res.push((r, s));
+        } else if let Some((t, u)) = negative_unit {
+            res.push((&r * t + d * &s * u, &r * u + &s * t));
+        }
+    }
+    res
+}
+
+// the square roots of D modulo m, taken in (-m/2, m/2]
+fn symmetric_sqrt_mod(d: &BigInt, m: &BigInt) -> Vec<BigInt> {
+    let roots = match m.to_u64() {
+        Some(small) if small <= BRUTE_FORCE_LIMIT => {
+            let d_mod_m = d % m;
+            (0..small)
+                .map(BigInt::from_u64)
+                .filter(|z| (z * z) % m == d_mod_m)
+                .collect()
+        }
+        _ => sqrt_mod(d, &factor(m)),
+    };
+    roots
+        .into_iter()
+        .map(|z| if &z + &z > *m { z - m } else { z })
+        .collect()
+}
+
+// walks the continued fraction of (P_0 + sqrt(D)) / Q_0 with P_0^2 = D (mod Q_0), returning
+// (G_(i-1), B_(i-1)) for the first i >= 1 with Q_i = +-1, or None if the expansion becomes
+// periodic first
+fn lmm_solution(d: &BigInt, sqrt_d: &BigInt, p0: &BigInt, q0: &BigInt) -> Option<(BigInt, BigInt)> {
+    let one = BigInt::from_u64(1);
+    let (mut p, mut q) = (p0.clone(), q0.clone());
+    // G_i = a_i G_(i-1) + G_(i-2) from G_(-2) = -P_0, G_(-1) = Q_0, and B_i likewise from
+    // B_(-2) = 1, B_(-1) = 0
+    let (mut g_prev2, mut g_prev) = (-p0, q0.clone());
+    let (mut b_prev2, mut b_prev) = (one.clone(), BigInt::from_u64(0));
+    let mut seen = HashSet::new();
+    let mut first = true;
+    loop {
+        if !first && (q == one || q == -&one) {
+            return Some((g_prev, b_prev));
+        }
+        first = false;
+        if !seen.insert((p.clone(), q.clone())) {
+            return None;
+        }
+        // a = floor((P + sqrt(D)) / Q), with sqrt(D) irrational
+        let a = if q.sgn == 1 {
+            (&p + sqrt_d) / &q
+        } else {
+            -((&p + sqrt_d) / -&q) - &one
+        };
+        let g = &a * &g_prev + &g_prev2;
+        let b = &a * &b_prev + &b_prev2;
+        (g_prev2, g_prev) = (g_prev, g);
+        (b_prev2, b_prev) = (b_prev, b);
+        p = &a * &q - &p;
+        q = (d - &p * &p) / &q;
+    }
+}
+
+// (x + y sqrt(D)) (u + v sqrt(D))
+fn mul_unit(d: &BigInt, (x, y): &(BigInt, BigInt), (u, v): &(BigInt, BigInt)) -> (BigInt, BigInt) {
+    (x * u + d * y * v, x * v + y * u)
+}
+
+// the member of the class of (x, y) with the smallest y >= 0
+fn reduce_in_class(
+    d: &BigInt,
+    unit: &(BigInt, BigInt),
+    solution: (BigInt, BigInt),
+) -> (BigInt, BigInt) {
+    let inverse = (unit.0.clone(), -&unit.1);
+    let abs_y = |s: &(BigInt, BigInt)| {
+        let mut y = s.1.clone();
+        y.sgn = y.sgn.abs();
+        y
+    };
+    let mut best = solution;
+    for step in [&inverse, unit] {
+        loop {
+            let next = mul_unit(d, &best, step);
+            if abs_y(&next) < abs_y(&best) {
+                best = next;
+            } else {
+                break;
+            }
+        }
+    }
+    if best.1.sgn == -1 {
+        best = (-&best.0, -&best.1);
+    }
+    best
+}
+
+// two solutions of x^2 - D y^2 = N lie in the same class exactly when
+// x1 x2 - D y1 y2 and x1 y2 - x2 y1 are both divisible by N
+fn same_class(d: &BigInt, n: &BigInt, a: &(BigInt, BigInt), b: &(BigInt, BigInt)) -> bool {
+    let mut n = n.clone();
+    n.sgn = 1;
+    ((&a.0 * &b.0 - d * &a.1 * &b.1) % &n).sgn == 0 && ((&a.0 * &b.1 - &b.0 * &a.1) % &n).sgn == 0
+}
+
+impl GeneralizedPell {
+    /// Returns an iterator over the solutions +-(x + y sqrt(D)) (u + v sqrt(D))^k for k = 0,
+    /// 1, 2, ..., going through every class for each k, with the sign chosen so that y >= 0.
+    /// Every solution with x, y > 0 eventually shows up.
+    pub fn solutions(&self) -> GeneralizedPellSolutions {
+        GeneralizedPellSolutions {
+            classes: self
+                .classes
+                .iter()
+                .map(|c| PellSolutions {
+                    d: self.d.clone(),
+                    current: c.clone(),
+                    unit: self.unit.clone(),
+                    norm: self.n.clone(),
+                })
+                .collect(),
+            next_class: 0,
+        }
+    }
+}
+
+/// Iterator over the solutions of a generalized Pell equation, created by
+/// `GeneralizedPell::solutions`. It is empty when the equation has no solutions.
+#[derive(Debug, Clone)]
+pub struct GeneralizedPellSolutions {
+    classes: Vec<PellSolutions>,
+    next_class: usize,
+}
+
+impl Iterator for GeneralizedPellSolutions {
+    type Item = (BigInt, BigInt);
+
+    fn next(&mut self) -> Option<(BigInt, BigInt)> {
+        if self.classes.is_empty() {
+            return None;
+        }
+        let i = self.next_class;
+        self.next_class = (i + 1) % self.classes.len();
+        let (x, y) = self.classes[i].next()?;
+        if y.sgn == -1 {
+            Some((-x, -y))
+        } else {
+            Some((x, y))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::contfrac::sqrt_continued_fraction;
+    use crate::mparith::{build_bigint, isqrt, BigInt};
+
+    fn b(n: u64) -> BigInt {
+        BigInt::from_u64(n)
+    }
+
+    #[test]
+    fn pell_fundamental_test() {
+        let cases = [
+            (2, "3", "2"),
+            (7, "8", "3"),
+            (13, "649", "180"),
+            (61, "1766319049", "226153980"),
+            (109, "158070671986249", "15140424455100"),
+            (
+                991,
+                "379516400906811930638014896080",
+                "12055735790331359447442538767",
+            ),
+        ];
+        for (d, x, y) in cases {
+            assert_eq!(
+                Some((build_bigint(x), build_bigint(y))),
+                super::pell_fundamental(&b(d))
+            );
+        }
+        for d in [0, 1, 4, 144] {
+            assert_eq!(None, super::pell_fundamental(&b(d)));
+        }
+        assert_eq!(None, super::pell_fundamental(&BigInt::from_i64(-5)));
+    }
+
+    #[test]
+    fn pell_negative_fundamental_test() {
+        let cases = [
+            (2, "1", "1"),
+            (5, "2", "1"),
+            (13, "18", "5"),
+            (61, "29718", "3805"),
+        ];
+        for (d, x, y) in cases {
+            assert_eq!(
+                Some((build_bigint(x), build_bigint(y))),
+                super::pell_negative_fundamental(&b(d))
+            );
+        }
+        for d in [3, 7, 34, 16] {
+            assert_eq!(None, super::pell_negative_fundamental(&b(d)));
+        }
+    }
+
+    #[test]
+    fn pell_solutions_test() {
+        let solutions: Vec<(BigInt, BigInt)> =
+            super::pell_solutions(&b(2)).unwrap().take(4).collect();
+        assert_eq!(
+            vec![
+                (b(3), b(2)),
+                (b(17), b(12)),
+                (b(99), b(70)),
+                (b(577), b(408))
+            ],
+            solutions
+        );
+        let solutions: Vec<(BigInt, BigInt)> = super::pell_negative_solutions(&b(2))
+            .unwrap()
+            .take(4)
+            .collect();
+        assert_eq!(
+            vec![(b(1), b(1)), (b(7), b(5)), (b(41), b(29)), (b(239), b(169))],
+            solutions
+        );
+        assert!(super::pell_negative_solutions(&b(3)).is_none());
+        // the checks inside the iterator cover every solution
+        for d in 2..200 {
+            if let Some(solutions) = super::pell_solutions(&b(d)) {
+                assert_eq!(5, solutions.take(5).count());
+            }
+            if let Some(solutions) = super::pell_negative_solutions(&b(d)) {
+                assert_eq!(5, solutions.take(5).count());
+            }
+        }
+    }
+
+    fn i(n: i64) -> BigInt {
+        BigInt::from_i64(n)
+    }
+
+    #[test]
+    fn generalized_pell_test() {
+        // number of classes and some representatives from sympy's diop_DN
+        type Case<'a> = (u64, &'a str, usize, &'a [(&'a str, &'a str)]);
+        let cases: [Case; 12] = [
+            (
+                13,
+                "27",
+                4,
+                &[("220", "61"), ("40", "11"), ("768", "213"), ("12", "3")],
+            ),
+            (13, "-4", 3, &[("3", "1"), ("393", "109"), ("36", "10")]),
+            (2, "7", 2, &[("-3", "1"), ("3", "1")]),
+            (10, "9", 3, &[("7", "2"), ("13", "4"), ("57", "18")]),
+            (6, "-5", 2, &[("-1", "1"), ("1", "1")]),
+            (5, "-4", 3, &[("-1", "1"), ("1", "1"), ("4", "2")]),
+            (1000099, "2", 0, &[]),
+            (61, "-3", 2, &[("5639", "722"), ("469849", "60158")]),
+            (
+                991,
+                "-7",
+                2,
+                &[
+                    ("99653067", "3165584"),
+                    ("13329317833979374896288", "423419735452394562331"),
+                ],
+            ),
+            (2, "-98", 3, &[("-8", "-9"), ("12", "11"), ("0", "7")]),
+            // |N| above the brute force limit
+            (3, "1018084054243", 0, &[]),
+            (
+                2,
+                "-49000000343",
+                6,
+                &[
+                    ("611385", "459778"),
+                    ("448675", "353772"),
+                    ("69063", "163966"),
+                ],
+            ),
+        ];
+        for (d, n, count, expected) in cases {
+            let (d, n) = (b(d), build_bigint(n));
+            let pell = super::generalized_pell(&d, &n).unwrap();
+            assert_eq!(count, pell.classes.len());
+            for (x, y) in expected {
+                let solution = (build_bigint(x), build_bigint(y));
+                assert!(pell
+                    .classes
+                    .iter()
+                    .any(|c| super::same_class(&d, &n, c, &solution)));
+            }
+        }
+        let pell = super::generalized_pell(&b(157), &b(12)).unwrap();
+        assert_eq!(6, pell.classes.len());
+        assert_eq!(vec![(i(-13), b(1)), (b(13), b(1))], pell.classes[..2]);
+        let solutions: Vec<(BigInt, BigInt)> = super::generalized_pell(&b(2), &b(7))
+            .unwrap()
+            .solutions()
+            .take(4)
+            .collect();
+        assert_eq!(
+            vec![(i(-3), b(1)), (b(3), b(1)), (b(5), b(3)), (b(13), b(9))],
+            solutions
+        );
+        assert!(super::generalized_pell(&b(9), &b(7)).is_none());
+        assert!(super::generalized_pell(&b(7), &b(0)).is_none());
+    }
+
+    #[test]
+    fn lagrange_lmm_agree_test() {
+        // both ways of finding the primitive solutions meet the same classes when m^2 < D
+        let mut found = 0;
+        for d in [13_u64, 31, 46, 94, 109, 151, 211, 313, 421, 661, 991, 1021] {
+            let big_d = b(d);
+            let cf = sqrt_continued_fraction(&big_d);
+            let sqrt_d = isqrt(&big_d);
+            let negative_unit = super::pell_negative_fundamental(&big_d);
+            for m in (-30_i64..=30).filter(|&m| m != 0 && m * m < d as i64) {
+                let m = i(m);
+                let lagrange = super::lagrange_solutions(&cf, &m);
+                let lmm = super::lmm_solutions(&big_d, &sqrt_d, &m, &negative_unit);
+                found += lagrange.len();
+                for (a, others) in [(&lagrange, &lmm), (&lmm, &lagrange)] {
+                    for s in a {
+                        assert!(others.iter().any(|o| super::same_class(&big_d, &m, s, o)));
+                    }
+                }
+            }
+        }
+        assert!(found > 50, "{found}");
+    }
+
+    #[test]
+    fn generalized_pell_brute_force_test() {
+        // every small solution lies in one of the classes, which are distinct, and the
+        // positive ones are reached by the iterator
+        for d in [2, 3, 5, 6, 7, 10, 13, 21] {
+            for n in (-60..=60).filter(|&n| n != 0) {
+                let (big_d, big_n) = (b(d as u64), i(n));
+                let pell = super::generalized_pell(&big_d, &big_n).unwrap();
+                let found: Vec<(BigInt, BigInt)> =
+                    pell.solutions().take(8 * pell.classes.len()).collect();
+                for y in 0..200 {
+                    let x2 = n + d * y * y;
+                    let x = (x2.max(0) as f64).sqrt().round() as i64;
+                    if x * x != x2 {
+                        continue;
+                    }
+                    let solution = (i(x), i(y));
+                    let classes = pell
+                        .classes
+                        .iter()
+                        .filter(|c| {
+                            super::same_class(&big_d, &big_n, c, &solution)
+                                || super::same_class(&big_d, &big_n, c, &(-i(x), i(y)))
+                        })
+                        .count();
+                    assert!(classes >= 1, "{d} {n} {x} {y}");
+                    assert!(
+                        x == 0 || y == 0 || found.contains(&solution),
+                        "{d} {n} {x} {y}"
+                    );
+                }
+                for (j, c) in pell.classes.iter().enumerate() {
+                    assert!(pell.classes[..j]
+                        .iter()
+                        .all(|e| !super::same_class(&big_d, &big_n, c, e)));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use crate::modular::{ModContext, MontgomeryCtx};
+use crate::mparith::BigInt;
+use crate::residues::jacobi;
+use crate::roots::{is_perfect_square, small_primes};
+
+// trial division is done by every prime below this bound
+const TRIAL_DIVISION_LIMIT: usize = 1000;
+
+// Miller-Rabin with these bases is deterministic for every n < 2^64 (Jiang and Deng)
+const DETERMINISTIC_BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// The answer of a primality test.
+///
+/// `Prime` is only returned when the answer is proven (small inputs and inputs below 2^64);
+/// `ProbablyPrime` means n passed every test that was run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primality {
+    Composite,
+    ProbablyPrime,
+    Prime,
+}
+
+/// The test used for inputs that are too large for a deterministic answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimalityTest {
+    /// Miller-Rabin with the given number of pseudo-random bases
+    MillerRabin(u32),
+    /// a strong base 2 test followed by a strong Lucas test with Selfridge's parameters
+    BailliePsw,
+}
+
+/// Tests whether n is prime.
+///
+/// Small factors are removed by trial division first. Inputs below 2^64 then get a
+/// deterministic answer from Miller-Rabin with the first twelve primes as bases, and
+/// larger inputs are run through the chosen test. Numbers below 2 (including all negative
+/// numbers) are reported as `Composite`.
+pub fn is_probable_prime(n: &BigInt, test: PrimalityTest) -> Primality {
+    if n.sgn != 1 || (n.len == 1 && n.mag[0] == 1) {
+        return Primality::Composite;
+    }
+    if let Some(res) = trial_division(n) {
+        return res;
+    }
+
+    let ctx = MontgomeryCtx::new(n);
+    let n_minus_one = n - BigInt::from_u64(1);
+    if n.bits() <= 64 {
+        for base in DETERMINISTIC_BASES {
+            if !strong_probable_prime(&ctx, &n_minus_one, &BigInt::from_u64(base)) {
+                return Primality::Composite;
+            }
+        }
+        return Primality::Prime;
+    }
+
+    match test {
+        PrimalityTest::MillerRabin(rounds) => {
+            // bases are drawn from [2, n - 2] with a generator seeded by n, so the answer
+            // for a given n is reproducible
+            let mut state = n.mag[0] as u64 ^ 0x9e37_79b9_7f4a_7c15;
+            let range = n - BigInt::from_u64(3);
+            for _ in 0..rounds {
+                let mut base = BigInt::from_u64(0);
+                for _ in 0..n.len {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    base = (base << BigInt::from_u64(64)) + BigInt::from_u64(state);
+                }
+                base = base % &range + BigInt::from_u64(2);
+                if !strong_probable_prime(&ctx, &n_minus_one, &base) {
+                    return Primality::Composite;
+                }
+            }
+        }
+        PrimalityTest::BailliePsw => {
+            if !strong_probable_prime(&ctx, &n_minus_one, &BigInt::from_u64(2))
+                || !strong_lucas_probable_prime(n)
+            {
+                return Primality::Composite;
+            }
+        }
+    }
+    Primality::ProbablyPrime
+}
+
+// divides n > 1 by the primes below TRIAL_DIVISION_LIMIT, returning None if that decides nothing
+fn trial_division(n: &BigInt) -> Option<Primality> {
+    let primes = small_primes(TRIAL_DIVISION_LIMIT);
+    if let Some(v) = n.to_u64() {
+        if v < (TRIAL_DIVISION_LIMIT * TRIAL_DIVISION_LIMIT) as u64 {
+            // n has no factor above its square root, so trial division is a proof here
+            for &p in primes.iter().take_while(|&&p| (p as u64) * (p as u64) <= v) {
+                if v.is_multiple_of(p as u64) {
+                    return Some(Primality::Composite);
+                }
+            }
+            return Some(Primality::Prime);
+        }
+    }
+
+    // reduce n modulo a product of several primes at once to save on long divisions
+    let mut i = 0;
+    while i < primes.len() {
+        let mut prod: u64 = 1;
+        let mut j = i;
+        while j < primes.len() && prod < (1 << 52) {
+            prod *= primes[j] as u64;
+            j += 1;
+        }
+        let r = (n % BigInt::from_u64(prod)).to_u64().unwrap();
+        if primes[i..j].iter().any(|&p| r.is_multiple_of(p as u64)) {
+            return Some(Primality::Composite);
+        }
+        i = j;
+    }
+    None
+}
+
+// strong probable prime test of the odd modulus of ctx to the given base
+fn strong_probable_prime(ctx: &MontgomeryCtx, n_minus_one: &BigInt, base: &BigInt) -> bool {
+    let s = n_minus_one.trailing_zeros();
+    let d = n_minus_one >> BigInt::from_u64(s as u64);
+    let one = ctx.one();
+    let minus_one = ctx.to_domain(n_minus_one);
+
+    let mut x = ctx.pow_mod(&ctx.to_domain(base), &d);
+    if x == one || x == minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = ctx.sqr_mod(&x);
+        if x == minus_one {
+            return true;
+        }
+        if x == one {
+            return false;
+        }
+    }
+    false
+}
+
+// strong Lucas probable prime test with Selfridge's method A: D is the first of
+// 5, -7, 9, -11, ... with (D/n) = -1, P = 1 and Q = (1 - D) / 4
+fn strong_lucas_probable_prime(n: &BigInt) -> bool {
+    let mut d: i64 = 5;
+    let mut tries = 0;
+    loop {
+        let j = jacobi(&BigInt::from_i64(d), n);
+        if j == -1 {
+            break;
+        }
+        if j == 0 && BigInt::from_i64(d.abs()) != *n {
+            return false;
+        }
+        tries += 1;
+        // no D exists when n is a square
+        if tries == 10 && is_perfect_square(n) {
+            return false;
+        }
+        d = if d > 0 { -(d + 2) } else { -d + 2 };
+    }
+
+    let q = BigInt::from_i64((1 - d) / 4);
+    let d = BigInt::from_i64(d);
+    let half = |x: BigInt| -> BigInt {
+        if x.is_odd() {
+            (x + n) >> BigInt::from_u64(1)
+        } else {
+            x >> BigInt::from_u64(1)
+        }
+    };
+
+    // U_k, V_k and Q^k for k running over the leading bits of the odd part of n + 1
+    let n_plus_one = n + BigInt::from_u64(1);
+    let s = n_plus_one.trailing_zeros();
+    let k = n_plus_one >> BigInt::from_u64(s as u64);
+    let mut u = BigInt::from_u64(1);
+    let mut v = BigInt::from_u64(1);
+    let mut qk = &q % n;
+    for i in (0..(k.bits() - 1)).rev() {
+        u = (&u * &v) % n;
+        v = (&v * &v - &qk - &qk) % n;
+        qk = (&qk * &qk) % n;
+        if (k.mag[i / (isize::BITS as usize - 2)] >> (i % (isize::BITS as usize - 2))) & 1 == 1 {
+            let u_next = half(&u + &v);
+            v = half((&d * &u + &v) % n) % n;
+            u = u_next % n;
+            qk = (&qk * &q) % n;
+        }
+    }
+
+    if u.sgn == 0 || v.sgn == 0 {
+        return true;
+    }
+    for _ in 1..s {
+        v = (&v * &v - &qk - &qk) % n;
+        if v.sgn == 0 {
+            return true;
+        }
+        qk = (&qk * &qk) % n;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Primality, PrimalityTest};
+    use crate::mparith::{build_bigint, Abs, Pow};
+    use std::fs::File;
+    use std::io::{self, BufRead};
+    use std::path::Path;
+    const A_DEC: usize = 1;
+    const B_DEC: usize = 3;
+
+    fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(filename)?;
+        Ok(io::BufReader::new(file).lines())
+    }
+
+    const TESTS: [PrimalityTest; 2] = [PrimalityTest::MillerRabin(20), PrimalityTest::BailliePsw];
+
+    #[test]
+    fn small_primality_test() {
+        let primes = crate::roots::small_primes(3000);
+        for i in -5..3000 {
+            let expected = if i >= 0 && primes.contains(&(i as u32)) {
+                Primality::Prime
+            } else {
+                Primality::Composite
+            };
+            for test in TESTS {
+                assert_eq!(
+                    expected,
+                    super::is_probable_prime(&build_bigint(&i.to_string()), test)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn word_primality_test() {
+        // Carmichael numbers and strong pseudoprimes to several bases
+        for c in [
+            "41041",
+            "2047",
+            "3215031751",
+            "3825123056546413051",
+            "318665857834031151167461",
+        ] {
+            for test in TESTS {
+                assert_eq!(
+                    Primality::Composite,
+                    super::is_probable_prime(&build_bigint(c), test)
+                );
+            }
+        }
+        for p in ["1000003", "4294967291", "18446744073709551557"] {
+            for test in TESTS {
+                assert_eq!(
+                    Primality::Prime,
+                    super::is_probable_prime(&build_bigint(p), test)
+                );
+            }
+        }
+        for test in TESTS {
+            assert_eq!(
+                Primality::ProbablyPrime,
+                super::is_probable_prime(&build_bigint("18446744073709551629"), test)
+            );
+        }
+    }
+
+    #[test]
+    fn large_primality_test() {
+        let two = build_bigint("2");
+        let m521 = (&two).pow(build_bigint("521")) - build_bigint("1");
+        let m523 = (&two).pow(build_bigint("523")) - build_bigint("1");
+        let m127 = (&two).pow(build_bigint("127")) - build_bigint("1");
+        for test in TESTS {
+            assert_eq!(
+                Primality::ProbablyPrime,
+                super::is_probable_prime(&m521, test)
+            );
+            assert_eq!(Primality::Composite, super::is_probable_prime(&m523, test));
+            assert_eq!(
+                Primality::Composite,
+                super::is_probable_prime(&(&m127 * &m127), test)
+            );
+            assert_eq!(
+                Primality::Composite,
+                super::is_probable_prime(&(&m127 * &m521), test)
+            );
+        }
+    }
+
+    #[test]
+    fn strong_lucas_test() {
+        // strong Lucas pseudoprimes pass the Lucas part on its own
+        for c in ["5459", "5777", "10877", "16109", "18971"] {
+            assert!(super::strong_lucas_probable_prime(&build_bigint(c)));
+        }
+        for p in ["1000003", "4294967291", "18446744073709551557"] {
+            assert!(super::strong_lucas_probable_prime(&build_bigint(p)));
+        }
+        for c in ["1000001", "4294967297", "18446744073709551559"] {
+            assert!(!super::strong_lucas_probable_prime(&build_bigint(c)));
+        }
+    }
+
+    #[test]
+    fn product_primality_test() {
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.take(200).map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]).abs() + build_bigint("2");
+                let b = build_bigint(v[B_DEC]).abs() + build_bigint("2");
+                for test in TESTS {
+                    assert_eq!(
+                        Primality::Composite,
+                        super::is_probable_prime(&(&a * &b), test)
+                    );
+                }
+            }
+        }
+    }
+}
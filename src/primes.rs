@@ -0,0 +1,404 @@
+use crate::mparith::{isqrt, BigInt};
+use crate::primality::{is_probable_prime, Primality, PrimalityTest};
+use crate::roots::small_primes;
+use std::collections::VecDeque;
+
+// sieving primes used for next_prime, prev_prime and the prime iterator; numbers below
+// the square of this bound are decided by the sieve alone
+const WINDOW_SIEVE_LIMIT: u64 = 1 << 12;
+
+// sieving primes used by primes_in_range are bounded by the square root of hi and this
+const RANGE_SIEVE_LIMIT: u64 = 1 << 20;
+
+const SEGMENT_LEN: usize = 1 << 15;
+
+// the 2 * 3 * 5 wheel: residues mod 30 that are coprime to 30
+const WHEEL: [bool; 30] = {
+    let mut wheel = [false; 30];
+    let mut i = 0;
+    while i < 30 {
+        wheel[i] = i % 2 != 0 && i % 3 != 0 && i % 5 != 0;
+        i += 1;
+    }
+    wheel
+};
+
+// A sieve of consecutive segments [lo + pos, lo + pos + len) by the primes up to some limit.
+// The wheel removes multiples of 2, 3 and 5 and the remaining primes cross off their
+// multiples starting at the next multiple after the previous segment.
+struct Sieve {
+    lo: BigInt,
+    lo_small: Option<u64>,
+    lo_mod_30: usize,
+    primes: Vec<u64>,
+    next: Vec<u64>, // offset from lo of the next multiple of each prime to cross off
+    pos: u64,
+}
+
+impl Sieve {
+    fn new(lo: &BigInt, limit: u64) -> Sieve {
+        let lo_small = lo.to_u64();
+        let primes: Vec<u64> = small_primes(limit as usize)
+            .into_iter()
+            .map(|p| p as u64)
+            .filter(|&p| p > 5)
+            .collect();
+
+        // reduce lo modulo a product of several primes at once to save on long divisions
+        let mut next = Vec::with_capacity(primes.len());
+        let mut i = 0;
+        while i < primes.len() {
+            let mut prod: u64 = 1;
+            let mut j = i;
+            while j < primes.len() && prod.checked_mul(primes[j]).is_some_and(|x| x < 1 << 62) {
+                prod *= primes[j];
+                j += 1;
+            }
+            let r = (lo % BigInt::from_u64(prod)).to_u64().unwrap();
+            for &p in &primes[i..j] {
+                let mut off = (p - r % p) % p;
+                // a prime in the segment is not crossed off by itself
+                if let Some(l) = lo_small {
+                    if l <= p * p {
+                        off = p * p - l;
+                    }
+                }
+                next.push(off);
+            }
+            i = j;
+        }
+
+        Sieve {
+            lo: lo.clone(),
+            lo_small,
+            lo_mod_30: (lo % BigInt::from_u64(30)).to_u64().unwrap() as usize,
+            primes,
+            next,
+            pos: 0,
+        }
+    }
+
+    // flags for the next len numbers, true for those with no factor among the sieving primes
+    fn next_segment(&mut self, len: usize) -> Vec<bool> {
+        let mut flags: Vec<bool> = (0..len)
+            .map(|i| WHEEL[(self.lo_mod_30 + ((self.pos + i as u64) % 30) as usize) % 30])
+            .collect();
+        if let Some(l) = self.lo_small.filter(|&l| l.saturating_add(self.pos) <= 5) {
+            for (i, flag) in flags.iter_mut().enumerate() {
+                let v = l + self.pos + i as u64;
+                if v > 5 {
+                    break;
+                }
+                *flag = v == 2 || v == 3 || v == 5;
+            }
+        }
+
+        let end = self.pos + len as u64;
+        for (p, next) in self.primes.iter().zip(self.next.iter_mut()) {
+            while *next < end {
+                flags[(*next - self.pos) as usize] = false;
+                *next += p;
+            }
+        }
+        self.pos = end;
+        flags
+    }
+
+    // the number at the given offset from lo
+    fn value(&self, offset: u64) -> BigInt {
+        &self.lo + BigInt::from_u64(offset)
+    }
+}
+
+// whether the sieve with primes up to limit leaves only primes below hi
+fn sieve_is_exact(hi: &BigInt, limit: u64) -> bool {
+    *hi <= BigInt::from_u64(limit * limit)
+}
+
+// length of the windows searched by next_prime and prev_prime, about twice the average gap
+fn window_len(n: &BigInt) -> u64 {
+    (2 * n.bits() as u64).max(256)
+}
+
+/// Returns the smallest (probable) prime greater than n.
+///
+/// Candidates are taken from a window after n that has been sieved by the small primes,
+/// and only the survivors are run through the Baillie-PSW test, so results above 2^64 are
+/// probable primes.
+pub fn next_prime(n: &BigInt) -> BigInt {
+    if *n < BigInt::from_u64(2) {
+        return BigInt::from_u64(2);
+    }
+    let lo = n + BigInt::from_u64(1);
+    let len = window_len(n);
+    let mut sieve = Sieve::new(&lo, WINDOW_SIEVE_LIMIT);
+    loop {
+        let start = sieve.pos;
+        let flags = sieve.next_segment(len as usize);
+        let exact = sieve_is_exact(&sieve.value(sieve.pos), WINDOW_SIEVE_LIMIT);
+        for (i, &flag) in flags.iter().enumerate() {
+            if !flag {
+                continue;
+            }
+            let candidate = sieve.value(start + i as u64);
+            if exact
+                || is_probable_prime(&candidate, PrimalityTest::BailliePsw) != Primality::Composite
+            {
+                return candidate;
+            }
+        }
+    }
+}
+
+/// Returns the largest (probable) prime less than n, or None if n <= 2.
+pub fn prev_prime(n: &BigInt) -> Option<BigInt> {
+    if *n <= BigInt::from_u64(2) {
+        return None;
+    }
+    let len = BigInt::from_u64(window_len(n));
+    let exact = sieve_is_exact(n, WINDOW_SIEVE_LIMIT);
+    let mut hi = n.clone();
+    loop {
+        let lo = if hi > len {
+            &hi - &len
+        } else {
+            BigInt::from_u64(0)
+        };
+        let mut sieve = Sieve::new(&lo, WINDOW_SIEVE_LIMIT);
+        let flags = sieve.next_segment((&hi - &lo).to_u64().unwrap() as usize);
+        for (i, &flag) in flags.iter().enumerate().rev() {
+            if !flag {
+                continue;
+            }
+            let candidate = sieve.value(i as u64);
+            if exact
+                || is_probable_prime(&candidate, PrimalityTest::BailliePsw) != Primality::Composite
+            {
+                return Some(candidate);
+            }
+        }
+        hi = lo;
+    }
+}
+
+/// An iterator over the consecutive (probable) primes from a starting point, created by
+/// `primes_from`.
+pub struct PrimeIter {
+    sieve: Sieve,
+    candidates: VecDeque<u64>,
+    exact: bool,
+}
+
+/// Returns an iterator over the primes greater than or equal to start.
+pub fn primes_from(start: &BigInt) -> PrimeIter {
+    let lo = if *start < BigInt::from_u64(2) {
+        BigInt::from_u64(2)
+    } else {
+        start.clone()
+    };
+    PrimeIter {
+        sieve: Sieve::new(&lo, WINDOW_SIEVE_LIMIT),
+        candidates: VecDeque::new(),
+        exact: false,
+    }
+}
+
+impl Iterator for PrimeIter {
+    type Item = BigInt;
+
+    fn next(&mut self) -> Option<BigInt> {
+        loop {
+            while let Some(offset) = self.candidates.pop_front() {
+                let candidate = self.sieve.value(offset);
+                if self.exact
+                    || is_probable_prime(&candidate, PrimalityTest::BailliePsw)
+                        != Primality::Composite
+                {
+                    return Some(candidate);
+                }
+            }
+            let start = self.sieve.pos;
+            let len = window_len(&self.sieve.value(start));
+            let flags = self.sieve.next_segment(len as usize);
+            self.exact = sieve_is_exact(&self.sieve.value(self.sieve.pos), WINDOW_SIEVE_LIMIT);
+            self.candidates
+                .extend((0..len).filter(|&i| flags[i as usize]).map(|i| start + i));
+        }
+    }
+}
+
+/// Returns all the primes in [lo, hi) in increasing order.
+///
+/// This is a segmented sieve of Eratosthenes: the range is processed in segments of
+/// 2^15 numbers, sieved by the primes up to the square root of hi. When that square root
+/// is above 2^20 only the primes up to 2^20 are used and the survivors are checked with the
+/// Baillie-PSW test, so the range itself must be small enough to hold in memory.
+pub fn primes_in_range(lo: &BigInt, hi: &BigInt) -> Vec<BigInt> {
+    let lo = if *lo < BigInt::from_u64(2) {
+        BigInt::from_u64(2)
+    } else {
+        lo.clone()
+    };
+    if *hi <= lo {
+        return Vec::new();
+    }
+    let len = (hi - &lo)
+        .to_u64()
+        .expect("The range given to primes_in_range is too long");
+
+    let root = isqrt(&(hi - BigInt::from_u64(1)));
+    let limit = root
+        .to_u64()
+        .map_or(RANGE_SIEVE_LIMIT, |r| r.min(RANGE_SIEVE_LIMIT));
+    let exact = root.to_u64().is_some_and(|r| r <= RANGE_SIEVE_LIMIT);
+    let mut sieve = Sieve::new(&lo, limit);
+    let mut res = Vec::new();
+    while sieve.pos < len {
+        let start = sieve.pos;
+        let flags = sieve.next_segment((len - start).min(SEGMENT_LEN as u64) as usize);
+        for (i, &flag) in flags.iter().enumerate() {
+            if !flag {
+                continue;
+            }
+            let candidate = sieve.value(start + i as u64);
+            if exact
+                || is_probable_prime(&candidate, PrimalityTest::BailliePsw) != Primality::Composite
+            {
+                res.push(candidate);
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mparith::{build_bigint, Abs};
+    use crate::primality::{is_probable_prime, Primality, PrimalityTest};
+    use crate::roots::small_primes;
+    use std::fs::File;
+    use std::io::{self, BufRead};
+    use std::path::Path;
+    const A_DEC: usize = 1;
+
+    fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(filename)?;
+        Ok(io::BufReader::new(file).lines())
+    }
+
+    #[test]
+    fn small_next_prev_prime_test() {
+        let primes = small_primes(100000);
+        for n in 0..3000_u32 {
+            let next = primes.iter().find(|&&p| p > n).unwrap();
+            let prev = primes.iter().rev().find(|&&p| p < n);
+            let n_bigint = build_bigint(&n.to_string());
+            assert_eq!(
+                build_bigint(&next.to_string()),
+                super::next_prime(&n_bigint)
+            );
+            assert_eq!(
+                prev.map(|p| build_bigint(&p.to_string())),
+                super::prev_prime(&n_bigint)
+            );
+        }
+        assert_eq!(build_bigint("2"), super::next_prime(&build_bigint("-10")));
+        assert_eq!(None, super::prev_prime(&build_bigint("-10")));
+    }
+
+    #[test]
+    fn next_prev_prime_test() {
+        assert_eq!(
+            build_bigint("18446744073709551629"),
+            super::next_prime(&build_bigint("18446744073709551557"))
+        );
+        assert_eq!(
+            Some(build_bigint("18446744073709551557")),
+            super::prev_prime(&build_bigint("18446744073709551616"))
+        );
+        // the prime gap of 1132 following 1693182318746371
+        assert_eq!(
+            build_bigint("1693182318747503"),
+            super::next_prime(&build_bigint("1693182318746371"))
+        );
+        assert_eq!(
+            Some(build_bigint("1693182318746371")),
+            super::prev_prime(&build_bigint("1693182318747503"))
+        );
+
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.take(4).map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]).abs() + build_bigint("3");
+                let next = super::next_prime(&a);
+                let prev = super::prev_prime(&a).unwrap();
+                assert!(next > a && prev < a);
+                for p in [&next, &prev] {
+                    assert_ne!(
+                        Primality::Composite,
+                        is_probable_prime(p, PrimalityTest::BailliePsw)
+                    );
+                }
+                assert!(super::prev_prime(&next).unwrap() >= prev);
+                assert!(super::next_prime(&prev) <= next);
+            }
+        }
+    }
+
+    #[test]
+    fn primes_from_test() {
+        let primes: Vec<_> = super::primes_from(&build_bigint("0")).take(2000).collect();
+        let expected: Vec<_> = small_primes(20000)
+            .iter()
+            .take(2000)
+            .map(|p| build_bigint(&p.to_string()))
+            .collect();
+        assert_eq!(expected, primes);
+
+        let start = build_bigint("1000000000000000000000");
+        let mut prev = start.clone();
+        for p in super::primes_from(&start).take(20) {
+            assert_eq!(super::next_prime(&(&prev - build_bigint("1"))), p);
+            prev = p + build_bigint("1");
+        }
+    }
+
+    #[test]
+    fn primes_in_range_test() {
+        let expected: Vec<_> = small_primes(300000)
+            .iter()
+            .map(|p| build_bigint(&p.to_string()))
+            .collect();
+        assert_eq!(
+            expected,
+            super::primes_in_range(&build_bigint("-5"), &build_bigint("300001"))
+        );
+        assert_eq!(
+            expected[1..5].to_vec(),
+            super::primes_in_range(&build_bigint("3"), &build_bigint("13"))
+        );
+        assert!(super::primes_in_range(&build_bigint("24"), &build_bigint("29")).is_empty());
+        assert!(super::primes_in_range(&build_bigint("30"), &build_bigint("20")).is_empty());
+
+        // 1e18 to 1e18 + 1000 contains 23 primes
+        let lo = build_bigint("1000000000000000000");
+        let primes = super::primes_in_range(&lo, &(&lo + build_bigint("1000")));
+        assert_eq!(23, primes.len());
+        let mut p = super::next_prime(&(&lo - build_bigint("1")));
+        for q in primes {
+            assert_eq!(p, q);
+            p = super::next_prime(&p);
+        }
+
+        let lo = build_bigint("100000000000000000000000000000");
+        let primes = super::primes_in_range(&lo, &(&lo + build_bigint("1000")));
+        assert_eq!(super::next_prime(&lo), primes[0]);
+        assert_eq!(
+            super::prev_prime(&(&lo + build_bigint("1000"))).unwrap(),
+            *primes.last().unwrap()
+        );
+    }
+}
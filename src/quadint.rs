@@ -0,0 +1,427 @@
+use crate::mparith::{BigInt, Pow};
+use std::fmt;
+use std::ops;
+
+const LIMB_BITS: usize = isize::BITS as usize - 2;
+
+/// An element of the quadratic ring Z[sqrt(D)], or of Z[(1 + sqrt(D)) / 2] for D = 1
+/// (mod 4).
+///
+/// The ring is given by its discriminant, 4D for Z[sqrt(D)] and D for Z[(1 + sqrt(D)) / 2].
+/// Elements of Z[sqrt(D)] are x + y sqrt(D), and elements of the larger ring are stored as
+/// (x + y sqrt(D)) / 2 with x = y (mod 2). Arithmetic between elements of different rings
+/// panics.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QuadInt {
+    pub x: BigInt,
+    pub y: BigInt,
+    pub disc: BigInt,
+}
+
+impl QuadInt {
+    /// x + y sqrt(D) in Z[sqrt(D)].
+    pub fn new(x: &BigInt, y: &BigInt, d: &BigInt) -> QuadInt {
+        QuadInt {
+            x: x.clone(),
+            y: y.clone(),
+            disc: BigInt::from_u64(4) * d,
+        }
+    }
+
+    /// (x + y sqrt(D)) / 2 in Z[(1 + sqrt(D)) / 2], which needs D = 1 (mod 4) and x = y
+    /// (mod 2).
+    pub fn new_half(x: &BigInt, y: &BigInt, d: &BigInt) -> QuadInt {
+        if d % BigInt::from_u64(4) != BigInt::from_u64(1) {
+            panic!("Half-integer quadratic rings need D = 1 (mod 4)");
+        }
+        if x.is_odd() != y.is_odd() {
+            panic!("Half-integer quadratic integers need x = y (mod 2)");
+        }
+        QuadInt {
+            x: x.clone(),
+            y: y.clone(),
+            disc: d.clone(),
+        }
+    }
+
+    /// The rational integer n in the same ring as self.
+    pub fn embed(&self, n: &BigInt) -> QuadInt {
+        let x = if self.is_half() { n + n } else { n.clone() };
+        QuadInt {
+            x,
+            y: BigInt::from_u64(0),
+            disc: self.disc.clone(),
+        }
+    }
+
+    /// Whether this is the ring Z[(1 + sqrt(D)) / 2].
+    pub fn is_half(&self) -> bool {
+        self.disc.is_odd()
+    }
+
+    /// The D of sqrt(D).
+    pub fn radicand(&self) -> BigInt {
+        if self.is_half() {
+            self.disc.clone()
+        } else {
+            &self.disc / BigInt::from_u64(4)
+        }
+    }
+
+    /// The conjugate, with sqrt(D) replaced by -sqrt(D).
+    pub fn conj(&self) -> QuadInt {
+        QuadInt {
+            x: self.x.clone(),
+            y: -&self.y,
+            disc: self.disc.clone(),
+        }
+    }
+
+    /// The norm, the product with the conjugate.
+    pub fn norm(&self) -> BigInt {
+        let n = &self.x * &self.x - self.radicand() * &self.y * &self.y;
+        if self.is_half() {
+            n / BigInt::from_u64(4)
+        } else {
+            n
+        }
+    }
+
+    /// The trace, the sum with the conjugate.
+    pub fn trace(&self) -> BigInt {
+        if self.is_half() {
+            self.x.clone()
+        } else {
+            &self.x + &self.x
+        }
+    }
+
+    /// Whether the norm is 1 or -1.
+    pub fn is_unit(&self) -> bool {
+        let n = self.norm();
+        n == BigInt::from_u64(1) || n == BigInt::from_i64(-1)
+    }
+
+    /// Returns self / n if that lies in the ring, None otherwise or when n = 0.
+    pub fn div_exact(&self, n: &BigInt) -> Option<QuadInt> {
+        if n.sgn == 0 || (&self.x % n).sgn != 0 || (&self.y % n).sgn != 0 {
+            return None;
+        }
+        let (x, y) = (&self.x / n, &self.y / n);
+        if self.is_half() && x.is_odd() != y.is_odd() {
+            return None;
+        }
+        Some(QuadInt {
+            x,
+            y,
+            disc: self.disc.clone(),
+        })
+    }
+}
+
+fn same_ring(a: &QuadInt, b: &QuadInt) {
+    if a.disc != b.disc {
+        panic!("Quadratic integers must be in the same ring");
+    }
+}
+
+fn addsub(a: &QuadInt, b: &QuadInt, sgn: i64) -> QuadInt {
+    same_ring(a, b);
+    let sgn = BigInt::from_i64(sgn);
+    QuadInt {
+        x: &a.x + &sgn * &b.x,
+        y: &a.y + &sgn * &b.y,
+        disc: a.disc.clone(),
+    }
+}
+
+fn mul(a: &QuadInt, b: &QuadInt) -> QuadInt {
+    same_ring(a, b);
+    let x = &a.x * &b.x + a.radicand() * &a.y * &b.y;
+    let y = &a.x * &b.y + &a.y * &b.x;
+    if a.is_half() {
+        // (x + y sqrt(D)) / 4 with x and y even
+        let two = BigInt::from_u64(2);
+        QuadInt {
+            x: x / &two,
+            y: y / &two,
+            disc: a.disc.clone(),
+        }
+    } else {
+        QuadInt {
+            x,
+            y,
+            disc: a.disc.clone(),
+        }
+    }
+}
+
+// exponentiation by squaring, from the most significant bit of e
+fn pow(a: &QuadInt, e: &BigInt) -> QuadInt {
+    if e.sgn == -1 {
+        panic!("Quadratic integers can only be raised to non-negative powers");
+    }
+    let mut res = a.embed(&BigInt::from_u64(1));
+    for i in (0..e.bits()).rev() {
+        res = mul(&res, &res);
+        if (e.mag[i / LIMB_BITS] >> (i % LIMB_BITS)) & 1 == 1 {
+            res = mul(&res, a);
+        }
+    }
+    res
+}
+
+impl ops::Add<QuadInt> for QuadInt {
+    type Output = QuadInt;
+
+    fn add(self, b: QuadInt) -> QuadInt {
+        addsub(&self, &b, 1)
+    }
+}
+
+impl ops::Add<&QuadInt> for QuadInt {
+    type Output = QuadInt;
+
+    fn add(self, b: &QuadInt) -> QuadInt {
+        addsub(&self, b, 1)
+    }
+}
+
+impl ops::Add<QuadInt> for &QuadInt {
+    type Output = QuadInt;
+
+    fn add(self, b: QuadInt) -> QuadInt {
+        addsub(self, &b, 1)
+    }
+}
+
+impl ops::Add<&QuadInt> for &QuadInt {
+    type Output = QuadInt;
+
+    fn add(self, b: &QuadInt) -> QuadInt {
+        addsub(self, b, 1)
+    }
+}
+
+impl ops::Sub<QuadInt> for QuadInt {
+    type Output = QuadInt;
+
+    fn sub(self, b: QuadInt) -> QuadInt {
+        addsub(&self, &b, -1)
+    }
+}
+
+impl ops::Sub<&QuadInt> for QuadInt {
+    type Output = QuadInt;
+
+    fn sub(self, b: &QuadInt) -> QuadInt {
+        addsub(&self, b, -1)
+    }
+}
+
+impl ops::Sub<QuadInt> for &QuadInt {
+    type Output = QuadInt;
+
+    fn sub(self, b: QuadInt) -> QuadInt {
+        addsub(self, &b, -1)
+    }
+}
+
+impl ops::Sub<&QuadInt> for &QuadInt {
+    type Output = QuadInt;
+
+    fn sub(self, b: &QuadInt) -> QuadInt {
+        addsub(self, b, -1)
+    }
+}
+
+impl ops::Mul<QuadInt> for QuadInt {
+    type Output = QuadInt;
+
+    fn mul(self, b: QuadInt) -> QuadInt {
+        mul(&self, &b)
+    }
+}
+
+impl ops::Mul<&QuadInt> for QuadInt {
+    type Output = QuadInt;
+
+    fn mul(self, b: &QuadInt) -> QuadInt {
+        mul(&self, b)
+    }
+}
+
+impl ops::Mul<QuadInt> for &QuadInt {
+    type Output = QuadInt;
+
+    fn mul(self, b: QuadInt) -> QuadInt {
+        mul(self, &b)
+    }
+}
+
+impl ops::Mul<&QuadInt> for &QuadInt {
+    type Output = QuadInt;
+
+    fn mul(self, b: &QuadInt) -> QuadInt {
+        mul(self, b)
+    }
+}
+
+impl ops::Neg for QuadInt {
+    type Output = QuadInt;
+
+    fn neg(self) -> QuadInt {
+        -&self
+    }
+}
+
+impl ops::Neg for &QuadInt {
+    type Output = QuadInt;
+
+    fn neg(self) -> QuadInt {
+        QuadInt {
+            x: -&self.x,
+            y: -&self.y,
+            disc: self.disc.clone(),
+        }
+    }
+}
+
+impl Pow<&BigInt> for &QuadInt {
+    type Output = QuadInt;
+
+    fn pow(self, e: &BigInt) -> QuadInt {
+        pow(self, e)
+    }
+}
+
+impl Pow<&BigInt> for QuadInt {
+    type Output = QuadInt;
+
+    fn pow(self, e: &BigInt) -> QuadInt {
+        pow(&self, e)
+    }
+}
+
+/// Division by a rational integer, which panics unless it is exact; see `div_exact`.
+impl ops::Div<&BigInt> for &QuadInt {
+    type Output = QuadInt;
+
+    fn div(self, n: &BigInt) -> QuadInt {
+        self.div_exact(n)
+            .expect("Quadratic integer is not divisible by the integer")
+    }
+}
+
+impl ops::Div<&BigInt> for QuadInt {
+    type Output = QuadInt;
+
+    fn div(self, n: &BigInt) -> QuadInt {
+        &self / n
+    }
+}
+
+/// Shown as x + y√D, or as (x + y√D)/2 for the half-integers of Z[(1 + √D)/2].
+impl fmt::Display for QuadInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (x, y, half) = if self.is_half() && self.x.is_odd() {
+            (self.x.clone(), self.y.clone(), true)
+        } else if self.is_half() {
+            let two = BigInt::from_u64(2);
+            (&self.x / &two, &self.y / &two, false)
+        } else {
+            (self.x.clone(), self.y.clone(), false)
+        };
+        let sign = if y.sgn == -1 { '-' } else { '+' };
+        let mut y_abs = y;
+        y_abs.sgn = y_abs.sgn.abs();
+        let s = format!("{} {} {}√{}", x, sign, y_abs, self.radicand());
+        if half {
+            write!(f, "({s})/2")
+        } else {
+            write!(f, "{s}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuadInt;
+    use crate::mparith::{build_bigint, BigInt, Pow};
+    use crate::pell::pell_fundamental;
+
+    fn i(n: i64) -> BigInt {
+        BigInt::from_i64(n)
+    }
+
+    #[test]
+    fn quadint_arithmetic_test() {
+        let a = QuadInt::new(&i(3), &i(2), &i(2));
+        let b = QuadInt::new(&i(-1), &i(5), &i(2));
+        assert_eq!(QuadInt::new(&i(2), &i(7), &i(2)), &a + &b);
+        assert_eq!(QuadInt::new(&i(4), &i(-3), &i(2)), &a - &b);
+        // (3 + 2√2)(-1 + 5√2) = -3 + 20 + (15 - 2)√2
+        assert_eq!(QuadInt::new(&i(17), &i(13), &i(2)), &a * &b);
+        assert_eq!(QuadInt::new(&i(-3), &i(-2), &i(2)), -&a);
+        assert_eq!(i(1), a.norm());
+        assert_eq!(i(6), a.trace());
+        assert_eq!(i(1), (&a * &a.conj()).x);
+        assert!(a.is_unit() && !b.is_unit());
+        assert_eq!((&a * &b).norm(), a.norm() * b.norm());
+        assert_eq!("3 + 2√2", a.to_string());
+        assert_eq!(
+            "-1 - 5√-7",
+            QuadInt::new(&i(-1), &i(-5), &i(-7)).to_string()
+        );
+
+        // the powers of the fundamental unit are the solutions of the Pell equation
+        let d = i(61);
+        let (x, y) = pell_fundamental(&d).unwrap();
+        let unit = QuadInt::new(&x, &y, &d);
+        let cube = (&unit).pow(&i(3));
+        assert_eq!(&(&unit * &unit) * &unit, cube);
+        assert_eq!(i(1), cube.norm());
+        assert_eq!(unit.embed(&i(1)), (&unit).pow(&i(0)));
+        assert_eq!(build_bigint("2822295814832482312327709940"), cube.y);
+
+        let c = QuadInt::new(&i(12), &i(-18), &i(7));
+        assert_eq!(QuadInt::new(&i(2), &i(-3), &i(7)), &c / &i(6));
+        assert_eq!(None, c.div_exact(&i(4)));
+        assert_eq!(None, c.div_exact(&i(0)));
+    }
+
+    #[test]
+    fn quadint_half_test() {
+        // the golden ratio (1 + √5)/2, the fundamental unit of Z[(1 + √5)/2]
+        let phi = QuadInt::new_half(&i(1), &i(1), &i(5));
+        assert!(phi.is_half());
+        assert_eq!(i(5), phi.radicand());
+        assert_eq!(i(-1), phi.norm());
+        assert_eq!(i(1), phi.trace());
+        assert_eq!("(1 + 1√5)/2", phi.to_string());
+        // phi^2 = phi + 1 and phi^n = (L_n + F_n √5)/2
+        let one = phi.embed(&i(1));
+        assert_eq!(&phi + &one, &phi * &phi);
+        let p = (&phi).pow(&i(10));
+        assert_eq!(QuadInt::new_half(&i(123), &i(55), &i(5)), p);
+        assert_eq!("(123 + 55√5)/2", p.to_string());
+        // phi^3 = 2 + √5 lies in Z[√5] too
+        assert_eq!("2 + 1√5", (&phi).pow(&i(3)).to_string());
+        assert_eq!(i(-1), (&phi).pow(&i(3)).norm());
+
+        let two = QuadInt::new_half(&i(2), &i(2), &i(5));
+        assert_eq!(phi, &two / &i(2));
+        let root5 = QuadInt::new_half(&i(0), &i(2), &i(5));
+        assert_eq!(None, root5.div_exact(&i(2)));
+        assert_eq!(i(-5), root5.norm());
+        let imaginary = QuadInt::new_half(&i(1), &i(1), &i(-3));
+        assert_eq!(i(1), imaginary.norm());
+        assert_eq!(imaginary.embed(&i(-1)), (&imaginary).pow(&i(3)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn quadint_different_rings_test() {
+        let _ = QuadInt::new(&i(1), &i(1), &i(2)) + QuadInt::new(&i(1), &i(1), &i(3));
+    }
+}
@@ -0,0 +1,581 @@
+use crate::gcd::gcd;
+use crate::mparith::{abs, build_bigint, Abs, BigInt, Pow};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops;
+use std::str::FromStr;
+
+/// An exact fraction p / q of BigInts, always in lowest terms with q > 0, so equal values
+/// have equal representations and the derived `Eq` and `Hash` are those of the rationals.
+///
+/// Division by zero panics like it does for `BigInt`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigRational {
+    num: BigInt,
+    den: BigInt,
+}
+
+impl BigRational {
+    /// num / den reduced to lowest terms, which panics if den = 0.
+    pub fn new(num: &BigInt, den: &BigInt) -> BigRational {
+        if den.sgn == 0 {
+            panic!("Divide by zero error");
+        }
+        let g = gcd(num, den);
+        let mut num = num / &g;
+        let mut den = den / &g;
+        if den.sgn == -1 {
+            num = -num;
+            den = -den;
+        }
+        BigRational { num, den }
+    }
+
+    /// The integer n as n / 1.
+    pub fn from_integer(n: &BigInt) -> BigRational {
+        BigRational {
+            num: n.clone(),
+            den: BigInt::from_u64(1),
+        }
+    }
+
+    /// The exact value of a finite f64, which is a dyadic fraction, or None for infinities and
+    /// NaN.
+    pub fn from_f64(x: f64) -> Option<BigRational> {
+        if !x.is_finite() {
+            return None;
+        }
+        let bits = x.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = bits & ((1 << 52) - 1);
+        // subnormals have no implicit leading bit and the exponent of the smallest normals
+        let (mantissa, exponent) = if exponent == 0 {
+            (fraction, -1074)
+        } else {
+            (fraction | (1 << 52), exponent - 1075)
+        };
+        let mut num = BigInt::from_u64(mantissa);
+        if bits >> 63 == 1 {
+            num = -num;
+        }
+        let shift = BigInt::from_u64(exponent.unsigned_abs());
+        if exponent >= 0 {
+            Some(BigRational::from_integer(&(num << shift)))
+        } else {
+            Some(BigRational::new(&num, &(BigInt::from_u64(1) << shift)))
+        }
+    }
+
+    pub fn numer(&self) -> &BigInt {
+        &self.num
+    }
+
+    /// The denominator, which is always positive.
+    pub fn denom(&self) -> &BigInt {
+        &self.den
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.den == BigInt::from_u64(1)
+    }
+
+    /// 1 / self, which panics for 0.
+    pub fn recip(&self) -> BigRational {
+        BigRational::new(&self.den, &self.num)
+    }
+
+    /// The largest integer <= self.
+    pub fn floor(&self) -> BigInt {
+        &self.num / &self.den
+    }
+
+    /// The smallest integer >= self.
+    pub fn ceil(&self) -> BigInt {
+        -(-&self.num / &self.den)
+    }
+
+    /// The nearest integer, with halves rounded away from zero like `f64::round`.
+    pub fn round(&self) -> BigInt {
+        // floor(|x| + 1/2) = floor((2|p| + q) / 2q), with the sign put back
+        let two = BigInt::from_u64(2);
+        let r = (&two * abs(&self.num) + &self.den) / (&two * &self.den);
+        if self.num.sgn == -1 {
+            -r
+        } else {
+            r
+        }
+    }
+}
+
+// a/b + sign c/d over the common denominator lcm(b, d), as in Knuth 4.5.1
+fn addsub(x: &BigRational, y: &BigRational, sign: isize) -> BigRational {
+    let g = gcd(&x.den, &y.den);
+    let mut c = &y.num * (&x.den / &g);
+    c.sgn *= sign;
+    let num = &x.num * (&y.den / &g) + c;
+    BigRational::new(&num, &(&x.den / &g * &y.den))
+}
+
+fn mul(x: &BigRational, y: &BigRational) -> BigRational {
+    // cross-cancelling keeps the products small and already in lowest terms
+    if x.num.sgn == 0 || y.num.sgn == 0 {
+        return BigRational::from_integer(&BigInt::from_u64(0));
+    }
+    let g1 = gcd(&x.num, &y.den);
+    let g2 = gcd(&y.num, &x.den);
+    BigRational {
+        num: (&x.num / &g1) * (&y.num / &g2),
+        den: (&x.den / &g2) * (&y.den / &g1),
+    }
+}
+
+fn div(x: &BigRational, y: &BigRational) -> BigRational {
+    mul(x, &y.recip())
+}
+
+// the floored remainder x - y floor(x / y), which has the sign of y
+fn rem(x: &BigRational, y: &BigRational) -> BigRational {
+    let q = div(x, y).floor();
+    addsub(x, &mul(y, &BigRational::from_integer(&q)), -1)
+}
+
+// exponentiation of numerator and denominator, where negative exponents invert
+fn pow(x: &BigRational, e: &BigInt) -> BigRational {
+    if e.sgn == -1 {
+        return pow(&x.recip(), &-e);
+    }
+    BigRational {
+        num: (&x.num).pow(e),
+        den: (&x.den).pow(e),
+    }
+}
+
+impl ops::Add<BigRational> for BigRational {
+    type Output = BigRational;
+
+    fn add(self, b: BigRational) -> BigRational {
+        addsub(&self, &b, 1)
+    }
+}
+
+impl ops::Add<&BigRational> for BigRational {
+    type Output = BigRational;
+
+    fn add(self, b: &BigRational) -> BigRational {
+        addsub(&self, b, 1)
+    }
+}
+
+impl ops::Add<BigRational> for &BigRational {
+    type Output = BigRational;
+
+    fn add(self, b: BigRational) -> BigRational {
+        addsub(self, &b, 1)
+    }
+}
+
+impl ops::Add<&BigRational> for &BigRational {
+    type Output = BigRational;
+
+    fn add(self, b: &BigRational) -> BigRational {
+        addsub(self, b, 1)
+    }
+}
+
+impl ops::Sub<BigRational> for BigRational {
+    type Output = BigRational;
+
+    fn sub(self, b: BigRational) -> BigRational {
+        addsub(&self, &b, -1)
+    }
+}
+
+impl ops::Sub<&BigRational> for BigRational {
+    type Output = BigRational;
+
+    fn sub(self, b: &BigRational) -> BigRational {
+        addsub(&self, b, -1)
+    }
+}
+
+impl ops::Sub<BigRational> for &BigRational {
+    type Output = BigRational;
+
+    fn sub(self, b: BigRational) -> BigRational {
+        addsub(self, &b, -1)
+    }
+}
+
+impl ops::Sub<&BigRational> for &BigRational {
+    type Output = BigRational;
+
+    fn sub(self, b: &BigRational) -> BigRational {
+        addsub(self, b, -1)
+    }
+}
+
+impl ops::Mul<BigRational> for BigRational {
+    type Output = BigRational;
+
+    fn mul(self, b: BigRational) -> BigRational {
+        mul(&self, &b)
+    }
+}
+
+impl ops::Mul<&BigRational> for BigRational {
+    type Output = BigRational;
+
+    fn mul(self, b: &BigRational) -> BigRational {
+        mul(&self, b)
+    }
+}
+
+impl ops::Mul<BigRational> for &BigRational {
+    type Output = BigRational;
+
+    fn mul(self, b: BigRational) -> BigRational {
+        mul(self, &b)
+    }
+}
+
+impl ops::Mul<&BigRational> for &BigRational {
+    type Output = BigRational;
+
+    fn mul(self, b: &BigRational) -> BigRational {
+        mul(self, b)
+    }
+}
+
+impl ops::Div<BigRational> for BigRational {
+    type Output = BigRational;
+
+    fn div(self, b: BigRational) -> BigRational {
+        div(&self, &b)
+    }
+}
+
+impl ops::Div<&BigRational> for BigRational {
+    type Output = BigRational;
+
+    fn div(self, b: &BigRational) -> BigRational {
+        div(&self, b)
+    }
+}
+
+impl ops::Div<BigRational> for &BigRational {
+    type Output = BigRational;
+
+    fn div(self, b: BigRational) -> BigRational {
+        div(self, &b)
+    }
+}
+
+impl ops::Div<&BigRational> for &BigRational {
+    type Output = BigRational;
+
+    fn div(self, b: &BigRational) -> BigRational {
+        div(self, b)
+    }
+}
+
+impl ops::Rem<BigRational> for BigRational {
+    type Output = BigRational;
+
+    fn rem(self, b: BigRational) -> BigRational {
+        rem(&self, &b)
+    }
+}
+
+impl ops::Rem<&BigRational> for BigRational {
+    type Output = BigRational;
+
+    fn rem(self, b: &BigRational) -> BigRational {
+        rem(&self, b)
+    }
+}
+
+impl ops::Rem<BigRational> for &BigRational {
+    type Output = BigRational;
+
+    fn rem(self, b: BigRational) -> BigRational {
+        rem(self, &b)
+    }
+}
+
+impl ops::Rem<&BigRational> for &BigRational {
+    type Output = BigRational;
+
+    fn rem(self, b: &BigRational) -> BigRational {
+        rem(self, b)
+    }
+}
+
+impl ops::Neg for BigRational {
+    type Output = BigRational;
+
+    fn neg(self) -> BigRational {
+        -&self
+    }
+}
+
+impl ops::Neg for &BigRational {
+    type Output = BigRational;
+
+    fn neg(self) -> BigRational {
+        BigRational {
+            num: -&self.num,
+            den: self.den.clone(),
+        }
+    }
+}
+
+/// Exponentiation by any integer, where negative powers are powers of the reciprocal.
+impl Pow<BigInt> for BigRational {
+    type Output = BigRational;
+
+    fn pow(self, e: BigInt) -> BigRational {
+        pow(&self, &e)
+    }
+}
+
+impl Pow<&BigInt> for BigRational {
+    type Output = BigRational;
+
+    fn pow(self, e: &BigInt) -> BigRational {
+        pow(&self, e)
+    }
+}
+
+impl Pow<BigInt> for &BigRational {
+    type Output = BigRational;
+
+    fn pow(self, e: BigInt) -> BigRational {
+        pow(self, &e)
+    }
+}
+
+impl Pow<&BigInt> for &BigRational {
+    type Output = BigRational;
+
+    fn pow(self, e: &BigInt) -> BigRational {
+        pow(self, e)
+    }
+}
+
+impl Abs for BigRational {
+    type Output = BigRational;
+
+    fn abs(self) -> BigRational {
+        (&self).abs()
+    }
+}
+
+impl Abs for &BigRational {
+    type Output = BigRational;
+
+    fn abs(self) -> BigRational {
+        BigRational {
+            num: abs(&self.num),
+            den: self.den.clone(),
+        }
+    }
+}
+
+impl Ord for BigRational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // the denominators are positive, so cross-multiplying keeps the order
+        (&self.num * &other.den).cmp(&(&other.num * &self.den))
+    }
+}
+
+impl PartialOrd for BigRational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Shown as p/q, or just p for integers.
+impl fmt::Display for BigRational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+/// The error returned when a string is not a valid fraction or decimal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRationalError;
+
+impl fmt::Display for ParseRationalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid rational number")
+    }
+}
+
+// an optional sign followed by at least one digit
+fn parse_integer(s: &str) -> Result<BigInt, ParseRationalError> {
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ParseRationalError);
+    }
+    Ok(build_bigint(s))
+}
+
+/// Parses "p/q" with an optional sign on either part, an integer "p", or a decimal like
+/// "-1.25", which is read exactly as 125 / 100.
+impl FromStr for BigRational {
+    type Err = ParseRationalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((p, q)) = s.split_once('/') {
+            let q = parse_integer(q)?;
+            if q.sgn == 0 {
+                return Err(ParseRationalError);
+            }
+            return Ok(BigRational::new(&parse_integer(p)?, &q));
+        }
+        let Some((int, frac)) = s.split_once('.') else {
+            return Ok(BigRational::from_integer(&parse_integer(s)?));
+        };
+        if frac.is_empty() || !frac.chars().all(|c| c.is_ascii_digit()) {
+            return Err(ParseRationalError);
+        }
+        // "-.5" and ".5" leave an integer part of just a sign or nothing
+        let num = match int {
+            "" | "-" | "+" => parse_integer(&format!("{int}0{frac}"))?,
+            _ => parse_integer(&format!("{int}{frac}"))?,
+        };
+        let den = BigInt::from_u64(10).pow(BigInt::from_u64(frac.len() as u64));
+        Ok(BigRational::new(&num, &den))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BigRational;
+    use crate::mparith::{build_bigint, Abs, BigInt, Pow};
+
+    fn i(n: i64) -> BigInt {
+        BigInt::from_i64(n)
+    }
+
+    fn r(p: i64, q: i64) -> BigRational {
+        BigRational::new(&i(p), &i(q))
+    }
+
+    #[test]
+    fn normalization_test() {
+        let x = r(6, -4);
+        assert_eq!((&i(-3), &i(2)), (x.numer(), x.denom()));
+        assert_eq!(r(0, 1), r(0, -7));
+        assert_eq!(i(1), *r(0, -7).denom());
+        assert!(r(10, 5).is_integer());
+        assert_eq!(r(-2, 3), r(3, -2).recip());
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let (x, y) = (r(1, 6), r(-3, 4));
+        assert_eq!(r(-7, 12), &x + &y);
+        assert_eq!(r(11, 12), &x - &y);
+        assert_eq!(r(-1, 8), &x * &y);
+        assert_eq!(r(-2, 9), &x / &y);
+        assert_eq!(r(3, 4), (&y).abs());
+        assert_eq!(r(3, 4), -y.clone());
+        assert_eq!(r(0, 1), &x * r(0, 5));
+        assert_eq!(r(0, 1), &x - &x);
+        // the remainder takes the sign of the divisor
+        assert_eq!(r(1, 6), r(13, 6) % r(1, 1));
+        assert_eq!(r(5, 6), r(-7, 6) % r(1, 1));
+        assert_eq!(r(-7, 12), r(1, 6) % y.clone());
+        // 1/2 + 1/3 + ... + 1/10
+        let mut h = r(0, 1);
+        for n in 1..=10 {
+            h = h + r(1, n);
+        }
+        assert_eq!(r(7381, 2520), h);
+
+        assert!(r(1, 3) < r(1, 2) && r(-1, 2) < r(-1, 3));
+        assert!(r(-1, 2) < r(0, 1) && r(2, 3) == r(4, 6));
+        assert_eq!(Some(&r(5, 3)), [r(1, 2), r(5, 3), r(-8, 1)].iter().max());
+    }
+
+    #[test]
+    fn pow_test() {
+        assert_eq!(r(8, 27), r(2, 3).pow(&i(3)));
+        assert_eq!(r(27, 8), r(2, 3).pow(&i(-3)));
+        assert_eq!(r(-27, 8), r(-2, 3).pow(&i(-3)));
+        assert_eq!(r(1, 1), r(-2, 3).pow(&i(0)));
+        assert_eq!(r(1, 1024), (&r(2, 1)).pow(i(-10)));
+    }
+
+    #[test]
+    fn rounding_test() {
+        // (value, floor, ceil, round)
+        let cases = [
+            (r(7, 2), 3, 4, 4),
+            (r(-7, 2), -4, -3, -4),
+            (r(5, 3), 1, 2, 2),
+            (r(-5, 3), -2, -1, -2),
+            (r(4, 3), 1, 2, 1),
+            (r(-4, 3), -2, -1, -1),
+            (r(-1, 2), -1, 0, -1),
+            (r(3, 1), 3, 3, 3),
+            (r(0, 1), 0, 0, 0),
+        ];
+        for (x, floor, ceil, round) in cases {
+            assert_eq!(i(floor), x.floor());
+            assert_eq!(i(ceil), x.ceil());
+            assert_eq!(i(round), x.round());
+        }
+    }
+
+    #[test]
+    fn f64_test() {
+        assert_eq!(
+            BigRational::new(
+                &build_bigint("3602879701896397"),
+                &build_bigint("36028797018963968")
+            ),
+            BigRational::from_f64(0.1).unwrap()
+        );
+        assert_eq!(Some(r(-3, 8)), BigRational::from_f64(-0.375));
+        assert_eq!(Some(r(0, 1)), BigRational::from_f64(-0.0));
+        let big = BigRational::from_f64(1e300).unwrap();
+        assert!(big.is_integer());
+        assert_eq!(format!("{:.0}", 1e300), big.to_string());
+        let tiny = BigRational::from_f64(f64::from_bits(1)).unwrap();
+        assert_eq!(BigInt::from_u64(2).pow(i(1074)), *tiny.denom());
+        assert_eq!(None, BigRational::from_f64(f64::NAN));
+        assert_eq!(None, BigRational::from_f64(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn parse_test() {
+        let cases = [
+            ("3/4", r(3, 4)),
+            ("-6/8", r(-3, 4)),
+            ("6/-8", r(-3, 4)),
+            ("+12", r(12, 1)),
+            ("1.25", r(5, 4)),
+            ("-0.125", r(-1, 8)),
+            ("-.5", r(-1, 2)),
+            ("007.50", r(15, 2)),
+        ];
+        for (s, x) in cases {
+            assert_eq!(Ok(x), s.parse::<BigRational>());
+        }
+        for s in [
+            "", "1/0", "1/", "/2", "1.", "1.2.3", "1/2.5", "a", "--1", "1e3",
+        ] {
+            assert!(s.parse::<BigRational>().is_err(), "{s}");
+        }
+        assert_eq!("-3/4", r(6, -8).to_string());
+        assert_eq!("5", r(10, 2).to_string());
+        let x: BigRational = "123456789012345678901234567890/987654321".parse().unwrap();
+        assert_eq!(x, x.to_string().parse().unwrap());
+    }
+}
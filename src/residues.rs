@@ -0,0 +1,433 @@
+use crate::factor::Factorization;
+use crate::gcd::mod_inverse;
+use crate::modular::pow_mod;
+use crate::mparith::{BigInt, Pow};
+
+/// The Jacobi symbol (a/n) for an odd positive n.
+pub fn jacobi(a: &BigInt, n: &BigInt) -> i32 {
+    if n.sgn != 1 || !n.is_odd() {
+        panic!("The Jacobi symbol needs an odd positive modulus");
+    }
+    let mut a = a % n;
+    let mut n = n.clone();
+    let mut t = 1;
+    while a.sgn != 0 {
+        let z = a.trailing_zeros();
+        a = a >> BigInt::from_u64(z as u64);
+        let n_mod_8 = n.mag[0] & 7;
+        if z % 2 == 1 && (n_mod_8 == 3 || n_mod_8 == 5) {
+            t = -t;
+        }
+        std::mem::swap(&mut a, &mut n);
+        if a.mag[0] & 3 == 3 && n.mag[0] & 3 == 3 {
+            t = -t;
+        }
+        a = a % &n;
+    }
+    if n == BigInt::from_u64(1) {
+        t
+    } else {
+        0
+    }
+}
+
+/// The Legendre symbol (a/p) for an odd prime p: 1 if a is a nonzero square modulo p, -1 if
+/// it is not a square and 0 if p divides a.
+///
+/// The primality of p is not checked; for composite p this is the Jacobi symbol.
+pub fn legendre(a: &BigInt, p: &BigInt) -> i32 {
+    jacobi(a, p)
+}
+
+/// The Kronecker symbol (a/n), which extends the Jacobi symbol to every integer n.
+pub fn kronecker(a: &BigInt, n: &BigInt) -> i32 {
+    if n.sgn == 0 {
+        let one = BigInt::from_u64(1);
+        return if *a == one || *a == -one { 1 } else { 0 };
+    }
+    let mut t = 1;
+    if n.sgn == -1 && a.sgn == -1 {
+        t = -t;
+    }
+    let z = n.trailing_zeros();
+    if z > 0 {
+        if !a.is_odd() {
+            return 0;
+        }
+        // (a/2) is 1 for a = +-1 mod 8 and -1 for a = +-3 mod 8
+        let a_mod_8 = a.rem_small(8);
+        let a_mod_8 = if a.sgn == -1 {
+            (8 - a_mod_8) % 8
+        } else {
+            a_mod_8
+        };
+        if z % 2 == 1 && (a_mod_8 == 3 || a_mod_8 == 5) {
+            t = -t;
+        }
+    }
+    let mut odd = n >> BigInt::from_u64(z as u64);
+    odd.sgn = 1;
+    t * jacobi(a, &odd)
+}
+
+/// Returns a square root of a modulo the prime p, or None if a is not a square modulo p.
+///
+/// The root returned is the smaller of the two, r <= p / 2. Primes with p = 3 (mod 4) use
+/// the exponent (p + 1) / 4 directly; otherwise Tonelli-Shanks is used, except when the
+/// 2-adic valuation s of p - 1 is so large that its O(s^2) multiplications lose to the
+/// O(log p) of Cipolla's method. The primality of p is not checked.
+pub fn sqrt_mod_prime(a: &BigInt, p: &BigInt) -> Option<BigInt> {
+    if p.sgn != 1 {
+        panic!("Modular arithmetic must be done with positive integers");
+    }
+    let a = a % p;
+    if a.sgn == 0 {
+        return Some(a);
+    }
+    if *p == BigInt::from_u64(2) {
+        return Some(a);
+    }
+    if legendre(&a, p) != 1 {
+        return None;
+    }
+    let s = (p - BigInt::from_u64(1)).trailing_zeros();
+    let r = if s == 1 {
+        pow_mod(&a, &((p + BigInt::from_u64(1)) >> BigInt::from_u64(2)), p)
+    } else if s * (s - 1) > 8 * p.bits() + 20 {
+        cipolla(&a, p)
+    } else {
+        tonelli_shanks(&a, p)
+    };
+    let other = p - &r;
+    Some(if other < r { other } else { r })
+}
+
+// square root of a quadratic residue a modulo an odd prime p
+fn tonelli_shanks(a: &BigInt, p: &BigInt) -> BigInt {
+    let one = BigInt::from_u64(1);
+    let p_minus_one = p - &one;
+    let s = p_minus_one.trailing_zeros();
+    let q = &p_minus_one >> BigInt::from_u64(s as u64);
+
+    let mut z = BigInt::from_u64(2);
+    while legendre(&z, p) != -1 {
+        z = z + &one;
+    }
+    let mut m = s;
+    let mut c = pow_mod(&z, &q, p);
+    let mut t = pow_mod(a, &q, p);
+    let mut r = pow_mod(a, &((&q + &one) >> BigInt::from_u64(1)), p);
+    // invariant: r^2 = a t and t has order dividing 2^(m - 1)
+    while t != one {
+        let mut i = 0;
+        let mut tt = t.clone();
+        while tt != one {
+            tt = (&tt * &tt) % p;
+            i += 1;
+        }
+        let mut b = c;
+        for _ in 0..(m - i - 1) {
+            b = (&b * &b) % p;
+        }
+        m = i;
+        c = (&b * &b) % p;
+        t = (t * &c) % p;
+        r = (r * &b) % p;
+    }
+    r
+}
+
+// square root of a quadratic residue a modulo an odd prime p: with w = t^2 - a a non-residue,
+// (t + sqrt(w))^((p + 1) / 2) lies in GF(p) and squares to a
+fn cipolla(a: &BigInt, p: &BigInt) -> BigInt {
+    let one = BigInt::from_u64(1);
+    let mut t = BigInt::from_u64(1);
+    let mut w = (&t * &t - a) % p;
+    while legendre(&w, p) != -1 {
+        t = t + &one;
+        w = (&t * &t - a) % p;
+    }
+    // (x0 + x1 sqrt(w)) * (y0 + y1 sqrt(w))
+    let mul = |x: &(BigInt, BigInt), y: &(BigInt, BigInt)| {
+        (
+            (&x.0 * &y.0 + &x.1 * &y.1 % p * &w) % p,
+            (&x.0 * &y.1 + &x.1 * &y.0) % p,
+        )
+    };
+    let e = (p + &one) >> BigInt::from_u64(1);
+    let mut result = (one.clone(), BigInt::from_u64(0));
+    let mut base = (t, one);
+    for i in 0..e.bits() {
+        if (&e >> BigInt::from_u64(i as u64)).is_odd() {
+            result = mul(&result, &base);
+        }
+        base = mul(&base, &base);
+    }
+    result.0
+}
+
+/// Lifts a root r of x^2 = a (mod p) to the root of x^2 = a (mod p^k) congruent to r, for an
+/// odd prime p not dividing a, with Newton's iteration doubling the precision at each step.
+pub fn hensel_lift(r: &BigInt, a: &BigInt, p: &BigInt, k: u32) -> BigInt {
+    let mut r = r % p;
+    let mut precision = 1;
+    while precision < k {
+        precision = (2 * precision).min(k);
+        let m = p.pow(&BigInt::from_u64(precision as u64));
+        let inverse = mod_inverse(&(&r + &r), &m).expect("p must not divide a");
+        r = (&r - (&r * &r - a) * inverse) % &m;
+    }
+    r
+}
+
+/// Returns every x in [0, p^k) with x^2 = a (mod p^k), in increasing order, for a prime p
+/// and k >= 1.
+///
+/// a does not need to be coprime to p: when p^v exactly divides a with v < k, the roots are
+/// p^(v/2) times the roots of a / p^v modulo p^(k - v), each taken with every lift modulo
+/// p^(k - v/2), and there are none for odd v.
+pub fn sqrt_mod_prime_power(a: &BigInt, p: &BigInt, k: u32) -> Vec<BigInt> {
+    let modulus = p.pow(&BigInt::from_u64(k as u64));
+    let a = a % &modulus;
+    let mut v = 0;
+    let mut u = a.clone();
+    while u.sgn != 0 && (&u % p).sgn == 0 {
+        u = u / p;
+        v += 1;
+    }
+    if u.sgn == 0 {
+        v = k;
+    }
+    if v % 2 == 1 && v < k {
+        return Vec::new();
+    }
+
+    // roots y of y^2 = u modulo p^(k - v), then x = p^(v/2) (y + t p^(k - v))
+    let half = p.pow(&BigInt::from_u64((v / 2) as u64));
+    let (unit_roots, unit_modulus) = if v >= k {
+        // x = 0 modulo p^ceil(k/2)
+        (
+            vec![BigInt::from_u64(0)],
+            p.pow(&BigInt::from_u64((k - k / 2 - v / 2) as u64)),
+        )
+    } else {
+        (
+            sqrt_unit_prime_power(&u, p, k - v),
+            p.pow(&BigInt::from_u64((k - v) as u64)),
+        )
+    };
+    let step = &half * &unit_modulus;
+    let mut roots = Vec::new();
+    for y in unit_roots {
+        let mut x = &half * y;
+        while x < modulus {
+            roots.push(x.clone());
+            x = x + &step;
+        }
+    }
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+// roots of x^2 = u modulo p^j for u coprime to the prime p and j >= 1
+fn sqrt_unit_prime_power(u: &BigInt, p: &BigInt, j: u32) -> Vec<BigInt> {
+    let modulus = p.pow(&BigInt::from_u64(j as u64));
+    if *p != BigInt::from_u64(2) {
+        return match sqrt_mod_prime(u, p) {
+            Some(r) => {
+                let r = hensel_lift(&r, u, p, j);
+                vec![&modulus - &r, r]
+            }
+            None => Vec::new(),
+        };
+    }
+    let u_mod_8 = u.rem_small(8);
+    match j {
+        1 => vec![BigInt::from_u64(1)],
+        2 if u_mod_8 % 4 == 1 => vec![BigInt::from_u64(1), BigInt::from_u64(3)],
+        _ if j >= 3 && u_mod_8 == 1 => {
+            // r^2 = u (mod 2^i) implies r or r + 2^(i - 1) is a root modulo 2^(i + 1)
+            let mut r = BigInt::from_u64(1);
+            for i in 3..j {
+                let next = BigInt::from_u64(1) << BigInt::from_u64((i + 1) as u64);
+                if ((&r * &r - u) % &next).sgn != 0 {
+                    r = r + (BigInt::from_u64(1) << BigInt::from_u64((i - 1) as u64));
+                }
+            }
+            let half = &modulus >> BigInt::from_u64(1);
+            vec![
+                r.clone(),
+                &modulus - &r,
+                (&r + &half) % &modulus,
+                (&modulus - &r + &half) % &modulus,
+            ]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Returns every x in [0, m) with x^2 = a (mod m), in increasing order, where the modulus m
+/// is given by its factorization (its sign is ignored).
+///
+/// The roots modulo each prime power come from `sqrt_mod_prime_power` and are combined with
+/// the Chinese remainder theorem, so there can be exponentially many of them.
+pub fn sqrt_mod(a: &BigInt, modulus: &Factorization) -> Vec<BigInt> {
+    let mut roots = vec![BigInt::from_u64(0)];
+    let mut m = BigInt::from_u64(1);
+    for (p, k) in &modulus.factors {
+        let pk = p.pow(&BigInt::from_u64(*k as u64));
+        let local = sqrt_mod_prime_power(a, p, *k);
+        // x = r + m ((s - r) m^-1 mod p^k) is r modulo m and s modulo p^k
+        let m_inverse = mod_inverse(&m, &pk).unwrap();
+        let mut combined = Vec::with_capacity(roots.len() * local.len());
+        for r in &roots {
+            for s in &local {
+                combined.push(r + &m * ((s - r) * &m_inverse % &pk));
+            }
+        }
+        roots = combined;
+        m = m * pk;
+    }
+    roots.sort();
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::factor::factor;
+    use crate::mparith::{build_bigint, Abs, BigInt};
+    use crate::primes::next_prime;
+    use std::fs::File;
+    use std::io::{self, BufRead};
+    use std::path::Path;
+    const A_DEC: usize = 1;
+    const B_DEC: usize = 3;
+
+    fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(filename)?;
+        Ok(io::BufReader::new(file).lines())
+    }
+
+    #[test]
+    fn jacobi_test() {
+        assert_eq!(1, super::jacobi(&build_bigint("2"), &build_bigint("7")));
+        assert_eq!(-1, super::jacobi(&build_bigint("3"), &build_bigint("7")));
+        assert_eq!(0, super::jacobi(&build_bigint("21"), &build_bigint("7")));
+        assert_eq!(-1, super::jacobi(&build_bigint("-1"), &build_bigint("7")));
+        assert_eq!(
+            -1,
+            super::jacobi(&build_bigint("1001"), &build_bigint("9907"))
+        );
+        assert_eq!(1, super::jacobi(&build_bigint("19"), &build_bigint("45")));
+        assert_eq!(-1, super::jacobi(&build_bigint("8"), &build_bigint("21")));
+        assert_eq!(1, super::legendre(&build_bigint("5"), &build_bigint("11")));
+    }
+
+    #[test]
+    fn kronecker_test() {
+        let cases = [
+            ("5", "0", 0),
+            ("-1", "0", 1),
+            ("3", "2", -1),
+            ("7", "2", 1),
+            ("-5", "2", -1),
+            ("4", "6", 0),
+            ("5", "12", -1),
+            ("-3", "-4", -1),
+            ("3", "-4", 1),
+            ("-1", "-1", -1),
+            ("2", "-7", 1),
+            ("-7", "16", 1),
+        ];
+        for (a, n, expected) in cases {
+            assert_eq!(
+                expected,
+                super::kronecker(&build_bigint(a), &build_bigint(n)),
+                "({a}/{n})"
+            );
+        }
+    }
+
+    #[test]
+    fn sqrt_mod_prime_test() {
+        // 3 * 2^30 + 1 and 15 * 2^27 + 1 go through Cipolla's method
+        for p in ["13", "17", "1000003", "3221225473", "2013265921"] {
+            let p = build_bigint(p);
+            for a in 0..50 {
+                let a = BigInt::from_u64(a);
+                match super::sqrt_mod_prime(&a, &p) {
+                    Some(r) => {
+                        assert_eq!(&a % &p, (&r * &r) % &p);
+                        assert!(&r + &r <= p);
+                    }
+                    None => assert_eq!(-1, super::legendre(&a, &p)),
+                }
+            }
+        }
+        let p = build_bigint("2013265921");
+        let a = build_bigint("123456789") * build_bigint("123456789") % &p;
+        assert_eq!(super::tonelli_shanks(&a, &p), super::cipolla(&a, &p) % &p);
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.take(20).map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let p = next_prime(&build_bigint(v[A_DEC]).abs());
+                let x = build_bigint(v[B_DEC]);
+                let a = (&x * &x) % &p;
+                let r = super::sqrt_mod_prime(&a, &p).unwrap();
+                let x = &x % &p;
+                assert!(r == x || r == &p - &x);
+            }
+        }
+    }
+
+    #[test]
+    fn sqrt_mod_prime_power_test() {
+        for (p, k) in [
+            (2_u64, 1_u32),
+            (2, 2),
+            (2, 3),
+            (2, 6),
+            (3, 4),
+            (5, 3),
+            (7, 2),
+        ] {
+            let m = p.pow(k);
+            for a in 0..m {
+                let expected: Vec<BigInt> = (0..m)
+                    .filter(|x| x * x % m == a)
+                    .map(BigInt::from_u64)
+                    .collect();
+                let roots =
+                    super::sqrt_mod_prime_power(&BigInt::from_u64(a), &BigInt::from_u64(p), k);
+                assert_eq!(expected, roots, "x^2 = {a} mod {p}^{k}");
+            }
+        }
+        let p = build_bigint("1000003");
+        let r = super::hensel_lift(&build_bigint("3"), &build_bigint("9"), &p, 5);
+        assert_eq!(build_bigint("3"), r);
+    }
+
+    #[test]
+    fn sqrt_mod_test() {
+        let m = 2520;
+        let f = factor(&BigInt::from_u64(m));
+        for a in [0, 1, 4, 9, 36, 100, 121, 1000, 2519] {
+            let expected: Vec<BigInt> = (0..m)
+                .filter(|x| x * x % m == a)
+                .map(BigInt::from_u64)
+                .collect();
+            assert_eq!(expected, super::sqrt_mod(&BigInt::from_u64(a), &f));
+        }
+        let p = build_bigint("1000000007");
+        let q = build_bigint("998244353");
+        let roots = super::sqrt_mod(&build_bigint("2"), &factor(&(&p * &q)));
+        assert_eq!(4, roots.len());
+        for r in roots {
+            assert_eq!(build_bigint("2"), (&r * &r) % (&p * &q));
+        }
+    }
+}
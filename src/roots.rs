@@ -0,0 +1,264 @@
+use crate::mparith::{abs, isqrt, karatsuba_sqrt, pow, BigInt};
+
+/// Returns (s, r) where s = isqrt(n) and r = n - s^2.
+pub fn sqrt_rem(n: &BigInt) -> (BigInt, BigInt) {
+    if n.sgn == 1 {
+        return karatsuba_sqrt(n);
+    }
+    let s = isqrt(n);
+    let r = n - &s * &s;
+    (s, r)
+}
+
+/// Returns the floor of the k-th root of n.
+///
+/// Negative numbers only have odd roots, and the result is rounded towards negative
+/// infinity, so nth_root(-9, 3) is -3.
+pub fn nth_root(n: &BigInt, k: u32) -> BigInt {
+    if k == 0 {
+        panic!("Do not take the zeroth root of a number");
+    }
+    if n.sgn == -1 {
+        if k.is_multiple_of(2) {
+            panic!("Do not take an even root of a negative number");
+        }
+        let n_abs = abs(n);
+        let r = nth_root(&n_abs, k);
+        if pow(&r, &BigInt::from_u64(k as u64)) == n_abs {
+            return -r;
+        }
+        return -(r + BigInt::from_u64(1));
+    }
+    if k == 1 || n.sgn == 0 {
+        return n.clone();
+    }
+    if k == 2 {
+        return isqrt(n);
+    }
+    // n < 2^bits <= 2^k
+    if k as usize >= n.bits() {
+        return BigInt::from_u64(1);
+    }
+
+    // Newton's method decreases monotonically towards the root from any starting point
+    // above it, and 2^ceil(bits / k) is always above it
+    let k_bigint = BigInt::from_u64(k as u64);
+    let k_minus_one = BigInt::from_u64(k as u64 - 1);
+    let mut x = BigInt::from_u64(1) << BigInt::from_u64(n.bits().div_ceil(k as usize) as u64);
+    loop {
+        let y = (&k_minus_one * &x + n / pow(&x, &k_minus_one)) / &k_bigint;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+const fn square_residues<const M: usize>() -> [bool; M] {
+    let mut res = [false; M];
+    let mut i = 0;
+    while i < M {
+        res[(i * i) % M] = true;
+        i += 1;
+    }
+    res
+}
+
+// squares modulo 64, 63, 65 and 11 rule out most non-squares before taking a root
+const SQUARES_64: [bool; 64] = square_residues();
+const SQUARES_63: [bool; 63] = square_residues();
+const SQUARES_65: [bool; 65] = square_residues();
+const SQUARES_11: [bool; 11] = square_residues();
+
+/// Returns whether n is the square of an integer.
+pub fn is_perfect_square(n: &BigInt) -> bool {
+    if n.sgn == -1 {
+        return false;
+    }
+    if n.sgn == 0 {
+        return true;
+    }
+    if !SQUARES_64[(n.mag[0] & 63) as usize] {
+        return false;
+    }
+    let r = n.rem_small(63 * 65 * 11) as usize;
+    if !SQUARES_63[r % 63] || !SQUARES_65[r % 65] || !SQUARES_11[r % 11] {
+        return false;
+    }
+    sqrt_rem(n).1.sgn == 0
+}
+
+pub(crate) fn small_primes(limit: usize) -> Vec<u32> {
+    let mut sieve = vec![true; limit + 1];
+    let mut primes = Vec::new();
+    for i in 2..=limit {
+        if sieve[i] {
+            primes.push(i as u32);
+            for j in ((i * i)..=limit).step_by(i) {
+                sieve[j] = false;
+            }
+        }
+    }
+    primes
+}
+
+/// Returns (b, k) with n = b^k and k >= 2 as large as possible, or None when n is not
+/// a perfect power. For negative n only odd exponents are considered, and 0, 1 and -1
+/// are never reported since they have no largest exponent.
+pub fn perfect_power(n: &BigInt) -> Option<(BigInt, u32)> {
+    let n_abs = abs(n);
+    if n_abs <= BigInt::from_u64(1) {
+        return None;
+    }
+
+    let (b, k) = perfect_power_abs(&n_abs);
+    if n.sgn == 1 {
+        return if k > 1 { Some((b, k)) } else { None };
+    }
+
+    let odd = k >> k.trailing_zeros();
+    if odd == 1 {
+        return None;
+    }
+    Some((-pow(&b, &BigInt::from_u64((k / odd) as u64)), odd))
+}
+
+// returns (b, k) with n = b^k and k maximal, for n >= 2
+fn perfect_power_abs(n: &BigInt) -> (BigInt, u32) {
+    // b^p with b >= 2 has more than p bits, so only p < bits can work
+    for p in small_primes(n.bits() - 1) {
+        let r = nth_root(n, p);
+        if pow(&r, &BigInt::from_u64(p as u64)) == *n {
+            let (b, k) = perfect_power_abs(&r);
+            return (b, k * p);
+        }
+    }
+    (n.clone(), 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mparith::{build_bigint, Abs, Pow};
+    use std::fs::File;
+    use std::io::{self, BufRead};
+    use std::path::Path;
+    const A_DEC: usize = 1;
+    const A_ABS_ISQRT_DEC: usize = 38;
+
+    fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(filename)?;
+        Ok(io::BufReader::new(file).lines())
+    }
+
+    #[test]
+    fn sqrt_rem_test() {
+        let (s, r) = super::sqrt_rem(&build_bigint("0"));
+        assert_eq!((build_bigint("0"), build_bigint("0")), (s, r));
+
+        // consecutive powers of 3 alternate between odd and even bit lengths
+        for e in 400..412 {
+            let a = build_bigint("3").pow(build_bigint(&e.to_string())) - build_bigint("1");
+            let (s, r) = super::sqrt_rem(&a);
+            assert_eq!(a, &s * &s + &r);
+            assert!(r.sgn >= 0 && r <= &s + &s);
+        }
+
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]).abs();
+                let (s, r) = super::sqrt_rem(&a);
+                assert_eq!(v[A_ABS_ISQRT_DEC], s.to_string());
+                assert_eq!(a, &s * &s + r);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn nth_root_even_neg_test() {
+        let _ = super::nth_root(&build_bigint("-8"), 4);
+    }
+
+    #[test]
+    fn nth_root_test() {
+        assert_eq!(build_bigint("2"), super::nth_root(&build_bigint("8"), 3));
+        assert_eq!(build_bigint("1"), super::nth_root(&build_bigint("7"), 3));
+        assert_eq!(build_bigint("-2"), super::nth_root(&build_bigint("-8"), 3));
+        assert_eq!(build_bigint("-3"), super::nth_root(&build_bigint("-9"), 3));
+        assert_eq!(build_bigint("-5"), super::nth_root(&build_bigint("-5"), 1));
+        assert_eq!(build_bigint("0"), super::nth_root(&build_bigint("0"), 7));
+        assert_eq!(build_bigint("1"), super::nth_root(&build_bigint("1023"), 10));
+        assert_eq!(build_bigint("2"), super::nth_root(&build_bigint("1024"), 10));
+        assert_eq!(build_bigint("-2"), super::nth_root(&build_bigint("-1000"), u32::MAX));
+
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                for k in [3, 5, 8] {
+                    if k % 2 == 0 && a.sgn == -1 {
+                        continue;
+                    }
+                    let kk = build_bigint(&k.to_string());
+                    let r = super::nth_root(&a, k);
+                    assert!((&r).pow(&kk) <= a);
+                    assert!((r + build_bigint("1")).pow(&kk) > a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn is_perfect_square_test() {
+        assert!(super::is_perfect_square(&build_bigint("0")));
+        assert!(super::is_perfect_square(&build_bigint("1")));
+        assert!(!super::is_perfect_square(&build_bigint("-4")));
+        assert!(!super::is_perfect_square(&build_bigint("2")));
+
+        if let Ok(lines) = read_lines("./test_inputs.txt") {
+            for testcase in lines.map_while(Result::ok) {
+                let v: Vec<&str> = testcase.split(',').collect();
+                let a = build_bigint(v[A_DEC]);
+                let sq = &a * &a;
+                assert!(super::is_perfect_square(&sq));
+                if a.abs() > build_bigint("1") {
+                    assert!(!super::is_perfect_square(&(&sq + build_bigint("1"))));
+                    assert!(!super::is_perfect_square(&(sq - build_bigint("1"))));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn perfect_power_test() {
+        assert_eq!(None, super::perfect_power(&build_bigint("1")));
+        assert_eq!(None, super::perfect_power(&build_bigint("-1")));
+        assert_eq!(None, super::perfect_power(&build_bigint("12")));
+        assert_eq!(None, super::perfect_power(&build_bigint("-16")));
+        assert_eq!(
+            Some((build_bigint("2"), 6)),
+            super::perfect_power(&build_bigint("64"))
+        );
+        assert_eq!(
+            Some((build_bigint("-4"), 3)),
+            super::perfect_power(&build_bigint("-64"))
+        );
+        assert_eq!(
+            Some((build_bigint("6"), 36)),
+            super::perfect_power(&build_bigint("6").pow(build_bigint("36")))
+        );
+        assert_eq!(
+            Some((build_bigint("-15"), 7)),
+            super::perfect_power(&build_bigint("-15").pow(build_bigint("7")))
+        );
+        let b = build_bigint("1267650600228229401496703205653");
+        assert_eq!(
+            Some((b.clone(), 10)),
+            super::perfect_power(&(&b).pow(build_bigint("10")))
+        );
+    }
+}
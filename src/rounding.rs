@@ -0,0 +1,127 @@
+use crate::mparith::{build_bigint, divmod, BigInt, Pow};
+use std::cmp::Ordering;
+
+/// How a result that is not representable is rounded, in the IEEE 754 sense for `BigFloat`
+/// and to the last decimal place for `BigDecimal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoundingMode {
+    /// To the nearest value, and to the one with an even last digit on ties.
+    HalfEven,
+    /// To the nearest value, and away from zero on ties.
+    HalfUp,
+    /// Toward zero.
+    Down,
+    /// Away from zero.
+    Up,
+    /// Toward positive infinity.
+    Ceiling,
+    /// Toward negative infinity.
+    Floor,
+}
+
+/// n / d rounded to an integer, for d > 0.
+pub(crate) fn div_round(n: &BigInt, d: &BigInt, mode: RoundingMode) -> BigInt {
+    let (q, r) = divmod(n, d);
+    if r.sgn == 0 {
+        return q;
+    }
+    // n / d lies strictly between q and q + 1, and is negative exactly when q < 0
+    let up = match mode {
+        RoundingMode::Floor => false,
+        RoundingMode::Ceiling => true,
+        RoundingMode::Down => q.sgn == -1,
+        RoundingMode::Up => q.sgn != -1,
+        RoundingMode::HalfEven | RoundingMode::HalfUp => match (&r + &r).cmp(d) {
+            Ordering::Less => false,
+            Ordering::Greater => true,
+            Ordering::Equal if mode == RoundingMode::HalfUp => q.sgn != -1,
+            Ordering::Equal => q.is_odd(),
+        },
+    };
+    if up {
+        q + BigInt::from_u64(1)
+    } else {
+        q
+    }
+}
+
+/// Splits a decimal string such as "-12.5e-3" into the integer -125 and the power of ten -4.
+/// The digits may be followed by a fractional part and an exponent, each optional.
+pub(crate) fn parse_decimal(s: &str) -> Option<(BigInt, i64)> {
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], s[i + 1..].parse::<i64>().ok()?),
+        None => (s, 0),
+    };
+    let (sign, digits) = match mantissa.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", mantissa.strip_prefix('+').unwrap_or(mantissa)),
+    };
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    if int.len() + frac.len() == 0 || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let n = build_bigint(&format!("{sign}0{int}{frac}"));
+    Some((n, exponent.checked_sub(frac.len() as i64)?))
+}
+
+// 10^n
+pub(crate) fn pow10(n: u64) -> BigInt {
+    BigInt::from_u64(10).pow(BigInt::from_u64(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoundingMode::*;
+    use crate::mparith::BigInt;
+
+    fn i(n: i64) -> BigInt {
+        BigInt::from_i64(n)
+    }
+
+    #[test]
+    fn div_round_test() {
+        // n / 2 for n = -7, -5, -3, -1, 1, 3, 5, 7, with ties
+        let halves = [
+            (HalfEven, [-4, -2, -2, 0, 0, 2, 2, 4]),
+            (HalfUp, [-4, -3, -2, -1, 1, 2, 3, 4]),
+            (Down, [-3, -2, -1, 0, 0, 1, 2, 3]),
+            (Up, [-4, -3, -2, -1, 1, 2, 3, 4]),
+            (Ceiling, [-3, -2, -1, 0, 1, 2, 3, 4]),
+            (Floor, [-4, -3, -2, -1, 0, 1, 2, 3]),
+        ];
+        for (mode, expected) in halves {
+            for (n, q) in [-7, -5, -3, -1, 1, 3, 5, 7].into_iter().zip(expected) {
+                assert_eq!(i(q), super::div_round(&i(n), &i(2), mode), "{n} {mode:?}");
+            }
+        }
+        // n / 3 away from the ties, and exact quotients
+        let thirds = [
+            (HalfEven, [-2, -1, 1, 2]),
+            (HalfUp, [-2, -1, 1, 2]),
+            (Down, [-1, 0, 0, 1]),
+        ];
+        for (mode, expected) in thirds {
+            for (n, q) in [-5, -2, 2, 5].into_iter().zip(expected) {
+                assert_eq!(i(q), super::div_round(&i(n), &i(3), mode));
+            }
+            assert_eq!(i(-4), super::div_round(&i(-12), &i(3), mode));
+        }
+    }
+
+    #[test]
+    fn parse_decimal_test() {
+        let cases = [
+            ("-12.5e-3", -125, -4),
+            ("+7", 7, 0),
+            ("-.5", -5, -1),
+            ("3.", 3, 0),
+            ("1.20E+3", 120, 1),
+        ];
+        for (s, n, k) in cases {
+            assert_eq!(Some((i(n), k)), super::parse_decimal(s), "{s}");
+        }
+        for s in ["", ".", "-", "1e", "1.2.3", "1e5.0", "0x10", " 1"] {
+            assert_eq!(None, super::parse_decimal(s), "{s}");
+        }
+    }
+}
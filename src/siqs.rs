@@ -0,0 +1,643 @@
+use crate::gcd::gcd;
+use crate::modular::{inv_mod_u64, pow_mod, pow_mod_u64, sqrt_mod_u64};
+use crate::mparith::{divmod, isqrt, BigInt};
+use crate::roots::small_primes;
+use std::collections::{HashMap, HashSet};
+
+// (digits, factor base size, half-width M of the sieve interval [-M, M))
+const SIQS_PARAMS: [(u32, usize, usize); 17] = [
+    (20, 60, 4096),
+    (25, 100, 8192),
+    (30, 150, 8192),
+    (35, 250, 16384),
+    (40, 400, 16384),
+    (45, 700, 32768),
+    (50, 1000, 32768),
+    (55, 1800, 32768),
+    (60, 3000, 32768),
+    (65, 4000, 65536),
+    (70, 5500, 65536),
+    (75, 7000, 65536),
+    (80, 9000, 98304),
+    (85, 12000, 98304),
+    (90, 15000, 131072),
+    (95, 20000, 131072),
+    (100, 25000, 163840),
+];
+
+// squarefree multipliers tried by the Knuth-Schroeppel function
+const MULTIPLIERS: [u64; 24] = [
+    1, 3, 5, 7, 11, 13, 15, 17, 19, 21, 23, 29, 31, 33, 35, 37, 39, 41, 43, 47, 51, 53, 55, 57,
+];
+
+// factor base primes below this are not sieved, the threshold makes up for them
+const SMALL_PRIME_CUTOFF: u64 = 30;
+
+// partial relations may have one prime above the factor base, below this multiple of its
+// largest prime
+const LARGE_PRIME_MULTIPLIER: u64 = 64;
+
+// relations collected beyond the number of columns before looking for dependencies
+const EXTRA_RELATIONS: usize = 32;
+
+// bits subtracted from the sieve threshold for the unsieved small primes and rounding
+const THRESHOLD_SLACK: f64 = 12.0;
+
+fn is_residue(a: u64, p: u64) -> bool {
+    pow_mod_u64(a, (p - 1) / 2, p) == 1
+}
+
+fn log2(n: &BigInt) -> f64 {
+    let bits = n.bits();
+    if bits <= 60 {
+        return (n.to_u64().unwrap() as f64).log2();
+    }
+    let top = n >> BigInt::from_u64(bits as u64 - 60);
+    (top.to_u64().unwrap() as f64).log2() + (bits - 60) as f64
+}
+
+// the multiplier k maximizing the Knuth-Schroeppel function, i.e. the expected contribution
+// of small primes to kn
+fn choose_multiplier(n: &BigInt) -> u64 {
+    let primes: Vec<u64> = small_primes(1000)
+        .into_iter()
+        .skip(1)
+        .map(|p| p as u64)
+        .collect();
+    let residues: Vec<u64> = primes.iter().map(|&p| n.rem_small(p)).collect();
+    let n_mod_8 = n.rem_small(8);
+    let ln2 = 2_f64.ln();
+
+    let mut best = (f64::MIN, 1);
+    for k in MULTIPLIERS {
+        let mut score = -0.5 * (k as f64).ln();
+        score += match (n_mod_8 * k) % 8 {
+            1 => 2.0 * ln2,
+            5 => ln2,
+            _ => 0.5 * ln2,
+        };
+        for (&p, &r) in primes.iter().zip(residues.iter()) {
+            if k % p == 0 {
+                score += (p as f64).ln() / p as f64;
+            } else if is_residue(r * k % p, p) {
+                score += 2.0 * (p as f64).ln() / (p - 1) as f64;
+            }
+        }
+        if score > best.0 {
+            best = (score, k);
+        }
+    }
+    best.1
+}
+
+// a relation y^2 = (-1)^e0 * prod p_j^ej * extra^2 mod n; factors lists the column of
+// every prime factor with repetition (column 0 is the sign, column j + 1 the j-th prime)
+#[derive(Debug, Clone)]
+struct Relation {
+    y: BigInt,
+    factors: Vec<usize>,
+    extra: BigInt,
+}
+
+struct FactorBase {
+    primes: Vec<u64>,
+    sqrt: Vec<u64>, // square roots of kn modulo each prime
+    logp: Vec<u8>,
+}
+
+// returns Err(p) if a prime p divides n
+fn build_factor_base(n: &BigInt, kn: &BigInt, size: usize) -> Result<FactorBase, u64> {
+    let mut fb = FactorBase {
+        primes: vec![2],
+        sqrt: vec![1],
+        logp: vec![1],
+    };
+    let mut limit = 1024;
+    let mut start = 1;
+    loop {
+        let primes = small_primes(limit);
+        for &p in &primes[start..] {
+            let p = p as u64;
+            let r = kn.rem_small(p);
+            if r == 0 {
+                if n.rem_small(p) == 0 {
+                    return Err(p);
+                }
+                continue;
+            }
+            if is_residue(r, p) {
+                fb.primes.push(p);
+                fb.sqrt.push(sqrt_mod_u64(r, p));
+                fb.logp.push((p as f64).log2().round() as u8);
+                if fb.primes.len() == size {
+                    return Ok(fb);
+                }
+            }
+        }
+        start = primes.len();
+        limit *= 2;
+    }
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+// picks s factor base primes whose product A is close to the target sqrt(2kn) / M and that
+// have not been used together before, returning their indices, or None when the factor base
+// is too small to give a new A
+fn choose_a(
+    fb: &FactorBase,
+    log_target: f64,
+    rng: &mut XorShift,
+    used: &mut HashSet<Vec<usize>>,
+) -> Option<Vec<usize>> {
+    let f = fb.primes.len();
+    if f < 3 {
+        return None;
+    }
+    // the smallest usable index, A should not contain tiny primes
+    let lo = fb
+        .primes
+        .iter()
+        .position(|&p| p > 10)
+        .unwrap_or(1)
+        .min(f - 2);
+    let q_ideal = (fb.primes[2 * f / 3] as f64).min(4000.0).log2();
+    let s = ((log_target / q_ideal).round() as usize).max(1);
+    if f - lo < s {
+        return None;
+    }
+    let q_avg = log_target / s as f64;
+    let center = fb
+        .primes
+        .iter()
+        .position(|&p| (p as f64).log2() >= q_avg)
+        .unwrap_or(f - 1)
+        .max(lo);
+
+    let mut width = (f / 20).max(3);
+    loop {
+        let window_lo = center.saturating_sub(width).max(lo);
+        let window_hi = (center + width).min(f - 1);
+        let span = (window_hi - window_lo + 1) as u64;
+        // all but the last prime come from the window, so it has to hold s - 1 of them
+        let attempts = if span as usize >= s - 1 { 20 } else { 0 };
+        for _ in 0..attempts {
+            let mut chosen: Vec<usize> = Vec::new();
+            let mut log_a = 0.0;
+            if s == 1 {
+                chosen.push(window_lo + (rng.next() % span) as usize);
+            } else {
+                while chosen.len() < s - 1 {
+                    let idx = window_lo + (rng.next() % span) as usize;
+                    if !chosen.contains(&idx) {
+                        chosen.push(idx);
+                        log_a += (fb.primes[idx] as f64).log2();
+                    }
+                }
+                // the last prime brings A as close as possible to the target
+                let rest = log_target - log_a;
+                let last = (lo..f)
+                    .filter(|j| !chosen.contains(j))
+                    .min_by(|&a, &b| {
+                        let da = ((fb.primes[a] as f64).log2() - rest).abs();
+                        let db = ((fb.primes[b] as f64).log2() - rest).abs();
+                        da.partial_cmp(&db).unwrap()
+                    })
+                    .unwrap();
+                chosen.push(last);
+            }
+            chosen.sort();
+            if used.insert(chosen.clone()) {
+                return Some(chosen);
+            }
+        }
+        if window_lo == lo && window_hi == f - 1 {
+            return None;
+        }
+        width += 1;
+    }
+}
+
+/// Looks for a nontrivial factor of n with the self-initializing quadratic sieve.
+///
+/// n should be an odd composite that is not a perfect power, with at least 20 digits. The
+/// sieve uses a Knuth-Schroeppel multiplier k, polynomials (Ax + B)^2 - kn whose A is a
+/// product of factor base primes, switching between the 2^(s-1) values of B for each A
+/// with a Gray code, and keeps relations with a single large prime until a second relation
+/// with the same prime turns up. Dependencies between the relations are found by Gaussian
+/// elimination over GF(2) on bit-packed rows. If the factor base runs out of fresh values
+/// of A it is doubled and the sieving restarts. Returns None if every dependency gave a
+/// trivial factor even after collecting more relations, which is very unlikely.
+pub fn siqs(n: &BigInt) -> Option<BigInt> {
+    let digits = (n.bits() as f64 * 2_f64.log10()).ceil() as u32;
+    let &(_, fb_size, m) = SIQS_PARAMS
+        .iter()
+        .find(|&&(d, _, _)| d >= digits)
+        .unwrap_or(&SIQS_PARAMS[SIQS_PARAMS.len() - 1]);
+
+    let k = choose_multiplier(n);
+    let kn = n * BigInt::from_u64(k);
+    // a factor base that runs out of values of A is doubled and the sieving starts over
+    let mut fb_size = fb_size;
+    loop {
+        if let Ok(res) = siqs_with_factor_base(n, &kn, fb_size, m) {
+            return res;
+        }
+        fb_size *= 2;
+    }
+}
+
+// the sieve with a given factor base size, or Err if it ran out of values of A
+fn siqs_with_factor_base(
+    n: &BigInt,
+    kn: &BigInt,
+    fb_size: usize,
+    m: usize,
+) -> Result<Option<BigInt>, ()> {
+    let fb = match build_factor_base(n, kn, fb_size) {
+        Ok(fb) => fb,
+        Err(p) => return Ok(Some(BigInt::from_u64(p))),
+    };
+    let f = fb.primes.len();
+    let pmax = fb.primes[f - 1];
+    let large_prime_bound = pmax * LARGE_PRIME_MULTIPLIER;
+
+    // Q(x) / A is about M sqrt(kn / 2) over the interval
+    let target = isqrt(&(kn + kn)) / BigInt::from_u64(m as u64);
+    let log_target = log2(&target);
+    let threshold = (log2(kn) / 2.0 + (m as f64).log2()
+        - 0.5
+        - (large_prime_bound as f64).log2()
+        - THRESHOLD_SLACK)
+        .max(0.0) as u8;
+
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    let mut used = HashSet::new();
+    let mut relations: Vec<Relation> = Vec::new();
+    let mut partials: HashMap<u64, Relation> = HashMap::new();
+    let mut needed = f + 1 + EXTRA_RELATIONS;
+
+    loop {
+        while relations.len() < needed {
+            let a_idx = choose_a(&fb, log_target, &mut rng, &mut used).ok_or(())?;
+            sieve_a(
+                n,
+                kn,
+                &fb,
+                &a_idx,
+                m,
+                threshold,
+                large_prime_bound,
+                &mut relations,
+                &mut partials,
+            );
+        }
+        if let Some(d) = combine(n, &fb, &relations) {
+            return Ok(Some(d));
+        }
+        needed += EXTRA_RELATIONS;
+        if needed > 2 * (f + 1) + 10 * EXTRA_RELATIONS {
+            return Ok(None);
+        }
+    }
+}
+
+// sieves every polynomial belonging to the A made of the given factor base primes
+#[allow(clippy::too_many_arguments)]
+fn sieve_a(
+    n: &BigInt,
+    kn: &BigInt,
+    fb: &FactorBase,
+    a_idx: &[usize],
+    m: usize,
+    threshold: u8,
+    large_prime_bound: u64,
+    relations: &mut Vec<Relation>,
+    partials: &mut HashMap<u64, Relation>,
+) {
+    let f = fb.primes.len();
+    let s = a_idx.len();
+    let mut a = BigInt::from_u64(1);
+    for &j in a_idx {
+        a = a * BigInt::from_u64(fb.primes[j]);
+    }
+
+    // B_l = (A / q_l) * (t_l * (A / q_l)^-1 mod q_l), so B = sum B_l satisfies B^2 = kn mod A
+    let mut b_parts = Vec::with_capacity(s);
+    for &j in a_idx {
+        let q = fb.primes[j];
+        let (a_over_q, _) = a.divmod_small(q);
+        let mut gamma =
+            fb.sqrt[j] as u128 * inv_mod_u64(a_over_q.rem_small(q), q) as u128 % q as u128;
+        if gamma > (q / 2) as u128 {
+            gamma = q as u128 - gamma;
+        }
+        b_parts.push(a_over_q * BigInt::from_u64(gamma as u64));
+    }
+    let mut b = b_parts.iter().fold(BigInt::from_u64(0), |acc, x| acc + x);
+
+    // roots of Q(x) / A modulo every sieved prime, and the amounts they move by when
+    // B changes by 2 B_l
+    let mut in_a = vec![false; f];
+    for &j in a_idx {
+        in_a[j] = true;
+    }
+    let sieved: Vec<bool> = (0..f)
+        .map(|j| !in_a[j] && fb.primes[j] >= SMALL_PRIME_CUTOFF)
+        .collect();
+    let mut r1 = vec![0_u64; f];
+    let mut r2 = vec![0_u64; f];
+    let mut deltas = vec![vec![0_u64; f]; s];
+    for j in 1..f {
+        if in_a[j] {
+            continue;
+        }
+        let p = fb.primes[j];
+        let a_inv = inv_mod_u64(a.rem_small(p), p) as u128;
+        let b_mod = b.rem_small(p);
+        let t = fb.sqrt[j];
+        r1[j] = (a_inv * ((p + t - b_mod) % p) as u128 % p as u128) as u64;
+        r2[j] = (a_inv * ((2 * p - t - b_mod) % p) as u128 % p as u128) as u64;
+        for l in 0..s {
+            deltas[l][j] = (2 * a_inv * b_parts[l].rem_small(p) as u128 % p as u128) as u64;
+        }
+    }
+
+    let mut b_sgn = vec![1; s];
+    for i in 0..(1_usize << (s - 1)) {
+        if i > 0 {
+            // Gray code step: flip the sign of B_l
+            let l = i.trailing_zeros() as usize + 1;
+            let twice = &b_parts[l] + &b_parts[l];
+            b_sgn[l] = -b_sgn[l];
+            if b_sgn[l] == -1 {
+                b = b - twice;
+                for j in 1..f {
+                    let p = fb.primes[j];
+                    r1[j] = (r1[j] + deltas[l][j]) % p;
+                    r2[j] = (r2[j] + deltas[l][j]) % p;
+                }
+            } else {
+                b = b + twice;
+                for j in 1..f {
+                    let p = fb.primes[j];
+                    r1[j] = (r1[j] + p - deltas[l][j]) % p;
+                    r2[j] = (r2[j] + p - deltas[l][j]) % p;
+                }
+            }
+        }
+        let (c, rem) = divmod(&(&b * &b - kn), &a);
+        debug_assert!(rem.sgn == 0);
+
+        let mut sieve = vec![0_u8; 2 * m];
+        for j in 1..f {
+            if !sieved[j] {
+                continue;
+            }
+            let p = fb.primes[j];
+            let logp = fb.logp[j];
+            let offset = m as u64 % p;
+            for root in [r1[j], r2[j]] {
+                let mut pos = ((root + offset) % p) as usize;
+                while pos < 2 * m {
+                    sieve[pos] = sieve[pos].wrapping_add(logp);
+                    pos += p as usize;
+                }
+                if r1[j] == r2[j] {
+                    break;
+                }
+            }
+        }
+
+        for (pos, &logs) in sieve.iter().enumerate() {
+            if logs < threshold {
+                continue;
+            }
+            let x = pos as i64 - m as i64;
+            let x_bigint = BigInt::from_i64(x);
+            // Q(x) / A = A x^2 + 2 B x + C and (Ax + B)^2 = A * Q(x) / A mod n
+            let mut v = (&a * &x_bigint + &b + &b) * &x_bigint + &c;
+            if v.sgn == 0 {
+                continue;
+            }
+            let mut factors: Vec<usize> = a_idx.iter().map(|&j| j + 1).collect();
+            if v.sgn == -1 {
+                factors.push(0);
+                v.sgn = 1;
+            }
+            let z = v.trailing_zeros();
+            v = v >> BigInt::from_u64(z as u64);
+            factors.extend(std::iter::repeat_n(1, z));
+            for j in 1..f {
+                let p = fb.primes[j];
+                let divides = if sieved[j] {
+                    let xm = x.rem_euclid(p as i64) as u64;
+                    xm == r1[j] || xm == r2[j]
+                } else {
+                    v.rem_small(p) == 0
+                };
+                if !divides {
+                    continue;
+                }
+                loop {
+                    let (q, r) = v.divmod_small(p);
+                    if r != 0 {
+                        break;
+                    }
+                    v = q;
+                    factors.push(j + 1);
+                }
+            }
+
+            let relation = Relation {
+                y: (&a * &x_bigint + &b) % n,
+                factors,
+                extra: BigInt::from_u64(1),
+            };
+            match v.to_u64() {
+                Some(1) => relations.push(relation),
+                Some(large) if large < large_prime_bound => {
+                    if let Some(other) = partials.get(&large) {
+                        let mut factors = other.factors.clone();
+                        factors.extend(relation.factors.iter());
+                        relations.push(Relation {
+                            y: (&other.y * &relation.y) % n,
+                            factors,
+                            extra: BigInt::from_u64(large),
+                        });
+                    } else {
+                        partials.insert(large, relation);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// finds dependencies among the relations and tries each for a nontrivial factor
+fn combine(n: &BigInt, fb: &FactorBase, relations: &[Relation]) -> Option<BigInt> {
+    let cols = fb.primes.len() + 1;
+    let parity: Vec<Vec<usize>> = relations
+        .iter()
+        .map(|r| {
+            let mut counts = HashMap::new();
+            for &c in &r.factors {
+                *counts.entry(c).or_insert(0) += 1;
+            }
+            counts
+                .into_iter()
+                .filter(|&(_, e)| e % 2 == 1)
+                .map(|(c, _)| c)
+                .collect()
+        })
+        .collect();
+
+    for dependency in gf2_dependencies(&parity, cols) {
+        let mut x = BigInt::from_u64(1);
+        let mut y = BigInt::from_u64(1);
+        let mut exponents = vec![0_u64; cols];
+        for &i in &dependency {
+            x = (x * &relations[i].y) % n;
+            y = (y * &relations[i].extra) % n;
+            for &c in &relations[i].factors {
+                exponents[c] += 1;
+            }
+        }
+        for (c, &e) in exponents.iter().enumerate().skip(1) {
+            if e > 0 {
+                y = y * pow_mod(
+                    &BigInt::from_u64(fb.primes[c - 1]),
+                    &BigInt::from_u64(e / 2),
+                    n,
+                ) % n;
+            }
+        }
+        let g = gcd(&(x - y), n);
+        if g != BigInt::from_u64(1) && g != *n {
+            return Some(g);
+        }
+    }
+    None
+}
+
+// Gaussian elimination over GF(2): each row lists the columns holding a 1, and the result
+// is a list of sets of rows that sum to zero
+fn gf2_dependencies(rows: &[Vec<usize>], cols: usize) -> Vec<Vec<usize>> {
+    let r = rows.len();
+    let col_words = cols.div_ceil(64);
+    let row_words = r.div_ceil(64);
+    let mut matrix: Vec<Vec<u64>> = rows
+        .iter()
+        .map(|row| {
+            let mut bits = vec![0_u64; col_words];
+            for &c in row {
+                bits[c / 64] |= 1 << (c % 64);
+            }
+            bits
+        })
+        .collect();
+    // history[i] records which of the original rows were added into row i
+    let mut history: Vec<Vec<u64>> = (0..r)
+        .map(|i| {
+            let mut bits = vec![0_u64; row_words];
+            bits[i / 64] |= 1 << (i % 64);
+            bits
+        })
+        .collect();
+
+    let mut rank = 0;
+    for c in 0..cols {
+        let (word, bit) = (c / 64, 1 << (c % 64));
+        let Some(pivot) = (rank..r).find(|&i| matrix[i][word] & bit != 0) else {
+            continue;
+        };
+        matrix.swap(rank, pivot);
+        history.swap(rank, pivot);
+        let (pivot_row, pivot_history) = (matrix[rank].clone(), history[rank].clone());
+        for i in 0..r {
+            if i != rank && matrix[i][word] & bit != 0 {
+                for (a, b) in matrix[i].iter_mut().zip(pivot_row.iter()) {
+                    *a ^= b;
+                }
+                for (a, b) in history[i].iter_mut().zip(pivot_history.iter()) {
+                    *a ^= b;
+                }
+            }
+        }
+        rank += 1;
+    }
+
+    history[rank..]
+        .iter()
+        .map(|h| (0..r).filter(|&i| h[i / 64] >> (i % 64) & 1 == 1).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mparith::build_bigint;
+    use std::collections::HashSet;
+
+    #[test]
+    fn choose_a_test() {
+        // 11, 13 and 17 are the only primes allowed in A, so only a few values of A exist
+        let fb = super::FactorBase {
+            primes: vec![2, 3, 5, 7, 11, 13, 17],
+            sqrt: vec![1; 7],
+            logp: vec![1; 7],
+        };
+        let mut rng = super::XorShift(0x2545_f491_4f6c_dd1d);
+        let mut used = HashSet::new();
+        let mut count = 0;
+        while let Some(a_idx) = super::choose_a(&fb, 7.5, &mut rng, &mut used) {
+            assert!(a_idx.iter().all(|&j| j >= 4));
+            count += 1;
+        }
+        assert!(count > 0 && count == used.len() && count <= 3);
+        // A needs more primes than there are
+        assert_eq!(
+            None,
+            super::choose_a(&fb, 40.0, &mut rng, &mut HashSet::new())
+        );
+    }
+
+    #[test]
+    fn gf2_dependencies_test() {
+        let rows = vec![vec![0, 1], vec![1, 2], vec![0, 2], vec![3], vec![0, 1, 3]];
+        let deps = super::gf2_dependencies(&rows, 4);
+        assert_eq!(2, deps.len());
+        for dep in deps {
+            let mut sum = [0; 4];
+            for i in dep {
+                for &c in &rows[i] {
+                    sum[c] ^= 1;
+                }
+            }
+            assert_eq!([0; 4], sum);
+        }
+    }
+
+    #[test]
+    fn siqs_test() {
+        for (p, q) in [
+            ("1000000007", "1000000000039"),
+            ("100000000003", "1000000000000037"),
+            ("10000000000000061", "100000000000000003"),
+        ] {
+            let p = build_bigint(p);
+            let q = build_bigint(q);
+            let d = super::siqs(&(&p * &q)).unwrap();
+            assert!(d == p || d == q);
+        }
+    }
+}